@@ -0,0 +1,27 @@
+CREATE TABLE sync_runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    source TEXT NOT NULL,
+    scope TEXT NOT NULL,
+    started_at TEXT NOT NULL,
+    finished_at TEXT,
+    status TEXT NOT NULL CHECK (status IN ('running', 'completed', 'failed')) DEFAULT 'running'
+);
+
+CREATE TABLE codebase_snapshots (
+    run_id INTEGER NOT NULL,
+    codebase_id INTEGER NOT NULL,
+    path TEXT NOT NULL,
+    pushed_at TEXT NOT NULL,
+    private BOOLEAN NOT NULL CHECK (private IN (0, 1)),
+    archived BOOLEAN NOT NULL CHECK (archived IN (0, 1)),
+    size INTEGER NOT NULL,
+    commit_count INTEGER NOT NULL,
+    languages TEXT NOT NULL DEFAULT '{}',
+    PRIMARY KEY (run_id, codebase_id),
+    FOREIGN KEY (run_id) REFERENCES sync_runs (id) ON DELETE CASCADE
+    -- No foreign key on codebase_id: snapshots outlive pruned codebases so
+    -- that diffs still show them deleted, and codebase ids are never reused
+);
+
+CREATE INDEX idx_sync_runs_source_scope ON sync_runs (source, scope);
+CREATE INDEX idx_codebase_snapshots_codebase_id ON codebase_snapshots (codebase_id);
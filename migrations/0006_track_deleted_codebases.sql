@@ -0,0 +1,6 @@
+ALTER TABLE codebases ADD COLUMN deleted_at TEXT;
+
+ALTER TABLE codebase_snapshots
+ADD COLUMN deleted BOOLEAN NOT NULL CHECK (deleted IN (0, 1)) DEFAULT 0;
+
+CREATE INDEX idx_codebases_source_path ON codebases (source, path);
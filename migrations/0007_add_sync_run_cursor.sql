@@ -0,0 +1 @@
+ALTER TABLE sync_runs ADD COLUMN end_cursor TEXT;
@@ -0,0 +1,4 @@
+ALTER TABLE sync_runs
+ADD COLUMN kind TEXT NOT NULL CHECK (kind IN ('full', 'incremental')) DEFAULT 'full';
+
+ALTER TABLE sync_runs ADD COLUMN watermark TEXT;
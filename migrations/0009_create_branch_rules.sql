@@ -0,0 +1,28 @@
+ALTER TABLE codebases ADD COLUMN default_branch TEXT;
+
+-- Whether the last sync read every branch rule of the codebase
+ALTER TABLE codebases
+ADD COLUMN branch_rules_known BOOLEAN CHECK (branch_rules_known IN (0, 1));
+
+CREATE TABLE branch_rules (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    codebase_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    is_default BOOLEAN NOT NULL CHECK (is_default IN (0, 1)),
+    is_protected BOOLEAN NOT NULL CHECK (is_protected IN (0, 1)),
+    allow_force_push BOOLEAN NOT NULL CHECK (allow_force_push IN (0, 1)),
+    code_owner_approval_required BOOLEAN NOT NULL CHECK (code_owner_approval_required IN (0, 1)),
+    UNIQUE (codebase_id, name),
+    FOREIGN KEY (codebase_id) REFERENCES codebases (id) ON DELETE CASCADE
+);
+
+CREATE TABLE branch_rule_access_levels (
+    branch_rule_id INTEGER NOT NULL,
+    action TEXT NOT NULL CHECK (action IN ('push', 'merge')),
+    access_level INTEGER NOT NULL,
+    description TEXT NOT NULL,
+    FOREIGN KEY (branch_rule_id) REFERENCES branch_rules (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_branch_rule_access_levels_branch_rule_id
+ON branch_rule_access_levels (branch_rule_id);
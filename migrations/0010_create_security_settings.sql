@@ -0,0 +1,8 @@
+CREATE TABLE codebase_security_settings (
+    codebase_id INTEGER PRIMARY KEY,
+    approvals_required INTEGER,
+    secret_push_protection BOOLEAN CHECK (secret_push_protection IN (0, 1)),
+    dependency_scanning BOOLEAN CHECK (dependency_scanning IN (0, 1)),
+    pipeline_must_succeed BOOLEAN CHECK (pipeline_must_succeed IN (0, 1)),
+    FOREIGN KEY (codebase_id) REFERENCES codebases (id) ON DELETE CASCADE
+);
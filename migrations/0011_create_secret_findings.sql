@@ -0,0 +1,16 @@
+ALTER TABLE codebases ADD COLUMN clone_url TEXT;
+
+CREATE TABLE secret_findings (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    codebase_id INTEGER NOT NULL,
+    rule_id TEXT NOT NULL,
+    file TEXT NOT NULL,
+    line INTEGER NOT NULL,
+    commit_sha TEXT,
+    secret TEXT NOT NULL,
+    fingerprint TEXT NOT NULL,
+    scanned_at TEXT NOT NULL,
+    FOREIGN KEY (codebase_id) REFERENCES codebases (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_secret_findings_codebase_id ON secret_findings (codebase_id);
@@ -0,0 +1,8 @@
+CREATE TABLE mirrors (
+    codebase_id INTEGER PRIMARY KEY,
+    dir TEXT NOT NULL,
+    head_commit TEXT,
+    size INTEGER NOT NULL,
+    fetched_at TEXT NOT NULL,
+    FOREIGN KEY (codebase_id) REFERENCES codebases (id) ON DELETE CASCADE
+);
@@ -0,0 +1,19 @@
+CREATE TABLE dependencies (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    codebase_id INTEGER NOT NULL,
+    ecosystem TEXT NOT NULL,
+    name TEXT NOT NULL,
+    version TEXT,
+    manifest TEXT NOT NULL,
+    FOREIGN KEY (codebase_id) REFERENCES codebases (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_dependencies_codebase_id ON dependencies (codebase_id);
+CREATE INDEX idx_dependencies_name ON dependencies (name COLLATE NOCASE, ecosystem);
+
+CREATE TABLE dependency_scans (
+    codebase_id INTEGER PRIMARY KEY,
+    head_commit TEXT NOT NULL,
+    scanned_at TEXT NOT NULL,
+    FOREIGN KEY (codebase_id) REFERENCES codebases (id) ON DELETE CASCADE
+);
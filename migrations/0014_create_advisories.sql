@@ -0,0 +1,20 @@
+CREATE TABLE advisories (
+    id TEXT PRIMARY KEY,
+    summary TEXT NOT NULL,
+    aliases TEXT NOT NULL,
+    severity TEXT NOT NULL,
+    imported_at TEXT NOT NULL
+);
+
+CREATE TABLE advisory_packages (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    advisory_id TEXT NOT NULL,
+    ecosystem TEXT NOT NULL,
+    name TEXT NOT NULL,
+    ranges TEXT NOT NULL,
+    versions TEXT NOT NULL,
+    FOREIGN KEY (advisory_id) REFERENCES advisories (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_advisory_packages_advisory_id ON advisory_packages (advisory_id);
+CREATE INDEX idx_advisory_packages_name ON advisory_packages (name, ecosystem);
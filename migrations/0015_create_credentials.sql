@@ -0,0 +1,21 @@
+-- Access tokens, deploy keys and deploy tokens of GitLab projects and groups.
+-- Project credentials belong to a codebase, group credentials only to a path.
+CREATE TABLE credentials (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    source TEXT NOT NULL,
+    owner_path TEXT NOT NULL,
+    web_url TEXT NOT NULL,
+    codebase_id INTEGER,
+    kind TEXT NOT NULL CHECK (kind IN ('access_token', 'deploy_key', 'deploy_token')),
+    external_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    scopes TEXT NOT NULL DEFAULT '[]',
+    created_at TEXT,
+    expires_at TEXT,
+    last_used_at TEXT,
+    collected_at TEXT NOT NULL,
+    FOREIGN KEY (codebase_id) REFERENCES codebases (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_credentials_codebase_id ON credentials (codebase_id);
+CREATE INDEX idx_credentials_owner ON credentials (source, owner_path);
@@ -0,0 +1,495 @@
+use std::{
+    collections::BTreeMap,
+    io::{IsTerminal, Read},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use argon2::Argon2;
+use chacha20poly1305::{
+    KeyInit, XChaCha20Poly1305, XNonce,
+    aead::{Aead, Payload},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::AppError,
+    settings::{TokenConfig, config_path},
+};
+
+const SECRETS_FILE: &str = "secrets.toml";
+const PASSPHRASE_ENV: &str = "DEVSEC_PASSPHRASE";
+
+/// Token options of an `update` command.
+pub struct TokenArgs {
+    /// `--auth`
+    pub auth: Option<String>,
+    pub file: Option<PathBuf>,
+    pub stdin: bool,
+}
+
+/// Token of a service such as `gitlab`, from the command line, else from the
+/// profile, else from `GITLAB_TOKEN` or the like. Errors name where the token
+/// was looked for, never the token.
+pub fn resolve_token(
+    service: &str,
+    args: TokenArgs,
+    config: &TokenConfig,
+) -> Result<String, AppError> {
+    let default_var = format!("{}_TOKEN", service.to_uppercase());
+
+    if args.auth.is_some() {
+        eprintln!(
+            "--auth is deprecated as it leaves the token in shell history and process listings, use --token-file, --token-stdin or a token setting instead"
+        );
+    }
+
+    if args.stdin {
+        return read_stdin("Token: ").and_then(|token| non_empty(token, "stdin"));
+    }
+    if let Some(path) = &args.file {
+        return read_file(path);
+    }
+    if let Some(token) = args.auth.filter(|token| !token.is_empty()) {
+        return Ok(token);
+    }
+
+    if let Some(var) = &config.token_env
+        && let Some(token) = std::env::var(var).ok().filter(|token| !token.is_empty())
+    {
+        return Ok(token);
+    }
+    if let Some(path) = &config.token_file {
+        return read_file(path);
+    }
+    if let Some(command) = &config.token_command {
+        return run_command(&format!("{service}.token_command"), command);
+    }
+    if let Some(name) = &config.token_secret {
+        return load_secret(name);
+    }
+
+    // Last, a variable set for another instance must not win over the profile
+    if let Some(token) = std::env::var(&default_var)
+        .ok()
+        .filter(|token| !token.is_empty())
+    {
+        return Ok(token);
+    }
+
+    Err(AppError::AuthError(format!(
+        "no {service} token, pass --token-file or --token-stdin, or set a {service}.token_* setting or {default_var}"
+    )))
+}
+
+fn read_file(path: &Path) -> Result<String, AppError> {
+    let token = std::fs::read_to_string(path).map_err(|err| {
+        AppError::AuthError(format!(
+            "could not read token file {}: {err}",
+            path.display()
+        ))
+    })?;
+    non_empty(token, &path.display().to_string())
+}
+
+/// Runs a credential helper through the shell and takes the first line it
+/// prints. Its stderr stays attached so helpers like `pass` can prompt.
+fn run_command(setting: &str, command: &str) -> Result<String, AppError> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    };
+
+    let output = shell
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|err| AppError::AuthError(format!("could not run {setting}: {err}")))?;
+    if !output.status.success() {
+        return Err(AppError::AuthError(format!(
+            "{setting} failed with {}",
+            output.status
+        )));
+    }
+
+    let stdout = String::from_utf8(output.stdout)
+        .map_err(|_| AppError::AuthError(format!("{setting} printed invalid UTF-8")))?;
+    non_empty(
+        stdout.lines().next().unwrap_or_default().to_string(),
+        setting,
+    )
+}
+
+fn non_empty(token: String, source: &str) -> Result<String, AppError> {
+    let token = token.trim();
+    if token.is_empty() {
+        return Err(AppError::AuthError(format!("no token in {source}")));
+    }
+    Ok(token.to_string())
+}
+
+/// Reads a line from stdin, without echoing it if stdin is a terminal.
+fn read_stdin(prompt: &str) -> Result<String, AppError> {
+    if std::io::stdin().is_terminal() {
+        return Ok(rpassword::prompt_password(prompt)?);
+    }
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+    Ok(input)
+}
+
+/// Tokens encrypted with a key derived from a passphrase (Argon2id) using
+/// XChaCha20-Poly1305, stored next to the config file. Values are hex encoded.
+#[derive(Serialize, Deserialize, Default)]
+struct SecretStore {
+    #[serde(flatten)]
+    secrets: BTreeMap<String, EncryptedSecret>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EncryptedSecret {
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+pub fn secrets_path() -> Result<PathBuf, AppError> {
+    let config_path = config_path()?;
+    let dir = config_path.parent().unwrap_or(Path::new("."));
+    Ok(dir.join(SECRETS_FILE))
+}
+
+/// Encrypts the token read from stdin into the secrets file under `name`. An
+/// empty token removes the secret.
+pub fn store_secret(name: &str) -> Result<PathBuf, AppError> {
+    let path = secrets_path()?;
+    let mut store = read_store(&path)?;
+
+    let token = read_stdin("Token: ")?;
+    let token = token.trim();
+    if token.is_empty() {
+        if store.secrets.remove(name).is_none() {
+            return Err(AppError::NotFound(format!("secret {name}")));
+        }
+        write_store(&path, &store)?;
+        return Ok(path);
+    }
+
+    let secret = encrypt_secret(name, token, &passphrase(true)?)?;
+    store.secrets.insert(name.to_string(), secret);
+    write_store(&path, &store)?;
+    Ok(path)
+}
+
+/// Encrypts `token` with the name as associated data, so that a secret copied
+/// to another name does not decrypt.
+fn encrypt_secret(name: &str, token: &str, passphrase: &str) -> Result<EncryptedSecret, AppError> {
+    let salt: [u8; 16] = rand::random();
+    let nonce: [u8; 24] = rand::random();
+    let cipher = cipher(passphrase, &salt)?;
+    let ciphertext = cipher
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: token.as_bytes(),
+                aad: name.as_bytes(),
+            },
+        )
+        .map_err(|_| AppError::AuthError(format!("could not encrypt secret {name}")))?;
+
+    Ok(EncryptedSecret {
+        salt: to_hex(&salt),
+        nonce: to_hex(&nonce),
+        ciphertext: to_hex(&ciphertext),
+    })
+}
+
+fn load_secret(name: &str) -> Result<String, AppError> {
+    let path = secrets_path()?;
+    let store = read_store(&path)?;
+    let secret = store
+        .secrets
+        .get(name)
+        .ok_or_else(|| AppError::AuthError(format!("no secret {name} in {}", path.display())))?;
+
+    let invalid = || AppError::AuthError(format!("secret {name} in {} is corrupt", path.display()));
+    let salt = from_hex(&secret.salt).ok_or_else(invalid)?;
+    let nonce = from_hex(&secret.nonce).ok_or_else(invalid)?;
+    let ciphertext = from_hex(&secret.ciphertext).ok_or_else(invalid)?;
+    if nonce.len() != 24 {
+        return Err(invalid());
+    }
+
+    let cipher = cipher(&passphrase(false)?, &salt)?;
+    let token = cipher
+        .decrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: &ciphertext,
+                aad: name.as_bytes(),
+            },
+        )
+        .map_err(|_| {
+            AppError::AuthError(format!(
+                "could not decrypt secret {name}, wrong passphrase?"
+            ))
+        })?;
+
+    String::from_utf8(token).map_err(|_| invalid())
+}
+
+fn cipher(passphrase: &str, salt: &[u8]) -> Result<XChaCha20Poly1305, AppError> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|err| AppError::AuthError(format!("could not derive key: {err}")))?;
+    Ok(XChaCha20Poly1305::new(&key.into()))
+}
+
+/// Passphrase of the secrets file from DEVSEC_PASSPHRASE, else prompted for.
+fn passphrase(confirm: bool) -> Result<String, AppError> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+
+    let no_terminal = |_| {
+        AppError::AuthError(format!(
+            "no passphrase, set {PASSPHRASE_ENV} or run in a terminal"
+        ))
+    };
+    let passphrase = rpassword::prompt_password("Passphrase: ").map_err(no_terminal)?;
+    if confirm
+        && rpassword::prompt_password("Repeat passphrase: ").map_err(no_terminal)? != passphrase
+    {
+        return Err(AppError::AuthError("passphrases don't match".to_string()));
+    }
+    Ok(passphrase)
+}
+
+fn read_store(path: &Path) -> Result<SecretStore, AppError> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => toml::from_str(&content).map_err(|err| {
+            AppError::InvalidConfig(format!("{}: {}", path.display(), err.message()))
+        }),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(SecretStore::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Writes the secrets file readable by the owner only.
+fn write_store(path: &Path, store: &SecretStore) -> Result<(), AppError> {
+    let content = toml::to_string(store).map_err(|err| AppError::InvalidConfig(err.to_string()))?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // `mode` only applies to new files
+        if path.exists() {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        }
+    }
+
+    use std::io::Write;
+    options.open(path)?.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(auth: Option<&str>, file: Option<&Path>) -> TokenArgs {
+        TokenArgs {
+            auth: auth.map(str::to_string),
+            file: file.map(Path::to_path_buf),
+            stdin: false,
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("devsec-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        let bytes = [0x00, 0x0f, 0xa5, 0xff];
+        assert_eq!(to_hex(&bytes), "000fa5ff");
+        assert_eq!(from_hex("000fa5ff").unwrap(), bytes);
+        assert_eq!(from_hex("000FA5FF").unwrap(), bytes);
+        assert_eq!(from_hex("").unwrap(), Vec::<u8>::new());
+        assert_eq!(from_hex("abc"), None);
+        assert_eq!(from_hex("zz"), None);
+        assert_eq!(from_hex("é"), None);
+    }
+
+    #[test]
+    fn token_precedence() {
+        let dir = temp_dir("token-precedence");
+        let file = dir.join("token");
+        std::fs::write(&file, "file-token\n").unwrap();
+        let config = |configure: fn(&mut TokenConfig)| {
+            let mut config = TokenConfig::default();
+            configure(&mut config);
+            config
+        };
+        let resolve = |args, config: &TokenConfig| resolve_token("devsectest", args, config);
+
+        // SAFETY: no other test reads these variables
+        unsafe {
+            std::env::set_var("DEVSECTEST_TOKEN", "default-token");
+            std::env::set_var("DEVSECTEST_PROFILE_TOKEN", "env-token");
+            std::env::set_var("DEVSECTEST_EMPTY_TOKEN", "");
+        }
+
+        let everything = TokenConfig {
+            token_env: Some("DEVSECTEST_PROFILE_TOKEN".to_string()),
+            token_file: Some(dir.join("missing")),
+            token_command: Some("exit 1".to_string()),
+            token_secret: Some("missing".to_string()),
+        };
+        assert_eq!(
+            resolve(args(Some("arg-token"), Some(&file)), &everything).unwrap(),
+            "file-token"
+        );
+        assert_eq!(
+            resolve(args(Some("arg-token"), None), &everything).unwrap(),
+            "arg-token"
+        );
+        assert_eq!(
+            resolve(args(Some(""), None), &everything).unwrap(),
+            "env-token"
+        );
+
+        // An empty variable falls through to the next setting
+        let file_config = TokenConfig {
+            token_env: Some("DEVSECTEST_EMPTY_TOKEN".to_string()),
+            token_file: Some(file.clone()),
+            ..everything.clone()
+        };
+        assert_eq!(
+            resolve(args(None, None), &file_config).unwrap(),
+            "file-token"
+        );
+
+        let command_config = config(|config| {
+            config.token_command = Some("echo command-token && echo second-line".to_string());
+            config.token_secret = Some("missing".to_string());
+        });
+        assert_eq!(
+            resolve(args(None, None), &command_config).unwrap(),
+            "command-token"
+        );
+
+        assert_eq!(
+            resolve(args(None, None), &TokenConfig::default()).unwrap(),
+            "default-token"
+        );
+
+        unsafe { std::env::remove_var("DEVSECTEST_TOKEN") };
+        assert_eq!(
+            resolve(args(None, None), &TokenConfig::default())
+                .unwrap_err()
+                .to_string(),
+            "Authentication error: no devsectest token, pass --token-file or --token-stdin, or set a devsectest.token_* setting or DEVSECTEST_TOKEN"
+        );
+        assert_eq!(
+            resolve(
+                args(None, None),
+                &config(|config| config.token_command = Some("exit 3".to_string()))
+            )
+            .unwrap_err()
+            .to_string(),
+            "Authentication error: devsectest.token_command failed with exit status: 3"
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn secrets_are_bound_to_their_name() {
+        let dir = temp_dir("secrets");
+        // SAFETY: no other test reads these variables
+        unsafe {
+            std::env::set_var("XDG_CONFIG_HOME", &dir);
+            std::env::set_var(PASSPHRASE_ENV, "correct horse");
+        }
+
+        let path = secrets_path().unwrap();
+        assert!(path.starts_with(&dir));
+
+        let token = ["glpat-", "Xy3vQ9rT2kLm8NpW4sZa"].concat();
+        let secret = encrypt_secret("gitlab", &token, "correct horse").unwrap();
+        let copied = EncryptedSecret {
+            salt: secret.salt.clone(),
+            nonce: secret.nonce.clone(),
+            ciphertext: secret.ciphertext.clone(),
+        };
+        let other_passphrase = encrypt_secret("github", &token, "battery staple").unwrap();
+        let mut store = SecretStore::default();
+        store.secrets.insert("gitlab".to_string(), secret);
+        store.secrets.insert("copied".to_string(), copied);
+        store.secrets.insert("github".to_string(), other_passphrase);
+        write_store(&path, &store).unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let config = TokenConfig {
+            token_secret: Some("gitlab".to_string()),
+            ..TokenConfig::default()
+        };
+        assert_eq!(
+            resolve_token("gitlab", args(None, None), &config).unwrap(),
+            token
+        );
+
+        assert_eq!(
+            load_secret("copied").unwrap_err().to_string(),
+            "Authentication error: could not decrypt secret copied, wrong passphrase?"
+        );
+        assert_eq!(
+            load_secret("github").unwrap_err().to_string(),
+            "Authentication error: could not decrypt secret github, wrong passphrase?"
+        );
+        assert_eq!(
+            load_secret("gitea").unwrap_err().to_string(),
+            format!(
+                "Authentication error: no secret gitea in {}",
+                path.display()
+            )
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
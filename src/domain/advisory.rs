@@ -0,0 +1,636 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tabled::Tabled;
+
+use super::{
+    dependency::{Ecosystem, normalize_pypi_name},
+    policy::Severity,
+};
+use crate::infrastructure::utils::{repositories::display_optional, sarif::Finding};
+
+/// An advisory in the OSV schema, as published in the per-ecosystem dumps of
+/// osv.dev. Only the fields needed to match package versions are read.
+#[derive(Deserialize, Debug)]
+pub struct OsvAdvisory {
+    pub id: String,
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub details: Option<String>,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub withdrawn: Option<String>,
+    #[serde(default)]
+    pub severity: Vec<OsvSeverity>,
+    #[serde(default)]
+    pub affected: Vec<OsvAffected>,
+    #[serde(default)]
+    pub database_specific: Option<Value>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OsvSeverity {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub score: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OsvAffected {
+    pub package: Option<OsvPackage>,
+    #[serde(default)]
+    pub ranges: Vec<OsvRange>,
+    #[serde(default)]
+    pub versions: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OsvPackage {
+    pub ecosystem: String,
+    pub name: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OsvRange {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub events: Vec<RangeEvent>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RangeEvent {
+    Introduced(String),
+    Fixed(String),
+    LastAffected(String),
+    Limit(String),
+}
+
+impl RangeEvent {
+    fn version(&self) -> &str {
+        match self {
+            RangeEvent::Introduced(version)
+            | RangeEvent::Fixed(version)
+            | RangeEvent::LastAffected(version)
+            | RangeEvent::Limit(version) => version,
+        }
+    }
+}
+
+/// An advisory reduced to the packages of supported ecosystems.
+#[derive(Debug)]
+pub struct NewAdvisory {
+    pub id: String,
+    pub summary: String,
+    pub aliases: Vec<String>,
+    pub severity: Severity,
+    pub packages: Vec<AffectedPackage>,
+}
+
+/// Versions of a package an advisory affects: any version in one of its
+/// ranges or listed explicitly.
+#[derive(Debug, Clone)]
+pub struct AffectedPackage {
+    pub ecosystem: Ecosystem,
+    pub name: String,
+    /// Events of every `SEMVER` and `ECOSYSTEM` range, one list per range
+    pub ranges: Vec<Vec<RangeEvent>>,
+    pub versions: Vec<String>,
+}
+
+impl AffectedPackage {
+    pub fn affects(&self, version: &str) -> bool {
+        self.versions
+            .iter()
+            .any(|affected| compare_versions(self.ecosystem, affected, version).is_eq())
+            || self
+                .ranges
+                .iter()
+                .any(|events| range_affects(self.ecosystem, events, version))
+    }
+
+    /// Lowest version fixing the advisory above `version`, if any is known.
+    pub fn fixed_after(&self, version: &str) -> Option<String> {
+        self.ranges
+            .iter()
+            .flatten()
+            .filter_map(|event| match event {
+                RangeEvent::Fixed(fixed)
+                    if compare_versions(self.ecosystem, fixed, version).is_gt() =>
+                {
+                    Some(fixed)
+                }
+                _ => None,
+            })
+            .min_by(|a, b| compare_versions(self.ecosystem, a, b))
+            .cloned()
+    }
+}
+
+/// Whether `version` falls in a range, evaluating its events in version
+/// order as described in the OSV schema.
+fn range_affects(ecosystem: Ecosystem, events: &[RangeEvent], version: &str) -> bool {
+    let mut events: Vec<&RangeEvent> = events.iter().collect();
+    events.sort_by(|a, b| compare_versions(ecosystem, a.version(), b.version()));
+
+    let mut affected = false;
+    for event in events {
+        let order = compare_versions(ecosystem, version, event.version());
+        match event {
+            RangeEvent::Introduced(_) if order.is_ge() => affected = true,
+            RangeEvent::Fixed(_) | RangeEvent::Limit(_) if order.is_ge() => affected = false,
+            RangeEvent::LastAffected(_) if order.is_gt() => affected = false,
+            _ => {}
+        }
+    }
+    affected
+}
+
+impl OsvAdvisory {
+    /// Reduces the advisory to the packages devsec can match, or `None` if it
+    /// affects none of them.
+    pub fn into_advisory(self) -> Option<NewAdvisory> {
+        let severity = self.rated_severity();
+
+        let packages: Vec<AffectedPackage> = self
+            .affected
+            .into_iter()
+            .filter_map(|affected| {
+                let package = affected.package?;
+                let ecosystem: Ecosystem = package.ecosystem.parse().ok()?;
+                let name = match ecosystem {
+                    Ecosystem::PyPI => normalize_pypi_name(&package.name),
+                    _ => package.name,
+                };
+                // Git ranges are commit hashes, not versions
+                let ranges: Vec<Vec<RangeEvent>> = affected
+                    .ranges
+                    .into_iter()
+                    .filter(|range| range.kind == "SEMVER" || range.kind == "ECOSYSTEM")
+                    .map(|range| range.events)
+                    .collect();
+                if ranges.is_empty() && affected.versions.is_empty() {
+                    return None;
+                }
+
+                Some(AffectedPackage {
+                    ecosystem,
+                    name,
+                    ranges,
+                    versions: affected.versions,
+                })
+            })
+            .collect();
+
+        if packages.is_empty() {
+            return None;
+        }
+
+        let summary = self
+            .summary
+            .or_else(|| {
+                self.details
+                    .and_then(|details| details.lines().next().map(str::to_string))
+            })
+            .unwrap_or_default();
+
+        Some(NewAdvisory {
+            id: self.id,
+            summary,
+            aliases: self.aliases,
+            severity,
+            packages,
+        })
+    }
+
+    /// Severity from the CVSS v3 vector, else the rating of the database the
+    /// advisory comes from, e.g. GitHub's `MODERATE`. Unrated advisories are
+    /// unknown.
+    fn rated_severity(&self) -> Severity {
+        let cvss = self
+            .severity
+            .iter()
+            .filter(|severity| severity.kind == "CVSS_V3")
+            .find_map(|severity| cvss_v3_base_score(&severity.score));
+        if let Some(score) = cvss {
+            return severity_of_score(score);
+        }
+
+        let rating = self
+            .database_specific
+            .as_ref()
+            .and_then(|specific| specific.get("severity"))
+            .and_then(Value::as_str)
+            .map(str::to_ascii_lowercase);
+        match rating.as_deref() {
+            Some("low") => Severity::Low,
+            Some("moderate" | "medium") => Severity::Medium,
+            Some("high") => Severity::High,
+            Some("critical") => Severity::Critical,
+            _ => Severity::Unknown,
+        }
+    }
+}
+
+/// Qualitative rating of a CVSS score. Scores of 0 are rated low.
+fn severity_of_score(score: f64) -> Severity {
+    match score {
+        score if score >= 9.0 => Severity::Critical,
+        score if score >= 7.0 => Severity::High,
+        score if score >= 4.0 => Severity::Medium,
+        _ => Severity::Low,
+    }
+}
+
+/// Base score of a CVSS 3.0 or 3.1 vector like
+/// `CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H`.
+fn cvss_v3_base_score(vector: &str) -> Option<f64> {
+    if !vector.starts_with("CVSS:3.") {
+        return None;
+    }
+    let metric = |name: &str| {
+        vector
+            .split('/')
+            .find_map(|part| part.strip_prefix(name)?.strip_prefix(':'))
+    };
+
+    let scope_changed = match metric("S")? {
+        "U" => false,
+        "C" => true,
+        _ => return None,
+    };
+    let attack_vector = match metric("AV")? {
+        "N" => 0.85,
+        "A" => 0.62,
+        "L" => 0.55,
+        "P" => 0.2,
+        _ => return None,
+    };
+    let attack_complexity = match metric("AC")? {
+        "L" => 0.77,
+        "H" => 0.44,
+        _ => return None,
+    };
+    let privileges_required = match (metric("PR")?, scope_changed) {
+        ("N", _) => 0.85,
+        ("L", false) => 0.62,
+        ("L", true) => 0.68,
+        ("H", false) => 0.27,
+        ("H", true) => 0.5,
+        _ => return None,
+    };
+    let user_interaction = match metric("UI")? {
+        "N" => 0.85,
+        "R" => 0.62,
+        _ => return None,
+    };
+    let impact_of = |name: &str| match metric(name)? {
+        "H" => Some(0.56),
+        "L" => Some(0.22),
+        "N" => Some(0.0),
+        _ => None,
+    };
+    let (confidentiality, integrity, availability) =
+        (impact_of("C")?, impact_of("I")?, impact_of("A")?);
+
+    let base = 1.0 - (1.0 - confidentiality) * (1.0 - integrity) * (1.0 - availability);
+    let impact = if scope_changed {
+        7.52 * (base - 0.029) - 3.25 * (base - 0.02f64).powi(15)
+    } else {
+        6.42 * base
+    };
+    if impact <= 0.0 {
+        return Some(0.0);
+    }
+
+    let exploitability =
+        8.22 * attack_vector * attack_complexity * privileges_required * user_interaction;
+    let score = if scope_changed {
+        (1.08 * (impact + exploitability)).min(10.0)
+    } else {
+        (impact + exploitability).min(10.0)
+    };
+    // Rounded up to one decimal
+    Some((score * 10.0).ceil() / 10.0)
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Segment {
+    /// Qualifiers like `alpha` or `rc`, ranked below the release
+    PreRelease(u8, String),
+    /// Qualifiers like `post` or `sp`, ranked above the release
+    PostRelease(String),
+    Number(u64),
+}
+
+impl Segment {
+    fn parse(part: &str) -> Option<Segment> {
+        if let Ok(number) = part.parse() {
+            return Some(Segment::Number(number));
+        }
+
+        let part = part.to_ascii_lowercase();
+        let rank = match part.as_str() {
+            // Mean the release itself in Maven
+            "final" | "ga" | "release" => return None,
+            "post" | "sp" | "patch" | "pl" => return Some(Segment::PostRelease(part)),
+            "dev" | "snapshot" => 0,
+            "alpha" | "a" => 1,
+            "beta" | "b" => 2,
+            "milestone" | "m" => 3,
+            "rc" | "cr" | "c" | "pre" | "preview" => 4,
+            _ => 5,
+        };
+        Some(Segment::PreRelease(rank, part))
+    }
+}
+
+/// Splits a version into numeric and alphabetic segments, dropping build
+/// metadata, RubyGems platforms and trailing zeros of the release.
+fn version_segments(ecosystem: Ecosystem, version: &str) -> Vec<Segment> {
+    let version = version.trim().trim_start_matches(['v', 'V']);
+    let version = version.split('+').next().unwrap_or_default();
+    let version = match ecosystem {
+        // `1.10.0-x86_64-linux`, pre-releases are dotted in RubyGems
+        Ecosystem::RubyGems => version.split('-').next().unwrap_or_default(),
+        _ => version,
+    };
+
+    let mut parts = Vec::new();
+    for part in version.split(|c: char| !c.is_ascii_alphanumeric()) {
+        // `rc1` is `rc.1`
+        let mut start = 0;
+        let chars: Vec<char> = part.chars().collect();
+        for index in 1..=chars.len() {
+            if index == chars.len()
+                || chars[index].is_ascii_digit() != chars[start].is_ascii_digit()
+            {
+                parts.push(chars[start..index].iter().collect::<String>());
+                start = index;
+            }
+        }
+    }
+
+    let mut segments: Vec<Segment> = parts
+        .iter()
+        .filter_map(|part| Segment::parse(part))
+        .collect();
+
+    let release = segments
+        .iter()
+        .position(|segment| !matches!(segment, Segment::Number(_)))
+        .unwrap_or(segments.len());
+    let mut end = release;
+    while end > 1 && segments[end - 1] == Segment::Number(0) {
+        end -= 1;
+    }
+    segments.drain(end..release);
+    segments
+}
+
+/// Orders two versions of a package. Versioning schemes differ between
+/// ecosystems and even packages, this is a lenient ordering agreeing with
+/// SemVer, PEP 440, Maven and RubyGems on common versions.
+pub fn compare_versions(ecosystem: Ecosystem, a: &str, b: &str) -> Ordering {
+    let a = version_segments(ecosystem, a);
+    let b = version_segments(ecosystem, b);
+
+    for index in 0..a.len().max(b.len()) {
+        match (a.get(index), b.get(index)) {
+            (Some(a), Some(b)) => match a.cmp(b) {
+                Ordering::Equal => continue,
+                order => return order,
+            },
+            // `1.0-rc1` is before `1.0`, `1.0.1` and `1.0-sp1` after it
+            (Some(a), None) => {
+                return match a {
+                    Segment::PreRelease(..) => Ordering::Less,
+                    _ => Ordering::Greater,
+                };
+            }
+            (None, Some(b)) => {
+                return match b {
+                    Segment::PreRelease(..) => Ordering::Greater,
+                    _ => Ordering::Less,
+                };
+            }
+            (None, None) => break,
+        }
+    }
+    Ordering::Equal
+}
+
+/// A dependency version an advisory affects.
+#[derive(Tabled, Serialize, Debug)]
+pub struct Vulnerability {
+    pub path: String,
+    #[tabled(rename = "advisory")]
+    pub advisory_id: String,
+    pub severity: Severity,
+    #[tabled(skip)]
+    pub ecosystem: Ecosystem,
+    #[tabled(rename = "package")]
+    pub name: String,
+    pub version: String,
+    #[tabled(display("display_optional"))]
+    pub fixed: Option<String>,
+    pub manifest: String,
+    #[tabled(skip)]
+    pub aliases: Vec<String>,
+    #[tabled(skip)]
+    pub summary: String,
+    #[tabled(skip)]
+    pub web_url: String,
+}
+
+impl Finding for Vulnerability {
+    fn rule_id(&self) -> &str {
+        &self.advisory_id
+    }
+
+    fn rule_description(&self) -> &str {
+        if self.summary.is_empty() {
+            &self.advisory_id
+        } else {
+            &self.summary
+        }
+    }
+
+    fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    fn message(&self) -> String {
+        let fixed = match &self.fixed {
+            Some(fixed) => format!(", fixed in {fixed}"),
+            None => String::new(),
+        };
+        format!(
+            "{}: {} {} in {} is affected by {}{fixed}",
+            self.path, self.name, self.version, self.manifest, self.advisory_id
+        )
+    }
+
+    fn location(&self) -> &str {
+        &self.web_url
+    }
+}
+
+/// Outcome of `advisories import`.
+#[derive(Debug, Default)]
+pub struct AdvisoryImportSummary {
+    pub imported: u64,
+    pub withdrawn: u64,
+    /// Advisories of unsupported ecosystems only
+    pub ignored: u64,
+    pub invalid: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(ecosystem: Ecosystem, events: Vec<RangeEvent>) -> AffectedPackage {
+        AffectedPackage {
+            ecosystem,
+            name: "package".to_string(),
+            ranges: vec![events],
+            versions: Vec::new(),
+        }
+    }
+
+    fn introduced(version: &str) -> RangeEvent {
+        RangeEvent::Introduced(version.to_string())
+    }
+
+    #[test]
+    fn versions_order_numerically() {
+        assert!(compare_versions(Ecosystem::Npm, "1.10.0", "1.9.2").is_gt());
+        assert!(compare_versions(Ecosystem::CratesIo, "0.2", "0.10.1").is_lt());
+        assert!(compare_versions(Ecosystem::PyPI, "2.0", "2.0.0").is_eq());
+        assert!(compare_versions(Ecosystem::Npm, "1.0.0+build.5", "1.0.0").is_eq());
+    }
+
+    #[test]
+    fn pre_releases_come_before_their_release() {
+        assert!(compare_versions(Ecosystem::Npm, "1.0.0-rc.1", "1.0.0").is_lt());
+        assert!(compare_versions(Ecosystem::Npm, "1.0.0-alpha", "1.0.0-beta").is_lt());
+        assert!(compare_versions(Ecosystem::Npm, "1.0.0-rc.1", "1.0.0-rc.2").is_lt());
+        assert!(compare_versions(Ecosystem::PyPI, "1.0rc1", "1.0").is_lt());
+        assert!(compare_versions(Ecosystem::PyPI, "1.0.post1", "1.0").is_gt());
+        assert!(compare_versions(Ecosystem::Maven, "1.0-SNAPSHOT", "1.0-alpha-1").is_lt());
+        assert!(compare_versions(Ecosystem::Maven, "1.0.Final", "1.0").is_eq());
+        assert!(compare_versions(Ecosystem::RubyGems, "7.1.0.beta1", "7.1.0").is_lt());
+        assert!(compare_versions(Ecosystem::RubyGems, "1.16.2-x86_64-linux", "1.16.2").is_eq());
+    }
+
+    #[test]
+    fn go_versions_ignore_the_v_prefix() {
+        assert!(compare_versions(Ecosystem::Go, "v1.2.3", "1.2.3").is_eq());
+        assert!(compare_versions(Ecosystem::Go, "v1.10.0", "v1.9.0").is_gt());
+        assert!(
+            compare_versions(
+                Ecosystem::Go,
+                "v0.0.0-20210101000000-abcdef123456",
+                "v0.1.0"
+            )
+            .is_lt()
+        );
+    }
+
+    #[test]
+    fn introduced_zero_affects_every_version_before_the_fix() {
+        let package = package(
+            Ecosystem::Npm,
+            vec![introduced("0"), RangeEvent::Fixed("1.2.3".to_string())],
+        );
+
+        assert!(package.affects("0.0.1"));
+        assert!(package.affects("1.2.3-rc.1"));
+        assert!(!package.affects("1.2.3"));
+        assert!(!package.affects("2.0.0"));
+        assert_eq!(package.fixed_after("1.0.0"), Some("1.2.3".to_string()));
+    }
+
+    #[test]
+    fn last_affected_includes_its_version() {
+        let package = package(
+            Ecosystem::PyPI,
+            vec![
+                introduced("1.0"),
+                RangeEvent::LastAffected("1.4".to_string()),
+            ],
+        );
+
+        assert!(!package.affects("0.9"));
+        assert!(package.affects("1.0"));
+        assert!(package.affects("1.4"));
+        assert!(!package.affects("1.4.1"));
+        assert_eq!(package.fixed_after("1.2"), None);
+    }
+
+    #[test]
+    fn ranges_can_reopen() {
+        let package = package(
+            Ecosystem::Go,
+            vec![
+                introduced("v1.0.0"),
+                RangeEvent::Fixed("v1.1.0".to_string()),
+                introduced("v2.0.0"),
+                RangeEvent::Fixed("v2.0.5".to_string()),
+            ],
+        );
+
+        assert!(package.affects("1.0.9"));
+        assert!(!package.affects("v1.5.0"));
+        assert!(package.affects("v2.0.4"));
+        assert!(!package.affects("v2.0.5"));
+        assert_eq!(package.fixed_after("v2.0.0"), Some("v2.0.5".to_string()));
+    }
+
+    #[test]
+    fn listed_versions_are_affected() {
+        let package = AffectedPackage {
+            versions: vec!["1.0.0".to_string()],
+            ..package(Ecosystem::CratesIo, Vec::new())
+        };
+
+        assert!(package.affects("1.0"));
+        assert!(!package.affects("1.0.1"));
+    }
+
+    #[test]
+    fn cvss_vectors_are_scored() {
+        let critical = "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H";
+        assert_eq!(cvss_v3_base_score(critical), Some(9.8));
+        assert_eq!(severity_of_score(9.8), Severity::Critical);
+
+        let scope_changed = "CVSS:3.1/AV:N/AC:L/PR:N/UI:R/S:C/C:L/I:L/A:N";
+        assert_eq!(cvss_v3_base_score(scope_changed), Some(6.1));
+        assert_eq!(severity_of_score(6.1), Severity::Medium);
+
+        let local = "CVSS:3.0/AV:L/AC:H/PR:H/UI:R/S:U/C:L/I:N/A:N";
+        assert_eq!(cvss_v3_base_score(local), Some(1.8));
+        assert_eq!(severity_of_score(1.8), Severity::Low);
+
+        let no_impact = "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:N/I:N/A:N";
+        assert_eq!(cvss_v3_base_score(no_impact), Some(0.0));
+    }
+
+    #[test]
+    fn unsupported_cvss_vectors_are_not_scored() {
+        assert_eq!(
+            cvss_v3_base_score("AV:N/AC:L/Au:N/C:P/I:P/A:P"),
+            None,
+            "CVSS v2"
+        );
+        assert_eq!(
+            cvss_v3_base_score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H"),
+            None
+        );
+    }
+}
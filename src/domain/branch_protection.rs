@@ -0,0 +1,172 @@
+use serde::Serialize;
+use sqlx::SqlitePool;
+use tabled::Tabled;
+
+use super::policy::Severity;
+use crate::infrastructure::utils::{repositories::display_optional, sarif::Finding};
+
+/// A GitLab branch rule, i.e. a protected branch name or wildcard pattern.
+#[derive(Debug)]
+pub struct NewBranchRule {
+    pub name: String,
+    /// Whether the rule applies to the default branch
+    pub is_default: bool,
+    pub is_protected: bool,
+    pub allow_force_push: bool,
+    pub code_owner_approval_required: bool,
+    pub push_access_levels: Vec<AccessLevel>,
+    pub merge_access_levels: Vec<AccessLevel>,
+}
+
+/// Who may push or merge, e.g. `40` / `Maintainers`.
+#[derive(Debug)]
+pub struct AccessLevel {
+    pub level: i64,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BranchProtectionIssue {
+    Unprotected,
+    ForcePushAllowed,
+}
+
+impl std::fmt::Display for BranchProtectionIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            BranchProtectionIssue::Unprotected => "unprotected",
+            BranchProtectionIssue::ForcePushAllowed => "force push allowed",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Tabled, Serialize, Debug)]
+pub struct BranchProtectionFinding {
+    pub path: String,
+    pub default_branch: String,
+    pub issue: BranchProtectionIssue,
+    #[tabled(display("display_optional"))]
+    pub allowed_to_push: Option<String>,
+    #[tabled(display("display_optional"))]
+    pub allowed_to_merge: Option<String>,
+    #[tabled(skip)]
+    pub web_url: String,
+}
+
+impl Finding for BranchProtectionFinding {
+    fn rule_id(&self) -> &str {
+        match self.issue {
+            BranchProtectionIssue::Unprotected => "unprotected-default-branch",
+            BranchProtectionIssue::ForcePushAllowed => "force-push-default-branch",
+        }
+    }
+
+    fn rule_description(&self) -> &str {
+        match self.issue {
+            BranchProtectionIssue::Unprotected => "Default branch is not protected",
+            BranchProtectionIssue::ForcePushAllowed => "Default branch allows force pushes",
+        }
+    }
+
+    fn severity(&self) -> Severity {
+        match self.issue {
+            BranchProtectionIssue::Unprotected => Severity::High,
+            BranchProtectionIssue::ForcePushAllowed => Severity::Medium,
+        }
+    }
+
+    fn message(&self) -> String {
+        format!(
+            "{}: {} ({})",
+            self.path,
+            self.rule_description(),
+            self.default_branch
+        )
+    }
+
+    fn location(&self) -> &str {
+        &self.web_url
+    }
+}
+
+/// Active GitLab repositories whose default branch no protection rule covers,
+/// or where one of the covering rules allows force pushes. Repositories whose
+/// rules could not be read are only reported for rules that allow force
+/// pushes, see [`count_unknown_branch_rules`].
+pub async fn get_branch_protection_findings(
+    pool: &SqlitePool,
+) -> Result<Vec<BranchProtectionFinding>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            c.path,
+            c.default_branch as "default_branch!",
+            c.web_url,
+            COUNT(r.id) as "rule_count!: i64",
+            COALESCE(MAX(r.allow_force_push), FALSE) as "allow_force_push!: bool",
+            (
+                SELECT GROUP_CONCAT(DISTINCT a.description)
+                FROM branch_rule_access_levels a
+                JOIN branch_rules ar ON ar.id = a.branch_rule_id
+                WHERE ar.codebase_id = c.id AND ar.is_default AND ar.is_protected
+                AND a.action = 'push'
+            ) as "allowed_to_push: String",
+            (
+                SELECT GROUP_CONCAT(DISTINCT a.description)
+                FROM branch_rule_access_levels a
+                JOIN branch_rules ar ON ar.id = a.branch_rule_id
+                WHERE ar.codebase_id = c.id AND ar.is_default AND ar.is_protected
+                AND a.action = 'merge'
+            ) as "allowed_to_merge: String"
+        FROM codebases c
+        LEFT JOIN branch_rules r
+            ON r.codebase_id = c.id AND r.is_default = TRUE AND r.is_protected = TRUE
+        WHERE c.deleted_at IS NULL
+        AND c.archived = FALSE
+        AND c.default_branch IS NOT NULL
+        AND c.source LIKE 'gitlab%'
+        GROUP BY c.id
+        HAVING (COUNT(r.id) = 0 AND c.branch_rules_known = TRUE)
+        OR MAX(r.allow_force_push) = TRUE
+        ORDER BY c.path
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| BranchProtectionFinding {
+            path: row.path,
+            default_branch: row.default_branch,
+            issue: if row.rule_count == 0 {
+                BranchProtectionIssue::Unprotected
+            } else {
+                BranchProtectionIssue::ForcePushAllowed
+            },
+            allowed_to_push: row.allowed_to_push,
+            allowed_to_merge: row.allowed_to_merge,
+            web_url: row.web_url,
+        })
+        .collect())
+}
+
+/// Active GitLab repositories with a default branch whose branch rules could
+/// not be read, which the report can't tell to be protected or not.
+pub async fn count_unknown_branch_rules(pool: &SqlitePool) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!: i64"
+        FROM codebases
+        WHERE deleted_at IS NULL
+        AND archived = FALSE
+        AND default_branch IS NOT NULL
+        AND source LIKE 'gitlab%'
+        AND branch_rules_known IS NOT TRUE
+        "#
+    )
+    .fetch_one(pool)
+    .await
+}
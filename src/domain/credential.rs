@@ -0,0 +1,290 @@
+use serde::Serialize;
+use tabled::Tabled;
+use time::{Date, Duration, OffsetDateTime};
+
+use super::policy::Severity;
+use crate::infrastructure::utils::{repositories::display_optional, sarif::Finding};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialKind {
+    /// Project or group access token
+    AccessToken,
+    DeployKey,
+    DeployToken,
+}
+
+impl std::fmt::Display for CredentialKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            CredentialKind::AccessToken => "access token",
+            CredentialKind::DeployKey => "deploy key",
+            CredentialKind::DeployToken => "deploy token",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Project or group the credentials of a collection belong to.
+#[derive(Debug)]
+pub struct CredentialOwner {
+    pub source: String,
+    pub path: String,
+    pub web_url: String,
+    /// Unset for groups
+    pub codebase_id: Option<i64>,
+}
+
+/// An active credential as collected from GitLab. Deploy keys carry the
+/// `read_repository` scope, and `write_repository` if they may push.
+#[derive(Debug)]
+pub struct NewCredential {
+    pub kind: CredentialKind,
+    pub external_id: i64,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: Option<OffsetDateTime>,
+    pub expires_at: Option<Date>,
+    /// Only tracked for access tokens
+    pub last_used_at: Option<OffsetDateTime>,
+}
+
+/// A stored credential with the project or group it belongs to.
+#[derive(Debug)]
+pub struct Credential {
+    pub owner_path: String,
+    pub web_url: String,
+    pub kind: CredentialKind,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: Option<OffsetDateTime>,
+    pub expires_at: Option<Date>,
+    pub last_used_at: Option<OffsetDateTime>,
+}
+
+/// Outcome of collecting the credentials of a group.
+#[derive(Debug, Default)]
+pub struct CredentialUpdateSummary {
+    /// Projects and groups whose credentials were collected
+    pub owners: u64,
+    pub credentials: u64,
+    /// Projects and groups the token may not list credentials of
+    pub skipped: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialIssue {
+    NeverExpires,
+    WriteAccess,
+    Unused,
+}
+
+impl std::fmt::Display for CredentialIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            CredentialIssue::NeverExpires => "never expires",
+            CredentialIssue::WriteAccess => "write access",
+            CredentialIssue::Unused => "unused",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Tabled, Serialize, Debug)]
+pub struct CredentialFinding {
+    pub path: String,
+    pub kind: CredentialKind,
+    pub name: String,
+    pub issue: CredentialIssue,
+    #[tabled(display("display_scopes"))]
+    pub scopes: Vec<String>,
+    #[tabled(display("display_optional"))]
+    pub expires_at: Option<String>,
+    #[tabled(display("display_optional"))]
+    pub last_used_at: Option<String>,
+    #[tabled(skip)]
+    pub web_url: String,
+}
+
+impl Finding for CredentialFinding {
+    fn rule_id(&self) -> &str {
+        match self.issue {
+            CredentialIssue::NeverExpires => "credential-never-expires",
+            CredentialIssue::WriteAccess => "write-scoped-deploy-credential",
+            CredentialIssue::Unused => "unused-credential",
+        }
+    }
+
+    fn rule_description(&self) -> &str {
+        match self.issue {
+            CredentialIssue::NeverExpires => "Credential never expires",
+            CredentialIssue::WriteAccess => "Deploy credential can write to the repository",
+            CredentialIssue::Unused => "Credential has not been used recently",
+        }
+    }
+
+    fn severity(&self) -> Severity {
+        match self.issue {
+            CredentialIssue::NeverExpires => Severity::High,
+            CredentialIssue::WriteAccess => Severity::Medium,
+            CredentialIssue::Unused => Severity::Medium,
+        }
+    }
+
+    fn message(&self) -> String {
+        format!(
+            "{}: {} '{}' {}",
+            self.path,
+            self.kind,
+            self.name,
+            match self.issue {
+                CredentialIssue::NeverExpires => "never expires".to_string(),
+                CredentialIssue::WriteAccess =>
+                    format!("has write scopes ({})", self.scopes.join(", ")),
+                CredentialIssue::Unused => match &self.last_used_at {
+                    Some(last_used_at) => format!("was last used at {last_used_at}"),
+                    None => "was never used".to_string(),
+                },
+            }
+        )
+    }
+
+    fn location(&self) -> &str {
+        &self.web_url
+    }
+}
+
+impl Credential {
+    /// Issues of the credential: no expiry date, write scopes on a deploy
+    /// credential, or no use for `unused_after`. Credentials that were never
+    /// used count as unused once they are that old.
+    pub fn issues(&self, now: OffsetDateTime, unused_after: Duration) -> Vec<CredentialIssue> {
+        let mut issues = Vec::new();
+
+        if self.expires_at.is_none() {
+            issues.push(CredentialIssue::NeverExpires);
+        }
+        if self.kind != CredentialKind::AccessToken
+            && self.scopes.iter().any(|scope| scope.starts_with("write_"))
+        {
+            issues.push(CredentialIssue::WriteAccess);
+        }
+        // Only access tokens record their last use
+        if self.kind == CredentialKind::AccessToken {
+            let last_activity = self.last_used_at.or(self.created_at);
+            if last_activity.is_some_and(|activity| now - activity >= unused_after) {
+                issues.push(CredentialIssue::Unused);
+            }
+        }
+
+        issues
+    }
+}
+
+fn display_scopes(scopes: &[String]) -> String {
+    scopes.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::{date, datetime};
+
+    use super::*;
+
+    const NOW: OffsetDateTime = datetime!(2024-06-01 12:00 UTC);
+    const UNUSED_AFTER: Duration = Duration::days(90);
+
+    fn credential(kind: CredentialKind, scopes: &[&str]) -> Credential {
+        Credential {
+            owner_path: "grp/app".to_string(),
+            web_url: "https://gitlab.com/grp/app".to_string(),
+            kind,
+            name: "ci".to_string(),
+            scopes: scopes.iter().map(|scope| scope.to_string()).collect(),
+            created_at: Some(NOW - Duration::days(365)),
+            expires_at: Some(date!(2025 - 01 - 01)),
+            last_used_at: Some(NOW - Duration::days(1)),
+        }
+    }
+
+    #[test]
+    fn expiry_and_recent_use_are_fine() {
+        let token = credential(CredentialKind::AccessToken, &["read_api"]);
+        assert!(token.issues(NOW, UNUSED_AFTER).is_empty());
+    }
+
+    #[test]
+    fn no_expiry_date() {
+        let token = Credential {
+            expires_at: None,
+            ..credential(CredentialKind::DeployToken, &["read_repository"])
+        };
+        assert_eq!(
+            token.issues(NOW, UNUSED_AFTER),
+            [CredentialIssue::NeverExpires]
+        );
+    }
+
+    #[test]
+    fn unused_after_ninety_days() {
+        let used = |days| Credential {
+            last_used_at: Some(NOW - Duration::days(days)),
+            ..credential(CredentialKind::AccessToken, &["api"])
+        };
+        assert!(used(89).issues(NOW, UNUSED_AFTER).is_empty());
+        assert_eq!(
+            used(90).issues(NOW, UNUSED_AFTER),
+            [CredentialIssue::Unused]
+        );
+    }
+
+    #[test]
+    fn never_used_tokens_count_from_their_creation() {
+        let created = |days| Credential {
+            created_at: Some(NOW - Duration::days(days)),
+            last_used_at: None,
+            ..credential(CredentialKind::AccessToken, &["api"])
+        };
+        assert!(created(10).issues(NOW, UNUSED_AFTER).is_empty());
+        assert_eq!(
+            created(100).issues(NOW, UNUSED_AFTER),
+            [CredentialIssue::Unused]
+        );
+
+        // Without any date there is nothing to go by
+        let undated = Credential {
+            created_at: None,
+            ..created(100)
+        };
+        assert!(undated.issues(NOW, UNUSED_AFTER).is_empty());
+    }
+
+    #[test]
+    fn write_scopes_only_matter_for_deploy_credentials() {
+        let scopes = ["read_repository", "write_repository"];
+        assert_eq!(
+            credential(CredentialKind::DeployKey, &scopes).issues(NOW, UNUSED_AFTER),
+            [CredentialIssue::WriteAccess]
+        );
+        assert_eq!(
+            credential(CredentialKind::DeployToken, &["write_registry"]).issues(NOW, UNUSED_AFTER),
+            [CredentialIssue::WriteAccess]
+        );
+        assert!(
+            credential(CredentialKind::AccessToken, &scopes)
+                .issues(NOW, UNUSED_AFTER)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn last_use_is_only_checked_for_access_tokens() {
+        let key = Credential {
+            last_used_at: None,
+            ..credential(CredentialKind::DeployKey, &["read_repository"])
+        };
+        assert!(key.issues(NOW, UNUSED_AFTER).is_empty());
+    }
+}
@@ -0,0 +1,137 @@
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use tabled::Tabled;
+
+use crate::infrastructure::utils::repositories::display_optional;
+
+/// Package ecosystems, named as in OSV.
+#[derive(
+    ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, sqlx::Type,
+)]
+#[sqlx(type_name = "TEXT")]
+pub enum Ecosystem {
+    #[serde(rename = "crates.io")]
+    #[sqlx(rename = "crates.io")]
+    #[value(name = "crates.io")]
+    CratesIo,
+    #[serde(rename = "npm")]
+    #[sqlx(rename = "npm")]
+    #[value(name = "npm")]
+    Npm,
+    #[serde(rename = "PyPI")]
+    #[sqlx(rename = "PyPI")]
+    #[value(name = "pypi")]
+    PyPI,
+    #[serde(rename = "Go")]
+    #[sqlx(rename = "Go")]
+    #[value(name = "go")]
+    Go,
+    #[serde(rename = "Maven")]
+    #[sqlx(rename = "Maven")]
+    #[value(name = "maven")]
+    Maven,
+    #[serde(rename = "RubyGems")]
+    #[sqlx(rename = "RubyGems")]
+    #[value(name = "rubygems")]
+    RubyGems,
+}
+
+impl std::fmt::Display for Ecosystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Ecosystem::CratesIo => "crates.io",
+            Ecosystem::Npm => "npm",
+            Ecosystem::PyPI => "PyPI",
+            Ecosystem::Go => "Go",
+            Ecosystem::Maven => "Maven",
+            Ecosystem::RubyGems => "RubyGems",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Ecosystem {
+    type Err = String;
+
+    /// Parses the OSV name of an ecosystem, as shown by `Display`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "crates.io" => Ok(Ecosystem::CratesIo),
+            "npm" => Ok(Ecosystem::Npm),
+            "PyPI" => Ok(Ecosystem::PyPI),
+            "Go" => Ok(Ecosystem::Go),
+            "Maven" => Ok(Ecosystem::Maven),
+            "RubyGems" => Ok(Ecosystem::RubyGems),
+            _ => Err(format!("unsupported ecosystem '{value}'")),
+        }
+    }
+}
+
+/// Python package names as normalized in PEP 503, e.g. `Django_Rest.framework`
+/// becomes `django-rest-framework`.
+pub fn normalize_pypi_name(name: &str) -> String {
+    name.to_lowercase().replace(['_', '.'], "-")
+}
+
+/// A package a manifest or lockfile pins, or merely names for manifests like
+/// `requirements.txt`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NewDependency {
+    pub ecosystem: Ecosystem,
+    /// Maven packages are named `<groupId>:<artifactId>`
+    pub name: String,
+    pub version: Option<String>,
+    /// Path of the manifest in the repository
+    pub manifest: String,
+}
+
+/// A repository using a package.
+#[derive(Tabled, Serialize, Debug, sqlx::FromRow)]
+pub struct DependencyUsage {
+    pub path: String,
+    pub ecosystem: Ecosystem,
+    pub name: String,
+    #[tabled(display("display_optional"))]
+    pub version: Option<String>,
+    pub manifest: String,
+    #[tabled(skip)]
+    pub web_url: String,
+}
+
+/// `<package>[@<version>]` as given to `deps search`. Scoped npm packages
+/// start with `@`, so only a later `@` separates the version.
+#[derive(Debug, Clone)]
+pub struct PackageSpec {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+impl FromStr for PackageSpec {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (name, version) = match value.rfind('@') {
+            Some(index) if index > 0 => (&value[..index], Some(&value[index + 1..])),
+            _ => (value, None),
+        };
+
+        if name.is_empty() || version.is_some_and(str::is_empty) {
+            return Err(format!("expected <package>[@<version>], got '{value}'"));
+        }
+
+        Ok(PackageSpec {
+            name: name.to_string(),
+            version: version.map(str::to_string),
+        })
+    }
+}
+
+/// Outcome of `deps update`.
+#[derive(Debug, Default)]
+pub struct DependencyUpdateSummary {
+    pub scanned: u64,
+    pub unchanged: u64,
+    pub dependencies: u64,
+}
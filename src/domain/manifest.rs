@@ -0,0 +1,591 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::dependency::{Ecosystem, NewDependency, normalize_pypi_name};
+
+/// Manifests and lockfiles dependencies are read from, by file name.
+const MANIFESTS: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "yarn.lock",
+    "requirements.txt",
+    "poetry.lock",
+    "go.sum",
+    "pom.xml",
+    "Gemfile.lock",
+];
+
+/// Lockfiles of large projects run into tens of megabytes, larger files are
+/// skipped.
+pub const MAX_MANIFEST_SIZE: u64 = 256 * 1024 * 1024;
+
+pub fn is_manifest(path: &str) -> bool {
+    // Installed packages ship their own lockfiles
+    if path
+        .split('/')
+        .any(|part| part == "node_modules" || part == "vendor")
+    {
+        return false;
+    }
+
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    MANIFESTS.contains(&file_name)
+}
+
+/// Dependencies declared in the manifest at `path`, deduplicated. Manifests
+/// that fail to parse yield none.
+pub fn parse_manifest(path: &str, content: &str) -> Vec<NewDependency> {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+
+    let packages = match file_name {
+        "Cargo.lock" => parse_cargo_lock(content).map(|packages| (Ecosystem::CratesIo, packages)),
+        "poetry.lock" => parse_poetry_lock(content).map(|packages| (Ecosystem::PyPI, packages)),
+        "package-lock.json" => {
+            parse_package_lock(content).map(|packages| (Ecosystem::Npm, packages))
+        }
+        "yarn.lock" => Some((Ecosystem::Npm, parse_yarn_lock(content))),
+        "requirements.txt" => Some((Ecosystem::PyPI, parse_requirements(content))),
+        "go.sum" => Some((Ecosystem::Go, parse_go_sum(content))),
+        "pom.xml" => parse_pom(content).map(|packages| (Ecosystem::Maven, packages)),
+        "Gemfile.lock" => Some((Ecosystem::RubyGems, parse_gemfile_lock(content))),
+        _ => None,
+    };
+
+    let Some((ecosystem, packages)) = packages else {
+        return Vec::new();
+    };
+
+    let mut dependencies: Vec<NewDependency> = packages
+        .into_iter()
+        .map(|(name, version)| NewDependency {
+            ecosystem,
+            name,
+            version,
+            manifest: path.to_string(),
+        })
+        .collect();
+    dependencies.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+    dependencies.dedup();
+    dependencies
+}
+
+type Package = (String, Option<String>);
+
+#[derive(Deserialize)]
+struct TomlLock {
+    #[serde(default)]
+    package: Vec<TomlLockPackage>,
+}
+
+#[derive(Deserialize)]
+struct TomlLockPackage {
+    name: String,
+    version: String,
+    source: Option<toml::Value>,
+}
+
+fn parse_cargo_lock(content: &str) -> Option<Vec<Package>> {
+    let lock: TomlLock = toml::from_str(content).ok()?;
+
+    Some(
+        lock.package
+            .into_iter()
+            // The crates of the workspace itself have no source
+            .filter(|package| package.source.is_some())
+            .map(|package| (package.name, Some(package.version)))
+            .collect(),
+    )
+}
+
+fn parse_poetry_lock(content: &str) -> Option<Vec<Package>> {
+    let lock: TomlLock = toml::from_str(content).ok()?;
+
+    Some(
+        lock.package
+            .into_iter()
+            .map(|package| (normalize_pypi_name(&package.name), Some(package.version)))
+            .collect(),
+    )
+}
+
+fn parse_package_lock(content: &str) -> Option<Vec<Package>> {
+    let lock: Value = serde_json::from_str(content).ok()?;
+    let mut packages = Vec::new();
+
+    // Lockfile v2 and v3 key packages by their install path
+    if let Some(installed) = lock.get("packages").and_then(Value::as_object) {
+        for (path, package) in installed {
+            let Some((_, name)) = path.rsplit_once("node_modules/") else {
+                continue;
+            };
+            if package.get("link").and_then(Value::as_bool) == Some(true) {
+                continue;
+            }
+            let version = package.get("version").and_then(Value::as_str);
+            packages.push((name.to_string(), version.map(str::to_string)));
+        }
+        return Some(packages);
+    }
+
+    // Lockfile v1 nests dependencies of dependencies
+    fn walk(dependencies: &Value, packages: &mut Vec<Package>) {
+        let Some(dependencies) = dependencies.as_object() else {
+            return;
+        };
+        for (name, package) in dependencies {
+            let version = package.get("version").and_then(Value::as_str);
+            packages.push((name.clone(), version.map(str::to_string)));
+            if let Some(nested) = package.get("dependencies") {
+                walk(nested, packages);
+            }
+        }
+    }
+    if let Some(dependencies) = lock.get("dependencies") {
+        walk(dependencies, &mut packages);
+    }
+
+    Some(packages)
+}
+
+/// Classic (`version "1.0.0"`) and Berry (`version: 1.0.0`) lockfiles alike.
+fn parse_yarn_lock(content: &str) -> Vec<Package> {
+    let mut packages = Vec::new();
+    let mut name: Option<String> = None;
+
+    for line in content.lines() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+
+        if !line.starts_with(' ') {
+            // "@scope/name@^1.0.0", "@scope/name@npm:^1.1.0":
+            let descriptor = line
+                .trim_end_matches(':')
+                .split(',')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .trim_matches('"');
+            // Skip the leading `@` of scoped packages
+            name = descriptor
+                .get(1..)
+                .and_then(|rest| rest.find('@'))
+                .map(|at| descriptor[..at + 1].to_string());
+            continue;
+        }
+
+        let Some(version) = line.trim().strip_prefix("version") else {
+            continue;
+        };
+        if let Some(name) = name.take() {
+            let version = version.trim_start_matches(':').trim().trim_matches('"');
+            packages.push((name, Some(version.to_string())));
+        }
+    }
+
+    // Berry lists the project itself as a workspace
+    packages.retain(|(_, version)| version.as_deref() != Some("0.0.0-use.local"));
+    packages
+}
+
+/// Pinned (`==`) requirements carry a version, other requirements only a
+/// name. Names are normalized as in PEP 503.
+fn parse_requirements(content: &str) -> Vec<Package> {
+    let mut packages = Vec::new();
+
+    for line in content.lines() {
+        let line = line.split(" #").next().unwrap_or_default().trim();
+        // Options like `-r other.txt`, comments and URLs
+        if line.is_empty() || line.starts_with(['#', '-']) || line.contains("://") {
+            continue;
+        }
+
+        let requirement = line.split(';').next().unwrap_or_default().trim();
+        let split = requirement
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+            .unwrap_or(requirement.len());
+        let (name, specifier) = requirement.split_at(split);
+        if name.is_empty() {
+            continue;
+        }
+
+        // Skip extras like `requests[socks]`
+        let specifier = match specifier.find(']') {
+            Some(end) if specifier.starts_with('[') => &specifier[end + 1..],
+            _ => specifier,
+        };
+        let version = specifier
+            .trim()
+            .strip_prefix("==")
+            .map(|version| version.trim().to_string());
+
+        packages.push((normalize_pypi_name(name), version));
+    }
+
+    packages
+}
+
+/// Every module version the build graph considered, with or without its
+/// full source.
+fn parse_go_sum(content: &str) -> Vec<Package> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let module = fields.next()?;
+            let version = fields.next()?.trim_end_matches("/go.mod");
+            Some((module.to_string(), Some(version.to_string())))
+        })
+        .collect()
+}
+
+/// Dependencies and managed dependencies of a POM, with `${property}`
+/// versions resolved from its properties where possible.
+fn parse_pom(content: &str) -> Option<Vec<Package>> {
+    let document = roxmltree::Document::parse(content).ok()?;
+    let project = document.root_element();
+
+    let child = |node: roxmltree::Node, name: &str| {
+        node.children()
+            .find(|child| child.has_tag_name(name))
+            .and_then(|child| child.text())
+            .map(|text| text.trim().to_string())
+    };
+
+    let mut properties: HashMap<String, String> = HashMap::new();
+    if let Some(version) = child(project, "version").or_else(|| {
+        project
+            .children()
+            .find(|node| node.has_tag_name("parent"))
+            .and_then(|parent| child(parent, "version"))
+    }) {
+        properties.insert("project.version".to_string(), version);
+    }
+    if let Some(declared) = project
+        .children()
+        .find(|node| node.has_tag_name("properties"))
+    {
+        for property in declared.children().filter(|node| node.is_element()) {
+            if let Some(value) = property.text() {
+                properties.insert(
+                    property.tag_name().name().to_string(),
+                    value.trim().to_string(),
+                );
+            }
+        }
+    }
+
+    let resolve = |value: String| match value
+        .strip_prefix("${")
+        .and_then(|value| value.strip_suffix('}'))
+    {
+        Some(property) => properties.get(property).cloned(),
+        None => Some(value),
+    };
+
+    let packages = project
+        .descendants()
+        .filter(|node| node.has_tag_name("dependency"))
+        .filter_map(|dependency| {
+            let group = child(dependency, "groupId")?;
+            let artifact = child(dependency, "artifactId")?;
+            let version = child(dependency, "version").and_then(resolve);
+            Some((format!("{group}:{artifact}"), version))
+        })
+        .collect();
+
+    Some(packages)
+}
+
+/// Gems of the `GEM` section, without the dependencies listed below each.
+fn parse_gemfile_lock(content: &str) -> Vec<Package> {
+    let mut packages = Vec::new();
+    let mut in_specs = false;
+
+    for line in content.lines() {
+        if !line.starts_with(' ') {
+            in_specs = false;
+            continue;
+        }
+        if line.trim() == "specs:" {
+            in_specs = true;
+            continue;
+        }
+
+        // Four spaces for gems, six for their dependencies
+        if in_specs && line.starts_with("    ") && !line.starts_with("     ") {
+            let spec = line.trim();
+            if let Some((name, version)) = spec.split_once(" (") {
+                packages.push((
+                    name.to_string(),
+                    Some(version.trim_end_matches(')').to_string()),
+                ));
+            }
+        }
+    }
+
+    packages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packages(path: &str, content: &str) -> Vec<(String, Option<String>)> {
+        parse_manifest(path, content)
+            .into_iter()
+            .map(|dependency| (dependency.name, dependency.version))
+            .collect()
+    }
+
+    fn pinned(name: &str, version: &str) -> (String, Option<String>) {
+        (name.to_string(), Some(version.to_string()))
+    }
+
+    #[test]
+    fn cargo_lock_skips_workspace_crates() {
+        let content = r#"
+version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+
+[[package]]
+name = "serde"
+version = "1.0.200"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#;
+
+        assert_eq!(
+            packages("Cargo.lock", content),
+            vec![pinned("serde", "1.0.200")]
+        );
+    }
+
+    #[test]
+    fn poetry_lock_normalizes_names() {
+        let content = r#"
+[[package]]
+name = "Django_REST.framework"
+version = "3.15.1"
+"#;
+
+        assert_eq!(
+            packages("poetry.lock", content),
+            vec![pinned("django-rest-framework", "3.15.1")]
+        );
+    }
+
+    #[test]
+    fn package_lock_v1_walks_nested_dependencies() {
+        let content = r#"{
+            "lockfileVersion": 1,
+            "dependencies": {
+                "express": {
+                    "version": "4.18.2",
+                    "dependencies": {
+                        "debug": { "version": "2.6.9" }
+                    }
+                },
+                "debug": { "version": "4.3.4" }
+            }
+        }"#;
+
+        assert_eq!(
+            packages("package-lock.json", content),
+            vec![
+                pinned("debug", "2.6.9"),
+                pinned("debug", "4.3.4"),
+                pinned("express", "4.18.2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn package_lock_v3_reads_install_paths() {
+        let content = r#"{
+            "lockfileVersion": 3,
+            "packages": {
+                "": { "name": "app", "version": "1.0.0" },
+                "node_modules/@babel/core": { "version": "7.24.0" },
+                "node_modules/express/node_modules/debug": { "version": "2.6.9" },
+                "node_modules/shared": { "resolved": "packages/shared", "link": true }
+            }
+        }"#;
+
+        assert_eq!(
+            packages("package-lock.json", content),
+            vec![pinned("@babel/core", "7.24.0"), pinned("debug", "2.6.9")]
+        );
+    }
+
+    #[test]
+    fn yarn_classic_lock() {
+        let content = r#"# THIS IS AN AUTOGENERATED FILE. DO NOT EDIT THIS FILE DIRECTLY.
+# yarn lockfile v1
+
+
+"@babel/code-frame@^7.0.0", "@babel/code-frame@^7.22.13":
+  version "7.22.13"
+  resolved "https://registry.yarnpkg.com/@babel/code-frame/-/code-frame-7.22.13.tgz"
+
+lodash@^4.17.21:
+  version "4.17.21"
+  dependencies:
+    version-utils "^1.0.0"
+"#;
+
+        assert_eq!(
+            packages("yarn.lock", content),
+            vec![
+                pinned("@babel/code-frame", "7.22.13"),
+                pinned("lodash", "4.17.21"),
+            ]
+        );
+    }
+
+    #[test]
+    fn yarn_berry_lock_skips_metadata_and_workspaces() {
+        let content = r#"# This file is generated by running "yarn install" inside your project.
+
+__metadata:
+  version: 8
+  cacheKey: 10c0
+
+"@types/node@npm:^20.0.0":
+  version: 20.11.30
+  resolution: "@types/node@npm:20.11.30"
+  languageName: node
+  linkType: hard
+
+"app@workspace:.":
+  version: 0.0.0-use.local
+  resolution: "app@workspace:."
+  languageName: unknown
+  linkType: soft
+"#;
+
+        assert_eq!(
+            packages("yarn.lock", content),
+            vec![pinned("@types/node", "20.11.30")]
+        );
+    }
+
+    #[test]
+    fn requirements_only_pin_exact_versions() {
+        let content = "\
+-r base.txt
+# comment
+Flask_Login==0.6.3  # pinned
+requests[socks]==2.31.0 ; python_version >= \"3.8\"
+urllib3>=2.0
+git+https://github.com/org/pkg.git
+";
+
+        assert_eq!(
+            packages("requirements.txt", content),
+            vec![
+                pinned("flask-login", "0.6.3"),
+                pinned("requests", "2.31.0"),
+                ("urllib3".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn go_sum_merges_go_mod_lines() {
+        let content = "\
+github.com/google/uuid v1.6.0 h1:NIvaJDMOsjHA8n1jAhLSgzrAzy1Hgr+hNrb57e+94F0=
+github.com/google/uuid v1.6.0/go.mod h1:TIyPZe4MgqvfeYDBFedMoGGpEw/LqOeaOT+nhxU+yHo=
+";
+
+        assert_eq!(
+            packages("go.sum", content),
+            vec![pinned("github.com/google/uuid", "v1.6.0")]
+        );
+    }
+
+    #[test]
+    fn pom_resolves_properties() {
+        let content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<project xmlns="http://maven.apache.org/POM/4.0.0">
+    <parent>
+        <groupId>com.example</groupId>
+        <artifactId>parent</artifactId>
+        <version>2.1.0</version>
+    </parent>
+    <artifactId>app</artifactId>
+    <properties>
+        <jackson.version>2.17.0</jackson.version>
+    </properties>
+    <dependencyManagement>
+        <dependencies>
+            <dependency>
+                <groupId>com.example</groupId>
+                <artifactId>common</artifactId>
+                <version>${project.version}</version>
+            </dependency>
+        </dependencies>
+    </dependencyManagement>
+    <dependencies>
+        <dependency>
+            <groupId>com.fasterxml.jackson.core</groupId>
+            <artifactId>jackson-databind</artifactId>
+            <version>${jackson.version}</version>
+        </dependency>
+        <dependency>
+            <groupId>junit</groupId>
+            <artifactId>junit</artifactId>
+            <version>${undefined.version}</version>
+        </dependency>
+    </dependencies>
+</project>
+"#;
+
+        assert_eq!(
+            packages("pom.xml", content),
+            vec![
+                pinned("com.example:common", "2.1.0"),
+                pinned("com.fasterxml.jackson.core:jackson-databind", "2.17.0"),
+                ("junit:junit".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn gemfile_lock_skips_nested_dependencies() {
+        let content = "\
+GEM
+  remote: https://rubygems.org/
+  specs:
+    rack (3.0.9)
+    rails (7.1.3)
+      actionpack (= 7.1.3)
+
+PLATFORMS
+  ruby
+";
+
+        assert_eq!(
+            packages("Gemfile.lock", content),
+            vec![pinned("rack", "3.0.9"), pinned("rails", "7.1.3")]
+        );
+    }
+
+    #[test]
+    fn unparsable_manifests_yield_nothing() {
+        assert!(packages("package-lock.json", "{").is_empty());
+        assert!(packages("pom.xml", "<project>").is_empty());
+    }
+
+    #[test]
+    fn installed_packages_are_not_manifests() {
+        assert!(is_manifest("web/package-lock.json"));
+        assert!(!is_manifest("web/node_modules/left-pad/package-lock.json"));
+        assert!(!is_manifest("vendor/github.com/x/y/go.sum"));
+        assert!(!is_manifest("package.json"));
+    }
+}
@@ -0,0 +1,102 @@
+use std::{path::PathBuf, str::FromStr};
+
+use clap::ValueEnum;
+use serde::Serialize;
+use tabled::Tabled;
+use time::OffsetDateTime;
+
+use super::{repository::Codebase, search_query::parse_size};
+use crate::infrastructure::utils::repositories::{display_commit, display_offset_datetime};
+
+/// A repository mirrored into the local cache.
+#[derive(Tabled, Serialize, Debug)]
+pub struct Mirror {
+    #[tabled(skip)]
+    pub codebase_id: i64,
+    pub path: String,
+    /// Commit `HEAD` pointed to when last fetched, unset for empty repositories
+    #[tabled(display("display_commit"))]
+    pub head_commit: Option<String>,
+    /// Bytes on disk
+    pub size: i64,
+    #[serde(with = "time::serde::rfc3339")]
+    #[tabled(display("display_offset_datetime"))]
+    pub fetched_at: OffsetDateTime,
+    /// Deleted upstream, the mirror is removed by `mirror prune`
+    pub deleted: bool,
+    #[tabled(skip)]
+    pub dir: String,
+    #[serde(with = "time::serde::rfc3339")]
+    #[tabled(skip)]
+    pub pushed_at: OffsetDateTime,
+}
+
+/// The state of a mirror after a fetch.
+#[derive(Debug)]
+pub struct NewMirror {
+    pub codebase_id: i64,
+    pub dir: String,
+    pub head_commit: Option<String>,
+    pub size: i64,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Transport {
+    /// The SSH URL reported by GitLab or GitHub
+    #[default]
+    Ssh,
+    /// The web URL, authenticated by git's credential helpers
+    Https,
+}
+
+impl Transport {
+    pub fn clone_url(&self, codebase: &Codebase) -> Option<String> {
+        match self {
+            Transport::Ssh => codebase.clone_url.clone(),
+            Transport::Https => Some(format!("{}.git", codebase.web_url.trim_end_matches('/'))),
+        }
+    }
+}
+
+/// Maximum bytes the mirrors may take up on disk, e.g. `20GB`.
+#[derive(Clone, Copy, Debug)]
+pub struct DiskQuota(pub i64);
+
+impl FromStr for DiskQuota {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        parse_size(value)
+            .map(DiskQuota)
+            .map_err(|err| err.to_string())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MirrorOptions {
+    /// Directory new mirrors are cloned below
+    pub mirrors_dir: PathBuf,
+    pub transport: Transport,
+    /// Number of repositories cloned or fetched at once
+    pub concurrency: usize,
+    /// Repositories that would exceed the quota are not cloned, existing
+    /// mirrors are still fetched
+    pub quota: Option<DiskQuota>,
+}
+
+/// Outcome of `mirror sync`.
+#[derive(Debug, Default)]
+pub struct MirrorSyncSummary {
+    pub cloned: u64,
+    pub fetched: u64,
+    pub over_quota: u64,
+    pub failed: u64,
+}
+
+/// Outcome of `mirror prune`.
+#[derive(Debug, Default)]
+pub struct MirrorPruneSummary {
+    pub removed: u64,
+    /// Bytes freed on disk
+    pub freed: u64,
+}
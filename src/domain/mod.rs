@@ -1,2 +1,13 @@
+pub mod advisory;
+pub mod branch_protection;
+pub mod credential;
+pub mod dependency;
+pub mod manifest;
+pub mod mirror;
+pub mod policy;
 pub mod repository;
+pub mod sbom;
+pub mod search_query;
+pub mod secret;
 pub mod statistics;
+pub mod sync;
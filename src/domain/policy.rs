@@ -0,0 +1,104 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tabled::Tabled;
+
+use crate::{
+    error::AppError,
+    infrastructure::utils::{config_file::read_config_file, sarif::Finding},
+};
+
+use super::search_query::SearchQuery;
+
+/// Compliance rules evaluated by `devsec check`.
+#[derive(Deserialize, Debug)]
+pub struct Policy {
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+/// A rule is violated by every repository its `match` query finds, e.g.
+/// `public:true -path:oss/*` or `archived:true pushed:>90d`.
+#[derive(Deserialize, Debug)]
+pub struct Rule {
+    pub id: String,
+    pub description: String,
+    #[serde(default)]
+    pub severity: Severity,
+    #[serde(rename = "match")]
+    pub query: String,
+}
+
+#[derive(
+    Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, sqlx::Type,
+)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum Severity {
+    /// Not rated, e.g. advisories without a CVSS score or rating
+    Unknown,
+    Low,
+    #[default]
+    Medium,
+    High,
+    Critical,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Severity::Unknown => "unknown",
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Tabled, Serialize, Debug)]
+pub struct Violation {
+    pub rule_id: String,
+    pub severity: Severity,
+    pub path: String,
+    pub description: String,
+    #[tabled(skip)]
+    pub web_url: String,
+}
+
+impl Finding for Violation {
+    fn rule_id(&self) -> &str {
+        &self.rule_id
+    }
+
+    fn rule_description(&self) -> &str {
+        &self.description
+    }
+
+    fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    fn message(&self) -> String {
+        format!("{}: {}", self.path, self.description)
+    }
+
+    fn location(&self) -> &str {
+        &self.web_url
+    }
+}
+
+/// Loads a policy from a TOML or YAML file, picked by its extension, and
+/// checks that every rule's query parses.
+pub fn load_policy(path: &Path) -> Result<Policy, AppError> {
+    let policy: Policy = read_config_file(path).map_err(AppError::InvalidPolicy)?;
+
+    for rule in &policy.rules {
+        rule.query
+            .parse::<SearchQuery>()
+            .map_err(|err| AppError::InvalidPolicy(format!("rule {}: {err}", rule.id)))?;
+    }
+
+    Ok(policy)
+}
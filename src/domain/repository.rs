@@ -2,9 +2,12 @@ use serde::{Deserialize, Serialize};
 use tabled::Tabled;
 use time::OffsetDateTime;
 
-use crate::infrastructure::utils::repositories::display_offset_datetime;
+use super::branch_protection::NewBranchRule;
+use crate::infrastructure::utils::repositories::{
+    display_offset_datetime, display_optional, display_score,
+};
 
-#[derive(Tabled, Serialize, Deserialize, Debug)]
+#[derive(Tabled, Serialize, Deserialize, Debug, sqlx::FromRow)]
 pub struct Codebase {
     #[tabled(skip)]
     pub id: i64,
@@ -41,6 +44,28 @@ pub struct Codebase {
     #[tabled(skip)]
     pub private: bool,
     pub archived: bool,
+
+    #[tabled(skip)]
+    pub default_branch: Option<String>,
+
+    #[tabled(skip)]
+    pub clone_url: Option<String>,
+}
+
+#[derive(Tabled, Serialize, Debug, sqlx::FromRow)]
+pub struct SearchResult {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    #[tabled(inline)]
+    pub codebase: Codebase,
+
+    /// Negated bm25 rank, higher is a better match. Only set for full-text matches.
+    #[tabled(display("display_score"))]
+    pub score: Option<f64>,
+
+    /// Excerpt of the best matching column with matches wrapped in `[` `]`
+    #[tabled(display("display_optional"))]
+    pub snippet: Option<String>,
 }
 
 #[derive(Debug)]
@@ -57,17 +82,33 @@ pub struct NewCodebase {
     pub commit_count: i64,
     pub private: bool,
     pub archived: bool,
+    /// Unset for empty repositories
+    pub default_branch: Option<String>,
+    /// SSH URL, or any other URL or path `git clone` accepts
+    pub clone_url: Option<String>,
 }
 
+/// A codebase as fetched from upstream together with its language shares in
+/// percent, branch rules and security settings, written in one go by
+/// `CodebaseRepository::save_batch`.
 #[derive(Debug)]
-pub struct ProgrammingLanguage {
-    pub id: i64,
-    pub name: String,
+pub struct CodebaseRecord {
+    pub codebase: NewCodebase,
+    pub languages: Vec<(String, f64)>,
+    /// Unset if the rules could not be read, e.g. for lack of permissions, on
+    /// older instances, or on GitHub
+    pub branch_rules: Option<Vec<NewBranchRule>>,
+    pub security_settings: Option<SecuritySettings>,
 }
 
-#[derive(Debug)]
-pub struct CodebaseLanguage {
-    pub codebase_id: i64,
-    pub language_id: i64,
-    pub percentage: f64,
+/// Merge and security settings of a GitLab project. Settings the token may
+/// not read, or that the instance's tier lacks, are unknown.
+#[derive(Debug, Default)]
+pub struct SecuritySettings {
+    /// Approvals required for merge requests into the default branch
+    pub approvals_required: Option<i64>,
+    pub secret_push_protection: Option<bool>,
+    pub dependency_scanning: Option<bool>,
+    /// Merge requests can only be merged once their pipeline succeeded
+    pub pipeline_must_succeed: Option<bool>,
 }
@@ -0,0 +1,173 @@
+use clap::ValueEnum;
+
+use super::{
+    dependency::{Ecosystem, NewDependency},
+    repository::Codebase,
+};
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum SbomFormat {
+    /// CycloneDX 1.5 JSON
+    #[default]
+    CyclonedxJson,
+    /// SPDX 2.3 JSON
+    SpdxJson,
+}
+
+/// Which codebases an SBOM describes.
+#[derive(Debug, Clone)]
+pub enum SbomScope {
+    All,
+    /// A single codebase by its full path
+    Repository(String),
+    /// Every codebase below a group or organization
+    Group(String),
+}
+
+impl SbomScope {
+    pub fn includes(&self, codebase: &Codebase) -> bool {
+        match self {
+            SbomScope::All => true,
+            SbomScope::Repository(path) => codebase.path == *path,
+            SbomScope::Group(group) => codebase
+                .path
+                .strip_prefix(group.as_str())
+                .is_some_and(|rest| rest.starts_with('/')),
+        }
+    }
+
+    /// Name of the SBOM and of the component it describes.
+    pub fn name(&self) -> &str {
+        match self {
+            SbomScope::All => "repositories",
+            SbomScope::Repository(path) | SbomScope::Group(path) => path,
+        }
+    }
+}
+
+/// A codebase with its dependency inventory, as described by an SBOM.
+#[derive(Debug)]
+pub struct SbomSubject {
+    pub codebase: Codebase,
+    /// Languages with their share in percent
+    pub languages: Vec<(String, f64)>,
+    pub dependencies: Vec<NewDependency>,
+}
+
+/// Package URL of a dependency, e.g. `pkg:npm/%40babel/core@7.1.0`.
+pub fn purl(dependency: &NewDependency) -> String {
+    let kind = match dependency.ecosystem {
+        Ecosystem::CratesIo => "cargo",
+        Ecosystem::Npm => "npm",
+        Ecosystem::PyPI => "pypi",
+        Ecosystem::Go => "golang",
+        Ecosystem::Maven => "maven",
+        Ecosystem::RubyGems => "gem",
+    };
+    // Maven coordinates are `<groupId>:<artifactId>`, the namespace and name
+    let name = match dependency.ecosystem {
+        Ecosystem::Maven => dependency.name.replacen(':', "/", 1),
+        _ => dependency.name.clone(),
+    };
+    let name: Vec<String> = name.split('/').map(percent_encode).collect();
+
+    match &dependency.version {
+        Some(version) => format!("pkg:{kind}/{}@{}", name.join("/"), percent_encode(version)),
+        None => format!("pkg:{kind}/{}", name.join("/")),
+    }
+}
+
+/// Namespace and name of a dependency, e.g. the scope of npm packages.
+pub fn split_name(dependency: &NewDependency) -> (Option<&str>, &str) {
+    let separator = match dependency.ecosystem {
+        Ecosystem::Maven => ':',
+        Ecosystem::Npm if dependency.name.starts_with('@') => '/',
+        _ => return (None, &dependency.name),
+    };
+    match dependency.name.split_once(separator) {
+        Some((namespace, name)) => (Some(namespace), name),
+        None => (None, &dependency.name),
+    }
+}
+
+fn percent_encode(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'.' | b'-' | b'_' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dependency(ecosystem: Ecosystem, name: &str, version: Option<&str>) -> NewDependency {
+        NewDependency {
+            ecosystem,
+            name: name.to_string(),
+            version: version.map(str::to_string),
+            manifest: "manifest".to_string(),
+        }
+    }
+
+    #[test]
+    fn purls() {
+        let purls = [
+            (
+                Ecosystem::CratesIo,
+                "serde",
+                Some("1.0.0"),
+                "pkg:cargo/serde@1.0.0",
+            ),
+            (
+                Ecosystem::Npm,
+                "@babel/core",
+                Some("7.1.0"),
+                "pkg:npm/%40babel/core@7.1.0",
+            ),
+            (Ecosystem::Npm, "left-pad", None, "pkg:npm/left-pad"),
+            (
+                Ecosystem::Maven,
+                "org.apache.logging.log4j:log4j-core",
+                Some("2.17.1"),
+                "pkg:maven/org.apache.logging.log4j/log4j-core@2.17.1",
+            ),
+            (
+                Ecosystem::PyPI,
+                "Django",
+                Some("4.2"),
+                "pkg:pypi/Django@4.2",
+            ),
+            (
+                Ecosystem::Go,
+                "github.com/gorilla/mux",
+                Some("v1.8.0"),
+                "pkg:golang/github.com/gorilla/mux@v1.8.0",
+            ),
+            (
+                Ecosystem::RubyGems,
+                "rails",
+                Some("7.0.0+1"),
+                "pkg:gem/rails@7.0.0%2B1",
+            ),
+        ];
+        for (ecosystem, name, version, expected) in purls {
+            assert_eq!(purl(&dependency(ecosystem, name, version)), expected);
+        }
+    }
+
+    #[test]
+    fn names_split_into_namespace_and_name() {
+        let npm = dependency(Ecosystem::Npm, "@babel/core", None);
+        assert_eq!(split_name(&npm), (Some("@babel"), "core"));
+        let maven = dependency(Ecosystem::Maven, "org.slf4j:slf4j-api", None);
+        assert_eq!(split_name(&maven), (Some("org.slf4j"), "slf4j-api"));
+        let go = dependency(Ecosystem::Go, "github.com/gorilla/mux", None);
+        assert_eq!(split_name(&go), (None, "github.com/gorilla/mux"));
+    }
+}
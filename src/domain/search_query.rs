@@ -0,0 +1,480 @@
+use std::str::FromStr;
+
+use time::{
+    Date, Duration, OffsetDateTime, format_description::well_known::Rfc3339,
+    macros::format_description,
+};
+
+/// A parsed search query such as
+/// `backend lang:rust size:>100MB pushed:<2024-01-01 private:true -archived:true`.
+///
+/// Bare words are matched against the full-text index, `field:value` pairs
+/// become typed filters. Any term or filter can be negated with a leading `-`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SearchQuery {
+    pub terms: Vec<Term>,
+    pub filters: Vec<Filter>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term {
+    pub negated: bool,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    pub negated: bool,
+    pub kind: FilterKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterKind {
+    /// `lang:rust`, matched case-insensitively against the repository languages
+    Language(String),
+    /// `size:>100MB`, in bytes
+    Size(Comparison, i64),
+    /// `pushed:<2024-01-01` or `pushed:<1y`
+    Pushed(Comparison, OffsetDateTime),
+    /// `private:true`
+    Private(bool),
+    /// `archived:false`
+    Archived(bool),
+    /// `path:backend/*`, a glob matched against the full path or any trailing part of it
+    Path(String),
+    /// `approvals:>=2`, approvals required for merge requests into the default branch
+    Approvals(Comparison, i64),
+    /// `push_protection:true`, secret push protection
+    SecretPushProtection(bool),
+    /// `dep_scanning:true`
+    DependencyScanning(bool),
+    /// `pipeline_required:true`, pipelines must succeed before merge
+    PipelineRequired(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Lt,
+    Le,
+    Eq,
+    Ge,
+    Gt,
+}
+
+impl Comparison {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Eq => "=",
+            Comparison::Ge => ">=",
+            Comparison::Gt => ">",
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("{0}")]
+pub struct QueryParseError(String);
+
+impl SearchQuery {
+    /// Whether the query filters on `archived:` explicitly, in which case the
+    /// default of hiding archived repositories does not apply.
+    pub fn has_archived_filter(&self) -> bool {
+        self.filters
+            .iter()
+            .any(|filter| matches!(filter.kind, FilterKind::Archived(_)))
+    }
+}
+
+impl FromStr for SearchQuery {
+    type Err = QueryParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut query = SearchQuery::default();
+
+        for token in tokenize(input)? {
+            match token.field {
+                None => query.terms.push(Term {
+                    negated: token.negated,
+                    text: token.value,
+                }),
+                Some(field) => query.filters.push(Filter {
+                    negated: token.negated,
+                    kind: parse_filter(&field, &token.value)?,
+                }),
+            }
+        }
+
+        Ok(query)
+    }
+}
+
+struct Token {
+    negated: bool,
+    field: Option<String>,
+    value: String,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, QueryParseError> {
+    let mut tokens = Vec::new();
+    let mut current: Option<Token> = None;
+    let mut in_quotes = false;
+
+    for c in input.chars() {
+        if c.is_whitespace() && !in_quotes {
+            if let Some(token) = current.take() {
+                tokens.push(token);
+            }
+            continue;
+        }
+
+        let token = current.get_or_insert_with(|| Token {
+            negated: false,
+            field: None,
+            value: String::new(),
+        });
+
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '-' if !in_quotes
+                && !token.negated
+                && token.field.is_none()
+                && token.value.is_empty() =>
+            {
+                token.negated = true
+            }
+            ':' if !in_quotes
+                && token.field.is_none()
+                && !token.value.is_empty()
+                && token
+                    .value
+                    .chars()
+                    .all(|c| c.is_ascii_alphabetic() || c == '_') =>
+            {
+                token.field = Some(std::mem::take(&mut token.value).to_lowercase())
+            }
+            c => token.value.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(QueryParseError("unterminated quote".to_string()));
+    }
+
+    if let Some(token) = current.take() {
+        tokens.push(token);
+    }
+
+    // A lone `-` is a search term, not a negation of nothing
+    for token in tokens.iter_mut() {
+        if token.negated && token.field.is_none() && token.value.is_empty() {
+            token.negated = false;
+            token.value.push('-');
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_filter(field: &str, value: &str) -> Result<FilterKind, QueryParseError> {
+    if value.is_empty() {
+        return Err(QueryParseError(format!("missing value for '{field}:'")));
+    }
+
+    match field {
+        "lang" | "language" => Ok(FilterKind::Language(value.to_string())),
+        "size" => {
+            let (comparison, size) = split_comparison(value);
+            Ok(FilterKind::Size(comparison, parse_size(size)?))
+        }
+        "pushed" => {
+            let (comparison, date) = split_comparison(value);
+            Ok(FilterKind::Pushed(comparison, parse_datetime(date)?))
+        }
+        "private" => Ok(FilterKind::Private(parse_bool(field, value)?)),
+        "public" => Ok(FilterKind::Private(!parse_bool(field, value)?)),
+        "archived" => Ok(FilterKind::Archived(parse_bool(field, value)?)),
+        "path" => Ok(FilterKind::Path(value.to_string())),
+        "approvals" => {
+            let (comparison, approvals) = split_comparison(value);
+            let approvals = approvals.parse().map_err(|_| {
+                QueryParseError(format!("invalid number of approvals '{approvals}'"))
+            })?;
+            Ok(FilterKind::Approvals(comparison, approvals))
+        }
+        "push_protection" => Ok(FilterKind::SecretPushProtection(parse_bool(field, value)?)),
+        "dep_scanning" => Ok(FilterKind::DependencyScanning(parse_bool(field, value)?)),
+        "pipeline_required" => Ok(FilterKind::PipelineRequired(parse_bool(field, value)?)),
+        _ => Err(QueryParseError(format!(
+            "unknown filter '{field}:', expected one of lang, size, pushed, private, public, archived, path, approvals, push_protection, dep_scanning, pipeline_required"
+        ))),
+    }
+}
+
+fn split_comparison(value: &str) -> (Comparison, &str) {
+    for (prefix, comparison) in [
+        (">=", Comparison::Ge),
+        ("<=", Comparison::Le),
+        (">", Comparison::Gt),
+        ("<", Comparison::Lt),
+        ("=", Comparison::Eq),
+    ] {
+        if let Some(rest) = value.strip_prefix(prefix) {
+            return (comparison, rest);
+        }
+    }
+
+    (Comparison::Eq, value)
+}
+
+fn parse_bool(field: &str, value: &str) -> Result<bool, QueryParseError> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "1" => Ok(true),
+        "false" | "no" | "0" => Ok(false),
+        _ => Err(QueryParseError(format!(
+            "invalid value '{value}' for '{field}:', expected true or false"
+        ))),
+    }
+}
+
+/// Parses sizes like `100`, `512KB`, `1.5GB` into bytes.
+pub fn parse_size(value: &str) -> Result<i64, QueryParseError> {
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let multiplier: i64 = match unit.to_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        "T" | "TB" => 1 << 40,
+        _ => return Err(QueryParseError(format!("invalid size unit in '{value}'"))),
+    };
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| QueryParseError(format!("invalid size '{value}'")))?;
+
+    Ok((number * multiplier as f64) as i64)
+}
+
+/// Parses an absolute date (`2024-01-01` or RFC 3339) or an age relative to
+/// now (`30d`, `6w`, `3m`, `1y`).
+fn parse_datetime(value: &str) -> Result<OffsetDateTime, QueryParseError> {
+    if let Ok(date) = Date::parse(value, format_description!("[year]-[month]-[day]")) {
+        return Ok(date.midnight().assume_utc());
+    }
+
+    if let Ok(datetime) = OffsetDateTime::parse(value, &Rfc3339) {
+        return Ok(datetime);
+    }
+
+    let age = parse_age(value).ok_or_else(|| {
+        QueryParseError(format!(
+            "invalid date '{value}', expected YYYY-MM-DD or an age like 30d, 6w, 3m, 1y"
+        ))
+    })?;
+
+    OffsetDateTime::now_utc()
+        .checked_sub(age)
+        .ok_or_else(|| QueryParseError(format!("date '{value}' is out of range")))
+}
+
+/// Parses an age like `12h`, `30d`, `6w`, `3m` or `1y`, months counting 30
+/// days and years 365. Ages too large to represent are rejected.
+pub fn parse_age(value: &str) -> Option<Duration> {
+    let unit = value.chars().last()?;
+    let amount: i64 = value[..value.len() - unit.len_utf8()].parse().ok()?;
+
+    let seconds_per_unit: i64 = match unit {
+        'h' => 3600,
+        'd' => 86_400,
+        'w' => 7 * 86_400,
+        'm' => 30 * 86_400,
+        'y' => 365 * 86_400,
+        _ => return None,
+    };
+
+    amount.checked_mul(seconds_per_unit).map(Duration::seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> SearchQuery {
+        input.parse().unwrap()
+    }
+
+    fn error(input: &str) -> String {
+        input.parse::<SearchQuery>().unwrap_err().to_string()
+    }
+
+    fn term(text: &str, negated: bool) -> Term {
+        Term {
+            negated,
+            text: text.to_string(),
+        }
+    }
+
+    fn filter(kind: FilterKind, negated: bool) -> Filter {
+        Filter { negated, kind }
+    }
+
+    #[test]
+    fn words_and_filters_are_split() {
+        assert_eq!(
+            parse("backend  lang:Rust -archived:true"),
+            SearchQuery {
+                terms: vec![term("backend", false)],
+                filters: vec![
+                    filter(FilterKind::Language("Rust".to_string()), false),
+                    filter(FilterKind::Archived(true), true),
+                ],
+            }
+        );
+        assert_eq!(parse(""), SearchQuery::default());
+    }
+
+    #[test]
+    fn quotes_keep_spaces_and_colons() {
+        let query = parse(r#""payment service" -"legacy api" path:"team a/*" "a:b""#);
+        assert_eq!(
+            query.terms,
+            vec![
+                term("payment service", false),
+                term("legacy api", true),
+                term("a:b", false),
+            ]
+        );
+        assert_eq!(
+            query.filters,
+            vec![filter(FilterKind::Path("team a/*".to_string()), false)]
+        );
+    }
+
+    #[test]
+    fn dashes_only_negate_at_the_start() {
+        assert_eq!(
+            parse("- foo-bar --x").terms,
+            vec![term("-", false), term("foo-bar", false), term("-x", true)]
+        );
+    }
+
+    #[test]
+    fn field_names_are_case_insensitive() {
+        assert_eq!(
+            parse("LANG:go Public:yes").filters,
+            vec![
+                filter(FilterKind::Language("go".to_string()), false),
+                filter(FilterKind::Private(false), false),
+            ]
+        );
+        assert!(parse("archived:no").has_archived_filter());
+        assert!(!parse("private:1").has_archived_filter());
+    }
+
+    #[test]
+    fn comparisons() {
+        let kinds: Vec<FilterKind> =
+            parse("approvals:>=2 approvals:<=3 approvals:>1 approvals:<4 approvals:=5 approvals:6")
+                .filters
+                .into_iter()
+                .map(|filter| filter.kind)
+                .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                FilterKind::Approvals(Comparison::Ge, 2),
+                FilterKind::Approvals(Comparison::Le, 3),
+                FilterKind::Approvals(Comparison::Gt, 1),
+                FilterKind::Approvals(Comparison::Lt, 4),
+                FilterKind::Approvals(Comparison::Eq, 5),
+                FilterKind::Approvals(Comparison::Eq, 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn sizes_have_binary_units() {
+        assert_eq!(parse_size("100").unwrap(), 100);
+        assert_eq!(parse_size("100b").unwrap(), 100);
+        assert_eq!(parse_size("512KB").unwrap(), 512 * 1024);
+        assert_eq!(parse_size("2m").unwrap(), 2 * 1024 * 1024);
+        assert_eq!(parse_size("1.5GB").unwrap(), 3 * 512 * 1024 * 1024);
+        assert_eq!(parse_size("1T").unwrap(), 1 << 40);
+        assert_eq!(
+            parse("size:>100MB").filters[0].kind,
+            FilterKind::Size(Comparison::Gt, 100 << 20)
+        );
+    }
+
+    #[test]
+    fn ages_have_units() {
+        assert_eq!(parse_age("12h"), Some(Duration::hours(12)));
+        assert_eq!(parse_age("30d"), Some(Duration::days(30)));
+        assert_eq!(parse_age("6w"), Some(Duration::weeks(6)));
+        assert_eq!(parse_age("3m"), Some(Duration::days(90)));
+        assert_eq!(parse_age("1y"), Some(Duration::days(365)));
+        assert_eq!(parse_age("1"), None);
+        assert_eq!(parse_age("d"), None);
+        assert_eq!(parse_age("1x"), None);
+        assert_eq!(parse_age("99999999999999y"), None);
+    }
+
+    #[test]
+    fn pushed_takes_dates_and_ages() {
+        assert_eq!(
+            parse("pushed:<2024-01-31").filters[0].kind,
+            FilterKind::Pushed(
+                Comparison::Lt,
+                Date::from_calendar_date(2024, time::Month::January, 31)
+                    .unwrap()
+                    .midnight()
+                    .assume_utc()
+            )
+        );
+        assert_eq!(
+            parse("pushed:>=2024-01-31T12:00:00+02:00").filters[0].kind,
+            FilterKind::Pushed(
+                Comparison::Ge,
+                OffsetDateTime::parse("2024-01-31T10:00:00Z", &Rfc3339).unwrap()
+            )
+        );
+
+        let FilterKind::Pushed(Comparison::Gt, pushed) = parse("pushed:>30d").filters[0].kind
+        else {
+            panic!("expected a pushed filter");
+        };
+        let age = OffsetDateTime::now_utc() - pushed;
+        assert!(age >= Duration::days(30) && age < Duration::days(30) + Duration::minutes(1));
+    }
+
+    #[test]
+    fn invalid_queries_explain_themselves() {
+        assert_eq!(error(r#"lang:"rust"#), "unterminated quote");
+        assert_eq!(error("lang:"), "missing value for 'lang:'");
+        assert!(error("owner:me").starts_with("unknown filter 'owner:', expected one of lang,"));
+        assert_eq!(
+            error("private:maybe"),
+            "invalid value 'maybe' for 'private:', expected true or false"
+        );
+        assert_eq!(error("size:>10XB"), "invalid size unit in '10XB'");
+        assert_eq!(error("size:MB"), "invalid size 'MB'");
+        assert_eq!(
+            error("approvals:>=two"),
+            "invalid number of approvals 'two'"
+        );
+        assert_eq!(
+            error("pushed:<yesterday"),
+            "invalid date 'yesterday', expected YYYY-MM-DD or an age like 30d, 6w, 3m, 1y"
+        );
+        assert_eq!(
+            error("pushed:<9999999999999d"),
+            "date '9999999999999d' is out of range"
+        );
+    }
+}
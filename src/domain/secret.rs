@@ -0,0 +1,403 @@
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tabled::Tabled;
+use time::OffsetDateTime;
+
+use super::{mirror::Transport, policy::Severity};
+use crate::{
+    error::AppError,
+    infrastructure::utils::{
+        config_file::read_config_file, repositories::display_commit, sarif::Finding,
+    },
+};
+
+/// Lines carrying this marker are never reported, e.g. test fixtures.
+const INLINE_ALLOW_MARKER: &str = "devsec:allow";
+
+/// Files larger than this are not scanned, they are rarely source files.
+pub const MAX_SCANNED_FILE_SIZE: u64 = 1024 * 1024;
+
+/// A built-in rule recognising one kind of secret. The secret is the rule's
+/// first capture group.
+#[derive(Debug)]
+pub struct SecretRule {
+    pub id: &'static str,
+    pub description: &'static str,
+    pub severity: Severity,
+    regex: Regex,
+    /// Minimum Shannon entropy in bits per character, filters out
+    /// placeholders like `changeme` for the generic rules
+    min_entropy: Option<f64>,
+}
+
+impl SecretRule {
+    fn new(
+        id: &'static str,
+        description: &'static str,
+        severity: Severity,
+        pattern: &str,
+        min_entropy: Option<f64>,
+    ) -> Self {
+        Self {
+            id,
+            description,
+            severity,
+            regex: Regex::new(pattern).expect("built-in secret rule must compile"),
+            min_entropy,
+        }
+    }
+}
+
+/// Specific rules come first, a generic match overlapping one of them isn't
+/// reported twice.
+pub static SECRET_RULES: LazyLock<Vec<SecretRule>> = LazyLock::new(|| {
+    vec![
+        SecretRule::new(
+            "private-key",
+            "Private key",
+            Severity::Critical,
+            r"(-----BEGIN (?:[A-Z0-9]+ )*PRIVATE KEY(?: BLOCK)?-----)",
+            None,
+        ),
+        SecretRule::new(
+            "aws-access-key-id",
+            "AWS access key ID",
+            Severity::High,
+            r"\b((?:AKIA|ASIA|ABIA|ACCA)[0-9A-Z]{16})\b",
+            None,
+        ),
+        SecretRule::new(
+            "aws-secret-access-key",
+            "AWS secret access key",
+            Severity::Critical,
+            r#"(?i)aws_?secret_?access_?key["']?\s*(?::|=|:=|=>)\s*["']?([A-Za-z0-9/+]{40})\b"#,
+            None,
+        ),
+        SecretRule::new(
+            "gitlab-pat",
+            "GitLab personal access token",
+            Severity::High,
+            r"\b(glpat-[0-9A-Za-z_-]{20,})",
+            None,
+        ),
+        SecretRule::new(
+            "gitlab-deploy-token",
+            "GitLab deploy token",
+            Severity::High,
+            r"\b(gldt-[0-9A-Za-z_-]{20,})",
+            None,
+        ),
+        SecretRule::new(
+            "gitlab-runner-token",
+            "GitLab runner authentication token",
+            Severity::High,
+            r"\b(glrt-[0-9A-Za-z_-]{20,})",
+            None,
+        ),
+        SecretRule::new(
+            "github-token",
+            "GitHub token",
+            Severity::High,
+            r"\b(gh[pousr]_[0-9A-Za-z]{36,})",
+            None,
+        ),
+        SecretRule::new(
+            "generic-secret",
+            "High entropy string assigned to a secret-like name",
+            Severity::Medium,
+            r#"(?i)(?:secret|token|passw(?:or)?d|pwd|api_?key|access_?key|credentials?)[\w.-]{0,20}["']?\s*(?::|=|:=|=>)\s*["']([^\s"']{12,})["']"#,
+            Some(3.5),
+        ),
+    ]
+});
+
+pub fn find_rule(id: &str) -> Option<&'static SecretRule> {
+    SECRET_RULES.iter().find(|rule| rule.id == id)
+}
+
+/// A secret on a single line.
+#[derive(Debug)]
+pub struct SecretMatch {
+    pub rule: &'static SecretRule,
+    pub secret: String,
+}
+
+/// Every secret on `line`, unless the line is marked with `devsec:allow`.
+pub fn scan_line(line: &str) -> Vec<SecretMatch> {
+    if line.contains(INLINE_ALLOW_MARKER) {
+        return Vec::new();
+    }
+
+    let mut spans: Vec<Range<usize>> = Vec::new();
+    let mut matches = Vec::new();
+
+    for rule in SECRET_RULES.iter() {
+        for captures in rule.regex.captures_iter(line) {
+            let Some(secret) = captures.get(1) else {
+                continue;
+            };
+
+            let span = secret.range();
+            if spans
+                .iter()
+                .any(|other| span.start < other.end && other.start < span.end)
+            {
+                continue;
+            }
+
+            if rule
+                .min_entropy
+                .is_some_and(|min_entropy| shannon_entropy(secret.as_str()) < min_entropy)
+            {
+                continue;
+            }
+
+            spans.push(span);
+            matches.push(SecretMatch {
+                rule,
+                secret: secret.as_str().to_string(),
+            });
+        }
+    }
+
+    matches
+}
+
+fn shannon_entropy(value: &str) -> f64 {
+    let mut counts = [0usize; 256];
+    for byte in value.bytes() {
+        counts[byte as usize] += 1;
+    }
+
+    let length = value.len() as f64;
+    counts
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let probability = *count as f64 / length;
+            -probability * probability.log2()
+        })
+        .sum()
+}
+
+/// Keeps enough of a secret to recognise it without storing it.
+pub fn redact(secret: &str) -> String {
+    let visible: String = secret.chars().take(4).collect();
+    format!("{visible}*****")
+}
+
+/// Identifies a finding across scans, `<repo>:<file>:<rule>:<line>` in the
+/// working tree and `<repo>:<commit>:<file>:<rule>:<line>` in history.
+pub fn fingerprint(
+    path: &str,
+    commit: Option<&str>,
+    file: &str,
+    rule_id: &str,
+    line: i64,
+) -> String {
+    match commit {
+        Some(commit) => format!("{path}:{commit}:{file}:{rule_id}:{line}"),
+        None => format!("{path}:{file}:{rule_id}:{line}"),
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct AllowlistFile {
+    #[serde(default)]
+    paths: Vec<String>,
+    #[serde(default)]
+    regexes: Vec<String>,
+    #[serde(default)]
+    fingerprints: Vec<String>,
+}
+
+/// Findings to ignore, by file path pattern, secret pattern or fingerprint.
+#[derive(Debug, Default)]
+pub struct Allowlist {
+    paths: Vec<Regex>,
+    regexes: Vec<Regex>,
+    fingerprints: Vec<String>,
+}
+
+impl Allowlist {
+    pub fn allows(&self, file: &str, secret: &str, fingerprint: &str) -> bool {
+        self.paths.iter().any(|path| path.is_match(file))
+            || self.regexes.iter().any(|regex| regex.is_match(secret))
+            || self
+                .fingerprints
+                .iter()
+                .any(|allowed| allowed == fingerprint)
+    }
+}
+
+/// Loads an allowlist from a TOML or YAML file, picked by its extension.
+pub fn load_allowlist(path: &Path) -> Result<Allowlist, AppError> {
+    let file: AllowlistFile = read_config_file(path).map_err(AppError::InvalidAllowlist)?;
+
+    let compile = |patterns: Vec<String>| {
+        patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern)
+                    .map_err(|err| AppError::InvalidAllowlist(format!("{pattern}: {err}")))
+            })
+            .collect::<Result<Vec<_>, _>>()
+    };
+
+    Ok(Allowlist {
+        paths: compile(file.paths)?,
+        regexes: compile(file.regexes)?,
+        fingerprints: file.fingerprints,
+    })
+}
+
+#[derive(Tabled, Serialize, Debug)]
+pub struct SecretFinding {
+    pub path: String,
+    pub file: String,
+    pub line: i64,
+    pub rule_id: String,
+    pub severity: Severity,
+    /// Commit that added the secret, unset for the current working tree
+    #[tabled(display("display_commit"))]
+    pub commit_sha: Option<String>,
+    /// Redacted secret
+    pub secret: String,
+    #[tabled(skip)]
+    pub fingerprint: String,
+    #[serde(with = "time::serde::rfc3339")]
+    #[tabled(skip)]
+    pub scanned_at: OffsetDateTime,
+    /// Link to the line in the web UI
+    #[tabled(skip)]
+    pub web_url: String,
+}
+
+impl Finding for SecretFinding {
+    fn rule_id(&self) -> &str {
+        &self.rule_id
+    }
+
+    fn rule_description(&self) -> &str {
+        find_rule(&self.rule_id)
+            .map(|rule| rule.description)
+            .unwrap_or("Potential secret")
+    }
+
+    fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    fn message(&self) -> String {
+        format!(
+            "{}: {} in {} line {}",
+            self.path,
+            self.rule_description(),
+            self.file,
+            self.line
+        )
+    }
+
+    fn location(&self) -> &str {
+        &self.web_url
+    }
+}
+
+/// Link to a line of a file at `rev`, in GitHub's or GitLab's web UI.
+pub fn blob_url(source: &str, web_url: &str, rev: &str, file: &str, line: i64) -> String {
+    let web_url = web_url.trim_end_matches('/');
+    if source == "github" {
+        format!("{web_url}/blob/{rev}/{file}#L{line}")
+    } else {
+        format!("{web_url}/-/blob/{rev}/{file}#L{line}")
+    }
+}
+
+/// Outcome of `scan secrets`.
+#[derive(Debug, Default)]
+pub struct SecretScanSummary {
+    pub findings: Vec<SecretFinding>,
+    /// Repositories that could not be fetched or scanned
+    pub failed: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct ScanOptions {
+    /// Directory new mirrors are cloned below
+    pub mirrors_dir: PathBuf,
+    /// Scan every commit instead of only the files at `HEAD`
+    pub history: bool,
+    pub transport: Transport,
+    /// Scan the existing mirrors without fetching them
+    pub offline: bool,
+    /// Number of repositories fetched and scanned at once
+    pub concurrency: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Assembled at runtime so this file doesn't trip secret scanners itself
+    fn gitlab_token() -> String {
+        ["glpat-", "Xy3vQ9rT2kLm8NpW4sZa"].concat()
+    }
+
+    fn rules(line: &str) -> Vec<(&'static str, String)> {
+        scan_line(line)
+            .into_iter()
+            .map(|secret_match| (secret_match.rule.id, secret_match.secret))
+            .collect()
+    }
+
+    #[test]
+    fn tokens_are_caught() {
+        let token = gitlab_token();
+        assert_eq!(
+            rules(&format!("curl -H 'PRIVATE-TOKEN: {token}'")),
+            vec![("gitlab-pat", token)]
+        );
+
+        let key_id = ["AKIA", "IOSFODNN7EXAMPLE"].concat();
+        assert_eq!(
+            rules(&format!("aws_access_key_id = {key_id}")),
+            vec![("aws-access-key-id", key_id)]
+        );
+
+        let header = ["-----BEGIN RSA ", "PRIVATE KEY-----"].concat();
+        assert_eq!(rules(&header), vec![("private-key", header.clone())]);
+    }
+
+    #[test]
+    fn specific_rules_win_over_generic_ones() {
+        let token = gitlab_token();
+        assert_eq!(
+            rules(&format!("api_token: \"{token}\"")),
+            vec![("gitlab-pat", token)]
+        );
+    }
+
+    #[test]
+    fn generic_secrets_need_entropy() {
+        assert_eq!(
+            rules(r#"db_password = "q7R!vX2m#Lp9sW4z""#),
+            vec![("generic-secret", "q7R!vX2m#Lp9sW4z".to_string())]
+        );
+        assert!(rules(r#"db_password = "changeme-changeme""#).is_empty());
+        assert!(rules(r#"password = "short""#).is_empty());
+    }
+
+    #[test]
+    fn marked_and_plain_lines_are_ignored() {
+        let token = gitlab_token();
+        assert!(rules(&format!("token = {token} # devsec:allow")).is_empty());
+        assert!(rules("let secret = load_secret(name);").is_empty());
+        assert!(rules("glpat-short").is_empty());
+    }
+}
@@ -1,93 +1,219 @@
+use clap::ValueEnum;
 use serde::Serialize;
 use sqlx::SqlitePool;
 use tabled::Tabled;
 
+use crate::infrastructure::utils::repositories::display_list;
+
 #[derive(Tabled, Debug, Serialize)]
 pub struct RepoStats {
     pub total_repos: i64,
-    pub largest_repo: String,
-    pub most_active_repo: String,
-    pub newest_repository: String,
-    pub most_used_language: String,
     pub private_repo_count: i64,
     pub public_repo_count: i64,
+    pub archived_repo_count: i64,
+    #[tabled(display("display_list"))]
+    pub largest_repos: Vec<String>,
+    #[tabled(display("display_list"))]
+    pub most_active_repos: Vec<String>,
+    #[tabled(display("display_list"))]
+    pub newest_repos: Vec<String>,
+    #[tabled(display("display_list"))]
+    pub most_used_languages: Vec<String>,
 }
-pub async fn get_repository_statistics(pool: &SqlitePool) -> Result<RepoStats, sqlx::Error> {
-    let row = sqlx::query!(
-            r#"
-            SELECT
-                COUNT(*) as total_repos,
-                (SELECT path FROM codebases WHERE archived = FALSE ORDER BY size DESC LIMIT 1) as largest_repo,
-                (SELECT path FROM codebases WHERE archived = FALSE ORDER BY commit_count DESC LIMIT 1) as most_active_repo
-            FROM codebases
-            WHERE archived = FALSE
-            "#
-        ).fetch_one(pool)
-        .await?;
-
-    let most_used_language = sqlx::query!(
-        r#"
-            SELECT
-                programming_languages.name,
-                COUNT(DISTINCT codebase_languages.language_id) as unique_languages
-            FROM codebase_languages
-            JOIN programming_languages ON codebase_languages.language_id = programming_languages.id
-            JOIN codebases ON codebase_languages.codebase_id = codebases.id
-            WHERE codebases.archived = FALSE
-            GROUP BY programming_languages.name
-            ORDER BY SUM(codebase_languages.percentage) DESC
-            LIMIT 1
-            "#
-    )
-    .fetch_optional(pool)
-    .await?
-    .map(|row| row.name)
-    .unwrap_or_else(|| "Unknown".to_string());
 
-    let private_repo_count = get_private_repo_count(pool).await?;
-    let public_repo_count = get_public_repo_count(pool).await?;
-    let newest_repository = get_newest_repo(pool).await?;
+#[derive(Tabled, Debug, Serialize)]
+pub struct StatsGroup {
+    pub group: String,
+    pub repo_count: i64,
+    pub private_repo_count: i64,
+    pub public_repo_count: i64,
+    pub archived_repo_count: i64,
+    pub total_size: i64,
+    pub total_commits: i64,
+}
 
-    Ok(RepoStats {
-        total_repos: row.total_repos,
-        largest_repo: row.largest_repo.unwrap_or_default(),
-        most_active_repo: row.most_active_repo.unwrap_or_default(),
-        newest_repository,
-        most_used_language,
-        private_repo_count,
-        public_repo_count,
-    })
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum StatsGrouping {
+    Language,
+    Visibility,
+    Archived,
+    Namespace,
 }
 
-async fn get_private_repo_count(pool: &SqlitePool) -> Result<i64, sqlx::Error> {
-    Ok(sqlx::query!(
+pub async fn get_repository_statistics(
+    pool: &SqlitePool,
+    top: i64,
+) -> Result<RepoStats, sqlx::Error> {
+    let counts = sqlx::query!(
         r#"
-        SELECT COUNT(*) private_count FROM codebases WHERE private = TRUE
+        SELECT
+            COUNT(*) as "total_repos!: i64",
+            COALESCE(SUM(private = TRUE), 0) as "private_repo_count!: i64",
+            COALESCE(SUM(private = FALSE), 0) as "public_repo_count!: i64",
+            COALESCE(SUM(archived = TRUE), 0) as "archived_repo_count!: i64"
+        FROM codebases
+        WHERE deleted_at IS NULL
         "#
     )
     .fetch_one(pool)
-    .await?
-    .private_count)
-}
+    .await?;
 
-async fn get_public_repo_count(pool: &SqlitePool) -> Result<i64, sqlx::Error> {
-    Ok(sqlx::query!(
+    let largest_repos = sqlx::query_scalar!(
         r#"
-        SELECT COUNT(*) public_count FROM codebases WHERE private = FALSE
-        "#
+        SELECT path FROM codebases
+        WHERE archived = FALSE AND deleted_at IS NULL
+        ORDER BY size DESC
+        LIMIT ?
+        "#,
+        top
     )
-    .fetch_one(pool)
-    .await?
-    .public_count)
-}
+    .fetch_all(pool)
+    .await?;
 
-async fn get_newest_repo(pool: &SqlitePool) -> Result<String, sqlx::Error> {
-    Ok(sqlx::query!(
+    let most_active_repos = sqlx::query_scalar!(
         r#"
-        SELECT path FROM codebases WHERE archived = FALSE ORDER BY created_at DESC LIMIT 1;
-        "#
+        SELECT path FROM codebases
+        WHERE archived = FALSE AND deleted_at IS NULL
+        ORDER BY commit_count DESC
+        LIMIT ?
+        "#,
+        top
     )
-    .fetch_one(pool)
-    .await?
-    .path)
+    .fetch_all(pool)
+    .await?;
+
+    let newest_repos = sqlx::query_scalar!(
+        r#"
+        SELECT path FROM codebases
+        WHERE archived = FALSE AND deleted_at IS NULL
+        ORDER BY created_at DESC
+        LIMIT ?
+        "#,
+        top
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let most_used_languages = sqlx::query_scalar!(
+        r#"
+        SELECT programming_languages.name
+        FROM codebase_languages
+        JOIN programming_languages ON codebase_languages.language_id = programming_languages.id
+        JOIN codebases ON codebase_languages.codebase_id = codebases.id
+        WHERE codebases.archived = FALSE AND codebases.deleted_at IS NULL
+        GROUP BY programming_languages.name
+        ORDER BY SUM(codebase_languages.percentage) DESC
+        LIMIT ?
+        "#,
+        top
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(RepoStats {
+        total_repos: counts.total_repos,
+        private_repo_count: counts.private_repo_count,
+        public_repo_count: counts.public_repo_count,
+        archived_repo_count: counts.archived_repo_count,
+        largest_repos,
+        most_active_repos,
+        newest_repos,
+        most_used_languages,
+    })
+}
+
+pub async fn get_grouped_statistics(
+    pool: &SqlitePool,
+    grouping: StatsGrouping,
+) -> Result<Vec<StatsGroup>, sqlx::Error> {
+    match grouping {
+        StatsGrouping::Language => {
+            sqlx::query_as!(
+                StatsGroup,
+                r#"
+                SELECT
+                    l.name as "group!: String",
+                    COUNT(*) as "repo_count!: i64",
+                    COALESCE(SUM(c.private = TRUE), 0) as "private_repo_count!: i64",
+                    COALESCE(SUM(c.private = FALSE), 0) as "public_repo_count!: i64",
+                    COALESCE(SUM(c.archived = TRUE), 0) as "archived_repo_count!: i64",
+                    COALESCE(SUM(c.size), 0) as "total_size!: i64",
+                    COALESCE(SUM(c.commit_count), 0) as "total_commits!: i64"
+                FROM codebases c
+                JOIN codebase_languages cl ON cl.codebase_id = c.id
+                JOIN programming_languages l ON l.id = cl.language_id
+                WHERE c.deleted_at IS NULL
+                GROUP BY l.name
+                ORDER BY COUNT(*) DESC, l.name
+                "#
+            )
+            .fetch_all(pool)
+            .await
+        }
+        StatsGrouping::Visibility => {
+            sqlx::query_as!(
+                StatsGroup,
+                r#"
+                SELECT
+                    CASE WHEN private THEN 'private' ELSE 'public' END as "group!: String",
+                    COUNT(*) as "repo_count!: i64",
+                    COALESCE(SUM(private = TRUE), 0) as "private_repo_count!: i64",
+                    COALESCE(SUM(private = FALSE), 0) as "public_repo_count!: i64",
+                    COALESCE(SUM(archived = TRUE), 0) as "archived_repo_count!: i64",
+                    COALESCE(SUM(size), 0) as "total_size!: i64",
+                    COALESCE(SUM(commit_count), 0) as "total_commits!: i64"
+                FROM codebases
+                WHERE deleted_at IS NULL
+                GROUP BY 1
+                ORDER BY COUNT(*) DESC
+                "#
+            )
+            .fetch_all(pool)
+            .await
+        }
+        StatsGrouping::Archived => {
+            sqlx::query_as!(
+                StatsGroup,
+                r#"
+                SELECT
+                    CASE WHEN archived THEN 'archived' ELSE 'active' END as "group!: String",
+                    COUNT(*) as "repo_count!: i64",
+                    COALESCE(SUM(private = TRUE), 0) as "private_repo_count!: i64",
+                    COALESCE(SUM(private = FALSE), 0) as "public_repo_count!: i64",
+                    COALESCE(SUM(archived = TRUE), 0) as "archived_repo_count!: i64",
+                    COALESCE(SUM(size), 0) as "total_size!: i64",
+                    COALESCE(SUM(commit_count), 0) as "total_commits!: i64"
+                FROM codebases
+                WHERE deleted_at IS NULL
+                GROUP BY 1
+                ORDER BY COUNT(*) DESC
+                "#
+            )
+            .fetch_all(pool)
+            .await
+        }
+        StatsGrouping::Namespace => {
+            // rtrim() strips every trailing character that is not a '/', which
+            // leaves the path up to and including the last separator.
+            sqlx::query_as!(
+                StatsGroup,
+                r#"
+                SELECT
+                    RTRIM(RTRIM(path, REPLACE(path, '/', '')), '/') as "group!: String",
+                    COUNT(*) as "repo_count!: i64",
+                    COALESCE(SUM(private = TRUE), 0) as "private_repo_count!: i64",
+                    COALESCE(SUM(private = FALSE), 0) as "public_repo_count!: i64",
+                    COALESCE(SUM(archived = TRUE), 0) as "archived_repo_count!: i64",
+                    COALESCE(SUM(size), 0) as "total_size!: i64",
+                    COALESCE(SUM(commit_count), 0) as "total_commits!: i64"
+                FROM codebases
+                WHERE deleted_at IS NULL
+                GROUP BY 1
+                ORDER BY COUNT(*) DESC, 1
+                "#
+            )
+            .fetch_all(pool)
+            .await
+        }
+    }
 }
@@ -0,0 +1,376 @@
+use std::{collections::HashMap, str::FromStr};
+
+use serde::Serialize;
+use tabled::Tabled;
+use time::{Duration, OffsetDateTime};
+
+use super::search_query::parse_age;
+
+#[derive(Debug, Default, Clone)]
+pub struct SyncOptions {
+    /// Delete codebases that disappeared upstream instead of marking them
+    pub prune: bool,
+    /// Number of fetched pages that may wait while earlier pages are being
+    /// written. Pages are fetched one after another, each needs the cursor of
+    /// the previous one.
+    pub prefetch: usize,
+    /// Continue the last run of the same source and scope if it did not complete
+    pub resume: bool,
+    /// Fetch every codebase even if an earlier run left a watermark
+    pub full: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum SyncStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+/// A full run lists every codebase of the scope, an incremental run only those
+/// pushed to or updated since the scope's watermark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum SyncKind {
+    Full,
+    Incremental,
+}
+
+/// Order an incremental run requests codebases in. Pushes only move the last
+/// activity, settings changes such as the visibility only the update time, so
+/// incremental runs make one pass in each order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncOrder {
+    Activity,
+    Update,
+}
+
+/// One `devsec update` run against a single source and scope (GitLab group or
+/// GitHub organization).
+#[derive(Debug, Clone)]
+pub struct SyncRun {
+    pub id: i64,
+    pub source: String,
+    pub scope: String,
+    pub started_at: OffsetDateTime,
+    pub kind: SyncKind,
+    /// Pagination cursor after the last page the run has written
+    pub end_cursor: Option<String>,
+}
+
+/// State of a codebase as observed by a sync run. The snapshot table also keeps
+/// activity, commit count and languages for later analysis.
+#[derive(Debug, Clone)]
+pub struct CodebaseSnapshot {
+    pub codebase_id: i64,
+    pub path: String,
+    pub private: bool,
+    pub archived: bool,
+    pub size: i64,
+    /// Tombstone recorded when a complete run no longer saw the codebase
+    pub deleted: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Created,
+    Deleted,
+    Moved,
+    Archived,
+    MadePublic,
+    Grew,
+}
+
+impl std::fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ChangeKind::Created => "created",
+            ChangeKind::Deleted => "deleted",
+            ChangeKind::Moved => "moved",
+            ChangeKind::Archived => "archived",
+            ChangeKind::MadePublic => "made public",
+            ChangeKind::Grew => "grew",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Tabled, Serialize, Debug)]
+pub struct CodebaseChange {
+    pub change: ChangeKind,
+    pub path: String,
+    pub source: String,
+    pub detail: String,
+    #[tabled(skip)]
+    pub base_run_id: i64,
+    #[tabled(skip)]
+    pub head_run_id: i64,
+}
+
+/// Compares the state of one source/scope at two runs.
+///
+/// `base` and `head` hold the most recent snapshot of every codebase as of the
+/// respective run.
+pub fn diff_snapshots(
+    base_run: &SyncRun,
+    head_run: &SyncRun,
+    base: &[CodebaseSnapshot],
+    head: &[CodebaseSnapshot],
+    min_growth_percent: f64,
+) -> Vec<CodebaseChange> {
+    let base_by_id: HashMap<i64, &CodebaseSnapshot> = base
+        .iter()
+        .map(|snapshot| (snapshot.codebase_id, snapshot))
+        .collect();
+
+    let change = |change: ChangeKind, path: &str, detail: String| CodebaseChange {
+        change,
+        path: path.to_string(),
+        source: head_run.source.clone(),
+        detail,
+        base_run_id: base_run.id,
+        head_run_id: head_run.id,
+    };
+
+    let mut changes = Vec::new();
+
+    for snapshot in head {
+        let before = base_by_id
+            .get(&snapshot.codebase_id)
+            .filter(|before| !before.deleted);
+
+        if snapshot.deleted {
+            if before.is_some() {
+                changes.push(change(ChangeKind::Deleted, &snapshot.path, String::new()));
+            }
+            continue;
+        }
+
+        let Some(before) = before else {
+            changes.push(change(ChangeKind::Created, &snapshot.path, String::new()));
+            continue;
+        };
+
+        if before.path != snapshot.path {
+            changes.push(change(
+                ChangeKind::Moved,
+                &snapshot.path,
+                format!("{} -> {}", before.path, snapshot.path),
+            ));
+        }
+
+        if !before.archived && snapshot.archived {
+            changes.push(change(ChangeKind::Archived, &snapshot.path, String::new()));
+        }
+
+        if before.private && !snapshot.private {
+            changes.push(change(
+                ChangeKind::MadePublic,
+                &snapshot.path,
+                "visibility changed from private to public".to_string(),
+            ));
+        }
+
+        if before.size > 0 {
+            let growth = (snapshot.size - before.size) as f64 * 100.0 / before.size as f64;
+            if growth >= min_growth_percent {
+                changes.push(change(
+                    ChangeKind::Grew,
+                    &snapshot.path,
+                    format!("{} -> {} bytes (+{growth:.0}%)", before.size, snapshot.size),
+                ));
+            }
+        }
+    }
+
+    changes
+}
+
+/// Baseline for `devsec diff`, either a sync run id or an age like `7d`.
+#[derive(Debug, Clone, Copy)]
+pub enum Since {
+    Run(i64),
+    Age(Duration),
+}
+
+impl FromStr for Since {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Ok(run_id) = value.parse() {
+            return Ok(Since::Run(run_id));
+        }
+
+        parse_age(value)
+            .map(Since::Age)
+            .ok_or_else(|| format!("expected a sync run id or an age like 7d, got '{value}'"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(id: i64) -> SyncRun {
+        SyncRun {
+            id,
+            source: "gitlab".to_string(),
+            scope: "grp".to_string(),
+            started_at: OffsetDateTime::UNIX_EPOCH,
+            kind: SyncKind::Full,
+            end_cursor: None,
+        }
+    }
+
+    fn snapshot(codebase_id: i64, path: &str) -> CodebaseSnapshot {
+        CodebaseSnapshot {
+            codebase_id,
+            path: path.to_string(),
+            private: true,
+            archived: false,
+            size: 1000,
+            deleted: false,
+        }
+    }
+
+    fn diff(
+        base: &[CodebaseSnapshot],
+        head: &[CodebaseSnapshot],
+    ) -> Vec<(ChangeKind, String, String)> {
+        diff_snapshots(&run(1), &run(2), base, head, 50.0)
+            .into_iter()
+            .map(|change| {
+                assert_eq!((change.base_run_id, change.head_run_id), (1, 2));
+                assert_eq!(change.source, "gitlab");
+                (change.change, change.path, change.detail)
+            })
+            .collect()
+    }
+
+    fn change(kind: ChangeKind, path: &str, detail: &str) -> (ChangeKind, String, String) {
+        (kind, path.to_string(), detail.to_string())
+    }
+
+    #[test]
+    fn unchanged_codebases_are_not_reported() {
+        let snapshots = [snapshot(1, "grp/a"), snapshot(2, "grp/b")];
+        assert!(diff(&snapshots, &snapshots).is_empty());
+    }
+
+    #[test]
+    fn created_and_deleted() {
+        let base = [snapshot(1, "grp/a")];
+        let head = [
+            CodebaseSnapshot {
+                deleted: true,
+                ..snapshot(1, "grp/a")
+            },
+            snapshot(2, "grp/b"),
+        ];
+        assert_eq!(
+            diff(&base, &head),
+            vec![
+                change(ChangeKind::Deleted, "grp/a", ""),
+                change(ChangeKind::Created, "grp/b", ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn tombstones_only_count_once() {
+        let deleted = CodebaseSnapshot {
+            deleted: true,
+            ..snapshot(1, "grp/a")
+        };
+        // Deleted before the base run: neither deleted again nor created
+        let tombstones = [deleted];
+        assert!(diff(&tombstones, &tombstones).is_empty());
+        // Deleted at the base run and back at the head run
+        assert_eq!(
+            diff(&tombstones, &[snapshot(1, "grp/a")]),
+            vec![change(ChangeKind::Created, "grp/a", "")]
+        );
+    }
+
+    #[test]
+    fn moved_archived_and_made_public() {
+        let base = [snapshot(1, "grp/a")];
+        let head = [CodebaseSnapshot {
+            private: false,
+            archived: true,
+            ..snapshot(1, "other/a")
+        }];
+        assert_eq!(
+            diff(&base, &head),
+            vec![
+                change(ChangeKind::Moved, "other/a", "grp/a -> other/a"),
+                change(ChangeKind::Archived, "other/a", ""),
+                change(
+                    ChangeKind::MadePublic,
+                    "other/a",
+                    "visibility changed from private to public"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn unarchived_and_made_private_are_not_reported() {
+        let base = [CodebaseSnapshot {
+            private: false,
+            archived: true,
+            ..snapshot(1, "grp/a")
+        }];
+        assert!(diff(&base, &[snapshot(1, "grp/a")]).is_empty());
+    }
+
+    #[test]
+    fn growth_above_the_threshold() {
+        let base = [
+            snapshot(1, "grp/a"),
+            snapshot(2, "grp/b"),
+            snapshot(3, "grp/c"),
+        ];
+        let head = [
+            CodebaseSnapshot {
+                size: 1500,
+                ..snapshot(1, "grp/a")
+            },
+            CodebaseSnapshot {
+                size: 1499,
+                ..snapshot(2, "grp/b")
+            },
+            CodebaseSnapshot {
+                size: 100,
+                ..snapshot(3, "grp/c")
+            },
+        ];
+        assert_eq!(
+            diff(&base, &head),
+            vec![change(
+                ChangeKind::Grew,
+                "grp/a",
+                "1000 -> 1500 bytes (+50%)"
+            )]
+        );
+
+        // A codebase that was empty has no growth rate
+        let empty = [CodebaseSnapshot {
+            size: 0,
+            ..snapshot(1, "grp/a")
+        }];
+        assert!(diff(&empty, &head[..1]).is_empty());
+    }
+
+    #[test]
+    fn since_is_a_run_id_or_an_age() {
+        assert!(matches!("42".parse(), Ok(Since::Run(42))));
+        assert!(matches!("7d".parse(), Ok(Since::Age(age)) if age == Duration::days(7)));
+        assert_eq!(
+            "last week".parse::<Since>().unwrap_err(),
+            "expected a sync run id or an age like 7d, got 'last week'"
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use sqlx::migrate::MigrateError;
 
+use crate::domain::search_query::QueryParseError;
+
 #[derive(thiserror::Error, Debug)]
 pub enum AppError {
     #[error("HTTP client error: {0}")]
@@ -25,6 +27,30 @@ pub enum AppError {
 
     #[error("Migration Error {0}")]
     MigrationError(#[from] MigrateError),
+
+    #[error("Invalid URL: {0}")]
+    InvalidUrl(String),
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Invalid search query: {0}")]
+    InvalidQuery(#[from] QueryParseError),
+
+    #[error("Invalid policy: {0}")]
+    InvalidPolicy(String),
+
+    #[error("Invalid allowlist: {0}")]
+    InvalidAllowlist(String),
+
+    #[error("Git error: {0}")]
+    GitError(String),
+
+    #[error("Zip error: {0}")]
+    ZipError(#[from] zip::result::ZipError),
+
+    #[error("Invalid config: {0}")]
+    InvalidConfig(String),
 }
 
 impl AppError {
@@ -0,0 +1,208 @@
+use std::{sync::LazyLock, time::Duration};
+
+use model::{OrganizationData, RepositoryConnection};
+use reqwest::{
+    Url,
+    header::{AUTHORIZATION, HeaderMap, HeaderValue, USER_AGENT},
+};
+use serde_json::json;
+
+use crate::{domain::sync::SyncOrder, error::AppError, infrastructure::api::graphql};
+
+static GITHUB_GRAPHQL_URL: LazyLock<Url> =
+    LazyLock::new(|| Url::parse("https://api.github.com/graphql").unwrap());
+
+#[derive(Clone)]
+pub struct GitHubClient {
+    client: reqwest::Client,
+}
+
+impl GitHubClient {
+    pub fn new(token: &str, timeout: Duration) -> Self {
+        let mut headers = HeaderMap::new();
+
+        if let Ok(mut token) = HeaderValue::from_str(&format!("Bearer {token}")) {
+            token.set_sensitive(true);
+            headers.insert(AUTHORIZATION, token);
+        } else {
+            eprintln!("Could not set Authorization header");
+        }
+
+        // GitHub rejects API requests without a User-Agent
+        headers.insert(USER_AGENT, HeaderValue::from_static("devsec"));
+
+        let client = reqwest::Client::builder()
+            .default_headers(headers)
+            .timeout(timeout)
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Self { client }
+    }
+
+    /// Fetches one page of the organization's repositories, most recent first
+    /// in `order` if one is given.
+    pub async fn get_repositories_after(
+        &self,
+        org: &str,
+        after: Option<&str>,
+        order: Option<SyncOrder>,
+    ) -> Result<RepositoryConnection, AppError> {
+        let query = r#"
+            query GetOrganizationRepositories($org: String!, $after: String, $order: RepositoryOrder) {
+                organization(login: $org) {
+                    repositories(first: 100, after: $after, orderBy: $order) {
+                        totalCount
+                        pageInfo {
+                            endCursor
+                            hasNextPage
+                        }
+                        nodes {
+                            databaseId
+                            nameWithOwner
+                            description
+                            url
+                            sshUrl
+                            createdAt
+                            updatedAt
+                            pushedAt
+                            isPrivate
+                            isArchived
+                            diskUsage
+                            defaultBranchRef {
+                                name
+                                target {
+                                    ... on Commit {
+                                        history {
+                                            totalCount
+                                        }
+                                    }
+                                }
+                            }
+                            languages(first: 20, orderBy: { field: SIZE, direction: DESC }) {
+                                totalSize
+                                edges {
+                                    size
+                                    node {
+                                        name
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        "#;
+
+        let mut variables = match after {
+            Some(after) => json!({ "org": org, "after": after }),
+            None => json!({ "org": org }),
+        };
+        if let Some(order) = order {
+            let field = match order {
+                SyncOrder::Activity => "PUSHED_AT",
+                SyncOrder::Update => "UPDATED_AT",
+            };
+            variables["order"] = json!({ "field": field, "direction": "DESC" });
+        }
+
+        let data = json!({ "query": query, "variables": variables });
+
+        let response: OrganizationData =
+            graphql::post(&self.client, &GITHUB_GRAPHQL_URL, &data, "GitHub").await?;
+
+        let organization = response
+            .organization
+            .ok_or_else(|| AppError::NotFound(format!("GitHub organization {org}")))?;
+
+        Ok(organization.repositories)
+    }
+}
+
+pub mod model {
+    use serde::{Deserialize, Serialize};
+    use time::OffsetDateTime;
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct OrganizationData {
+        pub organization: Option<Organization>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct Organization {
+        pub repositories: RepositoryConnection,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    pub struct RepositoryConnection {
+        pub total_count: i64,
+        pub page_info: PageInfo,
+        pub nodes: Vec<Repository>,
+    }
+
+    #[derive(Deserialize, Serialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Repository {
+        pub database_id: i64,
+        pub name_with_owner: String,
+        pub description: Option<String>,
+        pub url: String,
+        pub ssh_url: String,
+        #[serde(with = "time::serde::rfc3339")]
+        pub created_at: OffsetDateTime,
+        #[serde(with = "time::serde::rfc3339")]
+        pub updated_at: OffsetDateTime,
+        /// `null` for repositories that have never been pushed to.
+        #[serde(with = "time::serde::rfc3339::option")]
+        pub pushed_at: Option<OffsetDateTime>,
+        pub is_private: bool,
+        pub is_archived: bool,
+        /// Repository size in kilobytes.
+        pub disk_usage: Option<i64>,
+        pub default_branch_ref: Option<BranchRef>,
+        pub languages: LanguageConnection,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct BranchRef {
+        pub name: String,
+        pub target: Option<CommitTarget>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct CommitTarget {
+        pub history: Option<CommitHistory>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    pub struct CommitHistory {
+        pub total_count: i64,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    pub struct LanguageConnection {
+        pub total_size: i64,
+        pub edges: Vec<LanguageEdge>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct LanguageEdge {
+        pub size: i64,
+        pub node: Language,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct Language {
+        pub name: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    pub struct PageInfo {
+        pub end_cursor: Option<String>,
+        pub has_next_page: bool,
+    }
+}
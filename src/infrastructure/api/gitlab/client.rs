@@ -1,17 +1,49 @@
-use model::GroupProjectsResponse;
-use reqwest::header::{AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderValue};
+use std::{path::PathBuf, time::Duration};
+
+use model::{
+    AccessToken, CurrentUser, DeployKey, DeployToken, GroupData, GroupDetails, ProjectConnection,
+};
+use reqwest::{
+    Certificate, Url,
+    header::{AUTHORIZATION, HeaderMap, HeaderValue},
+};
 use serde_json::json;
 
-use crate::error::AppError;
+use crate::{
+    domain::sync::SyncOrder,
+    error::AppError,
+    infrastructure::api::{graphql, rest},
+};
+
+pub const GITLAB_DEFAULT_URL: &str = "https://gitlab.com";
 
-const GITLAB_GRAPHQL_URL: &str = "https://gitlab.com/api/graphql";
+/// Connection settings for a GitLab instance.
+#[derive(Debug)]
+pub struct GitLabInstance {
+    pub url: String,
+    pub ca_cert: Option<PathBuf>,
+    pub accept_invalid_certs: bool,
+    pub timeout: Duration,
+}
 
+#[derive(Clone)]
 pub struct GitLabClient {
     client: reqwest::Client,
+    graphql_url: Url,
+    /// Base of the REST API, `<instance>/api/v4/`
+    rest_url: Url,
+    source: String,
 }
 
 impl GitLabClient {
-    pub fn new(token: &str) -> Self {
+    pub fn new(token: &str, instance: &GitLabInstance) -> Result<Self, AppError> {
+        let base_url = instance.url.trim_end_matches('/');
+        let graphql_url = Url::parse(&format!("{base_url}/api/graphql"))
+            .map_err(|err| AppError::InvalidUrl(format!("{}: {err}", instance.url)))?;
+        let rest_url = Url::parse(&format!("{base_url}/api/v4/"))
+            .map_err(|err| AppError::InvalidUrl(format!("{}: {err}", instance.url)))?;
+        let source = source_for(&graphql_url);
+
         let mut headers = HeaderMap::new();
 
         if let Ok(mut token) = HeaderValue::from_str(&format!("Bearer {token}")) {
@@ -21,23 +53,96 @@ impl GitLabClient {
             eprintln!("Could not set Authorization header");
         }
 
-        let client = reqwest::Client::builder()
+        let mut builder = reqwest::Client::builder()
             .default_headers(headers)
-            .build()
-            .expect("Failed to create HTTP client");
+            .danger_accept_invalid_certs(instance.accept_invalid_certs)
+            .timeout(instance.timeout);
+
+        if let Some(ca_cert) = &instance.ca_cert {
+            let pem = std::fs::read(ca_cert)?;
+            for certificate in Certificate::from_pem_bundle(&pem)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        let client = builder.build()?;
+
+        Ok(Self {
+            client,
+            graphql_url,
+            rest_url,
+            source,
+        })
+    }
+
+    /// Value stored in `codebases.source` for projects of this instance.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The user the token belongs to.
+    pub async fn get_current_user(&self) -> Result<CurrentUser, AppError> {
+        rest::get(&self.client, &self.rest_endpoint("user")?, "GitLab").await
+    }
+
+    /// Scopes and expiry of the token itself. Personal, project and group
+    /// access tokens can be introspected, other tokens (e.g. OAuth) and
+    /// instances before GitLab 15.5 answer with [`AppError::NotFound`].
+    pub async fn get_token(&self) -> Result<AccessToken, AppError> {
+        let url = self.rest_endpoint("personal_access_tokens/self")?;
+        rest::get(&self.client, &url, "GitLab").await
+    }
+
+    /// Access tokens of a project or group, see [`owner_endpoint`].
+    pub async fn get_access_tokens(&self, owner: &str) -> Result<Vec<AccessToken>, AppError> {
+        let url = self.rest_endpoint(&format!("{owner}/access_tokens"))?;
+        rest::get_all(&self.client, &url, "GitLab").await
+    }
+
+    /// Deploy tokens of a project or group, see [`owner_endpoint`].
+    pub async fn get_deploy_tokens(&self, owner: &str) -> Result<Vec<DeployToken>, AppError> {
+        let url = self.rest_endpoint(&format!("{owner}/deploy_tokens"))?;
+        rest::get_all(&self.client, &url, "GitLab").await
+    }
+
+    /// A group by numeric id or by full path in any case, with its canonical
+    /// full path.
+    pub async fn get_group(&self, group: &str) -> Result<GroupDetails, AppError> {
+        let endpoint = owner_endpoint(None, group);
+        let url = self.rest_endpoint(&format!("{endpoint}?with_projects=false"))?;
+        rest::get(&self.client, &url, "GitLab").await
+    }
 
-        Self { client }
+    /// Subgroups of a group at any depth.
+    pub async fn get_descendant_groups(&self, group: &str) -> Result<Vec<GroupDetails>, AppError> {
+        let endpoint = owner_endpoint(None, group);
+        let url = self.rest_endpoint(&format!("{endpoint}/descendant_groups"))?;
+        rest::get_all(&self.client, &url, "GitLab").await
     }
 
+    pub async fn get_deploy_keys(&self, project_id: i64) -> Result<Vec<DeployKey>, AppError> {
+        let url = self.rest_endpoint(&format!("projects/{project_id}/deploy_keys"))?;
+        rest::get_all(&self.client, &url, "GitLab").await
+    }
+
+    fn rest_endpoint(&self, path: &str) -> Result<Url, AppError> {
+        self.rest_url
+            .join(path)
+            .map_err(|err| AppError::InvalidUrl(format!("{path}: {err}")))
+    }
+
+    /// Fetches one page of the group's projects, most recent first in `order`
+    /// if one is given.
     pub async fn get_projects_after(
         &self,
         group: &str,
         after: Option<&str>,
-    ) -> Result<GroupProjectsResponse, AppError> {
+        order: Option<SyncOrder>,
+    ) -> Result<ProjectConnection, AppError> {
         let query = r#"
-            query GetGroupProjects($group_id: ID!, $after: String) {
+            query GetGroupProjects($group_id: ID!, $after: String, $sort: NamespaceProjectSort) {
                 group(fullPath: $group_id) {
-                    projects(includeSubgroups: true, after: $after) {
+                    projects(includeSubgroups: true, after: $after, sort: $sort) {
                         count
                         pageInfo {
                             endCursor
@@ -63,48 +168,170 @@ impl GitLabClient {
                                 repositorySize
                                 commitCount
                             }
+                            repository {
+                                rootRef
+                            }
+                            onlyAllowMergeIfPipelineSucceeds
+                            secretPushProtectionEnabled
+                            securityScanners {
+                                enabled
+                            }
+                            branchRules(first: 100) {
+                                pageInfo {
+                                    endCursor
+                                    hasNextPage
+                                }
+                                nodes {
+                                    name
+                                    isDefault
+                                    isProtected
+                                    approvalRules {
+                                        nodes {
+                                            approvalsRequired
+                                        }
+                                    }
+                                    branchProtection {
+                                        allowForcePush
+                                        codeOwnerApprovalRequired
+                                        pushAccessLevels {
+                                            nodes {
+                                                accessLevel
+                                                accessLevelDescription
+                                            }
+                                        }
+                                        mergeAccessLevels {
+                                            nodes {
+                                                accessLevel
+                                                accessLevelDescription
+                                            }
+                                        }
+                                    }
+                                }
+                            }
                         }
                     }
                 }
             }
         "#;
 
-        let variables = match after {
+        let mut variables = match after {
             Some(after) => json!({ "group_id": group, "after": after }),
             None => json!({ "group_id": group }),
         };
+        // Left unset, GitLab keeps its default order
+        if let Some(order) = order {
+            variables["sort"] = json!(match order {
+                SyncOrder::Activity => "ACTIVITY_DESC",
+                SyncOrder::Update => "UPDATED_DESC",
+            });
+        }
 
         let data = json!({ "query": query, "variables": variables });
 
-        let response = self
-            .client
-            .post(GITLAB_GRAPHQL_URL)
-            .header(CONTENT_TYPE, "application/json")
-            .json(&data)
-            .send()
-            .await?;
+        let response: GroupData =
+            graphql::post(&self.client, &self.graphql_url, &data, "GitLab").await?;
+
+        // GitLab answers unknown or inaccessible groups with a null group
+        let group = response
+            .group
+            .ok_or_else(|| AppError::NotFound(format!("GitLab group {group}")))?;
 
-        let json = response.json::<GroupProjectsResponse>().await?;
-        Ok(json)
+        Ok(group.projects)
     }
+}
 
-    pub async fn get_projects(&self, group: &str) -> Result<GroupProjectsResponse, AppError> {
-        self.get_projects_after(group, None).await
+/// REST path of a project by id or a group by full path, e.g. `groups/a%2Fb`.
+pub fn owner_endpoint(project_id: Option<i64>, path: &str) -> String {
+    match project_id {
+        Some(id) => format!("projects/{id}"),
+        None => format!("groups/{}", path.replace('/', "%2F")),
+    }
+}
+
+/// gitlab.com keeps the plain `gitlab` source, other instances are qualified by
+/// host so that project ids from different instances don't collide.
+fn source_for(url: &Url) -> String {
+    match (url.host_str(), url.port()) {
+        (Some("gitlab.com"), None) | (None, _) => "gitlab".to_string(),
+        (Some(host), None) => format!("gitlab:{host}"),
+        (Some(host), Some(port)) => format!("gitlab:{host}:{port}"),
     }
 }
 
 pub mod model {
-    use serde::{Deserialize, Serialize};
-    use time::OffsetDateTime;
+    use serde::{Deserialize, Deserializer, Serialize, de::Error};
+    use time::{Date, OffsetDateTime, macros::format_description};
 
-    #[derive(Serialize, Deserialize, Debug)]
-    pub struct GroupProjectsResponse {
-        pub data: GroupData,
+    #[derive(Deserialize, Debug)]
+    pub struct CurrentUser {
+        pub username: String,
+    }
+
+    /// A personal, project or group access token.
+    #[derive(Deserialize, Debug)]
+    pub struct AccessToken {
+        pub id: i64,
+        pub name: String,
+        pub scopes: Vec<String>,
+        /// False once the token expired or was revoked
+        pub active: bool,
+        pub revoked: bool,
+        #[serde(default, with = "time::serde::rfc3339::option")]
+        pub created_at: Option<OffsetDateTime>,
+        #[serde(default, with = "time::serde::rfc3339::option")]
+        pub last_used_at: Option<OffsetDateTime>,
+        /// The token stops working at the start of this day (UTC)
+        #[serde(default, deserialize_with = "date")]
+        pub expires_at: Option<Date>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    pub struct DeployKey {
+        pub id: i64,
+        pub title: String,
+        #[serde(default)]
+        pub can_push: bool,
+        #[serde(default, with = "time::serde::rfc3339::option")]
+        pub created_at: Option<OffsetDateTime>,
+        #[serde(default, deserialize_with = "date")]
+        pub expires_at: Option<Date>,
+    }
+
+    /// A group as the REST API describes it.
+    #[derive(Deserialize, Debug)]
+    pub struct GroupDetails {
+        pub full_path: String,
+        pub web_url: String,
+    }
+
+    #[derive(Deserialize, Debug)]
+    pub struct DeployToken {
+        pub id: i64,
+        pub name: String,
+        pub scopes: Vec<String>,
+        #[serde(default)]
+        pub revoked: bool,
+        #[serde(default)]
+        pub expired: bool,
+        #[serde(default, deserialize_with = "date")]
+        pub expires_at: Option<Date>,
+    }
+
+    /// Expiry dates are plain dates for access tokens but timestamps for
+    /// deploy keys and tokens, only the day is kept.
+    fn date<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Date>, D::Error> {
+        let Some(value) = Option::<String>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+        let day = value.get(..10).unwrap_or(&value);
+        Date::parse(day, format_description!("[year]-[month]-[day]"))
+            .map(Some)
+            .map_err(|err| D::Error::custom(format!("invalid date {value}: {err}")))
     }
 
     #[derive(Serialize, Deserialize, Debug)]
     pub struct GroupData {
-        pub group: Group,
+        pub group: Option<Group>,
     }
 
     #[derive(Serialize, Deserialize, Debug)]
@@ -139,6 +366,77 @@ pub mod model {
         pub visibility: Visibility,
         pub languages: Vec<RepositoryLanguage>,
         pub statistics: ProjectStatistics,
+        /// Missing for projects without a repository
+        pub repository: Option<ProjectRepository>,
+        /// Missing if the token may not read the project's branch rules
+        pub branch_rules: Option<BranchRuleConnection>,
+        pub only_allow_merge_if_pipeline_succeeds: Option<bool>,
+        pub secret_push_protection_enabled: Option<bool>,
+        pub security_scanners: Option<SecurityScanners>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct SecurityScanners {
+        /// Scanners enabled in the latest default branch pipeline, e.g.
+        /// `DEPENDENCY_SCANNING`
+        pub enabled: Option<Vec<String>>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ProjectRepository {
+        /// Default branch, unset for empty repositories
+        pub root_ref: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    pub struct BranchRuleConnection {
+        pub page_info: PageInfo,
+        pub nodes: Vec<BranchRule>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    pub struct BranchRule {
+        pub name: String,
+        pub is_default: bool,
+        pub is_protected: bool,
+        pub branch_protection: Option<BranchProtection>,
+        /// Only available on GitLab Premium and Ultimate
+        pub approval_rules: Option<ApprovalRuleConnection>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct ApprovalRuleConnection {
+        pub nodes: Vec<ApprovalRule>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ApprovalRule {
+        pub approvals_required: i64,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    pub struct BranchProtection {
+        pub allow_force_push: bool,
+        pub code_owner_approval_required: bool,
+        pub push_access_levels: Option<AccessLevelConnection>,
+        pub merge_access_levels: Option<AccessLevelConnection>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct AccessLevelConnection {
+        pub nodes: Vec<AccessLevel>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    pub struct AccessLevel {
+        pub access_level: i64,
+        pub access_level_description: String,
     }
 
     #[derive(Deserialize, Serialize, Debug)]
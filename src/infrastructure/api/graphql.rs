@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use reqwest::{StatusCode, Url, header::CONTENT_TYPE};
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::Value;
+
+use crate::error::AppError;
+
+use super::http::{Attempt, check_status, retry, retry_delay};
+
+/// Default timeout of a single GraphQL request.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Deserialize, Debug)]
+struct GraphQLResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphQLError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GraphQLError {
+    message: String,
+    /// GitHub classifies errors, e.g. `RATE_LIMITED` or `NOT_FOUND`
+    #[serde(rename = "type")]
+    kind: Option<String>,
+}
+
+/// POSTs a GraphQL query and returns its `data`.
+///
+/// 429s, 5xx responses, timeouts and GitHub's `RATE_LIMITED` errors are retried
+/// with exponential backoff, waiting for `Retry-After` or the rate limit reset
+/// when the server sends one. `service` names the API in error messages.
+pub async fn post<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    url: &Url,
+    body: &Value,
+    service: &str,
+) -> Result<T, AppError> {
+    retry(|| send(client, url, body, service)).await
+}
+
+async fn send<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    url: &Url,
+    body: &Value,
+    service: &str,
+) -> Result<Attempt<T>, AppError> {
+    let response = client
+        .post(url.clone())
+        .header(CONTENT_TYPE, "application/json")
+        .json(body)
+        .send()
+        .await?;
+
+    let status = response.status();
+    let delay = retry_delay(response.headers());
+
+    let response = match check_status(response, service).await? {
+        Attempt::Done(response) => response,
+        Attempt::Retry(delay, error) => return Ok(Attempt::Retry(delay, error)),
+    };
+
+    let response: GraphQLResponse<T> = serde_json::from_slice(&response.bytes().await?)?;
+
+    let message = response
+        .errors
+        .iter()
+        .map(|error| error.message.as_str())
+        .collect::<Vec<_>>()
+        .join("; ");
+
+    if response
+        .errors
+        .iter()
+        .any(|error| error.kind.as_deref() == Some("RATE_LIMITED"))
+    {
+        let error = AppError::api_error(
+            StatusCode::TOO_MANY_REQUESTS.as_u16(),
+            format!("{service}: {message}"),
+        );
+        return Ok(Attempt::Retry(delay, error));
+    }
+
+    match response.data {
+        // Partial results, e.g. fields the token may not read
+        Some(data) => {
+            if !message.is_empty() {
+                eprintln!("{service} reported errors: {message}");
+            }
+            Ok(Attempt::Done(data))
+        }
+        None => Err(AppError::api_error(
+            status.as_u16(),
+            format!("{service}: {message}"),
+        )),
+    }
+}
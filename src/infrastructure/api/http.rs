@@ -0,0 +1,154 @@
+use std::{future::Future, time::Duration};
+
+use reqwest::{Response, StatusCode, header::HeaderMap};
+use serde_json::Value;
+use time::{OffsetDateTime, format_description::well_known::Rfc2822};
+
+use crate::error::AppError;
+
+/// Attempts after the first one before a transient failure is reported.
+const MAX_RETRIES: u32 = 5;
+const BASE_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+/// Outcome of a single attempt.
+pub(super) enum Attempt<T> {
+    Done(T),
+    /// Transient failure, with the delay requested by the server and the
+    /// error to report once retries are exhausted
+    Retry(Option<Duration>, AppError),
+}
+
+/// Repeats `send` while it fails transiently, with exponential backoff unless
+/// the server asks for a specific delay. Timeouts and connection errors are
+/// retried as well.
+pub(super) async fn retry<T, F, Fut>(mut send: F) -> Result<T, AppError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Attempt<T>, AppError>>,
+{
+    let mut attempt = 0;
+
+    loop {
+        let (delay, error) = match send().await {
+            Ok(Attempt::Done(data)) => return Ok(data),
+            Ok(Attempt::Retry(delay, error)) => (delay, error),
+            Err(AppError::HttpClient(err)) if err.is_timeout() || err.is_connect() => {
+                (None, AppError::HttpClient(err))
+            }
+            Err(err) => return Err(err),
+        };
+
+        if attempt >= MAX_RETRIES {
+            return Err(error);
+        }
+
+        let delay = delay
+            .unwrap_or_else(|| BASE_RETRY_DELAY * 2u32.pow(attempt))
+            .min(MAX_RETRY_DELAY);
+        eprintln!("{error}, retrying in {}s", delay.as_secs());
+        tokio::time::sleep(delay).await;
+
+        attempt += 1;
+    }
+}
+
+/// Passes successful responses on. 429s and 5xx responses are retried, other
+/// failures reported; `service` names the API in error messages.
+pub(super) async fn check_status(
+    response: Response,
+    service: &str,
+) -> Result<Attempt<Response>, AppError> {
+    let status = response.status();
+    let delay = retry_delay(response.headers());
+
+    // GitHub answers secondary rate limits with a 403 and Retry-After
+    if status == StatusCode::TOO_MANY_REQUESTS
+        || status.is_server_error()
+        || (status == StatusCode::FORBIDDEN && delay.is_some())
+    {
+        let message = error_message(response).await;
+        let error = AppError::api_error(status.as_u16(), format!("{service}: {message}"));
+        return Ok(Attempt::Retry(delay, error));
+    }
+    if status == StatusCode::UNAUTHORIZED {
+        let message = error_message(response).await;
+        return Err(AppError::AuthError(format!(
+            "{service} rejected the provided token: {message}"
+        )));
+    }
+    if status == StatusCode::FORBIDDEN {
+        let message = error_message(response).await;
+        return Err(AppError::AuthError(format!(
+            "{service} denied access: {message}"
+        )));
+    }
+    if !status.is_success() {
+        let message = error_message(response).await;
+        return Err(AppError::api_error(
+            status.as_u16(),
+            format!("{service}: {message}"),
+        ));
+    }
+
+    Ok(Attempt::Done(response))
+}
+
+async fn error_message(response: Response) -> String {
+    let status = response.status();
+    let text = response.text().await.unwrap_or_default();
+
+    // GitLab and GitHub both wrap errors in JSON, OAuth errors (e.g. expired
+    // tokens) come with a description. Fall back to the raw body
+    let message = match serde_json::from_str::<Value>(&text) {
+        Ok(Value::String(message)) => message,
+        Ok(json) => json
+            .get("message")
+            .or_else(|| json.get("error_description"))
+            .or_else(|| json.get("error"))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or(text),
+        Err(_) => text,
+    };
+
+    if message.trim().is_empty() {
+        status.canonical_reason().unwrap_or_default().to_string()
+    } else {
+        message
+    }
+}
+
+/// How long the server asks us to wait, from `Retry-After` (seconds or an HTTP
+/// date) or, once the rate limit is exhausted, its reset time.
+pub(super) fn retry_delay(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    let now = OffsetDateTime::now_utc();
+
+    if let Some(retry_after) = header("retry-after") {
+        if let Ok(seconds) = retry_after.trim().parse() {
+            return Some(Duration::from_secs(seconds));
+        }
+        if let Ok(date) = OffsetDateTime::parse(retry_after, &Rfc2822) {
+            return Some((date - now).try_into().unwrap_or_default());
+        }
+    }
+
+    // GitLab sends RateLimit-*, GitHub X-RateLimit-*
+    for prefix in ["ratelimit", "x-ratelimit"] {
+        if header(&format!("{prefix}-remaining")) != Some("0") {
+            continue;
+        }
+
+        let reset = header(&format!("{prefix}-reset"))
+            .and_then(|reset| reset.trim().parse().ok())
+            .and_then(|reset| OffsetDateTime::from_unix_timestamp(reset).ok());
+
+        if let Some(reset) = reset {
+            let wait: Duration = (reset - now).try_into().unwrap_or_default();
+            return Some(wait + Duration::from_secs(1));
+        }
+    }
+
+    None
+}
@@ -1 +1,5 @@
+pub mod github;
 pub mod gitlab;
+pub mod graphql;
+mod http;
+pub mod rest;
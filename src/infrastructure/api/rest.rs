@@ -0,0 +1,165 @@
+use reqwest::{StatusCode, Url};
+use serde::de::DeserializeOwned;
+
+use crate::error::AppError;
+
+use super::http::{Attempt, check_status, retry};
+
+/// Items requested per page of a list.
+const PER_PAGE: &str = "100";
+
+/// GETs a JSON resource, retrying transient failures like GraphQL requests.
+/// A 404 is reported as [`AppError::NotFound`] so callers can tell missing
+/// endpoints, e.g. on older instances, from other failures.
+pub async fn get<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    url: &Url,
+    service: &str,
+) -> Result<T, AppError> {
+    let (data, _) = retry(|| send(client, url, service)).await?;
+    Ok(data)
+}
+
+/// GETs every page of a list, following GitLab's `X-Next-Page` header.
+pub async fn get_all<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    url: &Url,
+    service: &str,
+) -> Result<Vec<T>, AppError> {
+    let mut items = Vec::new();
+    let mut page = Some("1".to_string());
+
+    while let Some(current) = page {
+        let mut page_url = url.clone();
+        page_url
+            .query_pairs_mut()
+            .append_pair("per_page", PER_PAGE)
+            .append_pair("page", &current);
+
+        let (data, next_page): (Vec<T>, _) = retry(|| send(client, &page_url, service)).await?;
+        items.extend(data);
+        page = next_page;
+    }
+
+    Ok(items)
+}
+
+async fn send<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    url: &Url,
+    service: &str,
+) -> Result<Attempt<(T, Option<String>)>, AppError> {
+    let response = client.get(url.clone()).send().await?;
+
+    if response.status() == StatusCode::NOT_FOUND {
+        return Err(AppError::NotFound(format!("{service} {}", url.path())));
+    }
+
+    let response = match check_status(response, service).await? {
+        Attempt::Done(response) => response,
+        Attempt::Retry(delay, error) => return Ok(Attempt::Retry(delay, error)),
+    };
+
+    // Empty on the last page
+    let next_page = response
+        .headers()
+        .get("x-next-page")
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|page| !page.is_empty())
+        .map(str::to_string);
+
+    let data = serde_json::from_slice(&response.bytes().await?)?;
+    Ok(Attempt::Done((data, next_page)))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// Serves `/items` in pages of two out of `1..=5` and 404 elsewhere,
+    /// closing every connection after one response.
+    async fn serve() -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!(
+            "http://{}/items?state=active",
+            listener.local_addr().unwrap()
+        ))
+        .unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    if read == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buffer[..read]);
+                }
+                let request = String::from_utf8(request).unwrap();
+                let target = request.split(' ').nth(1).unwrap();
+                let target = Url::parse("http://localhost")
+                    .unwrap()
+                    .join(target)
+                    .unwrap();
+
+                let response = if target.path() == "/items" {
+                    let query: Vec<_> = target.query_pairs().collect();
+                    assert!(query.contains(&("state".into(), "active".into())));
+                    assert!(query.contains(&("per_page".into(), PER_PAGE.into())));
+                    let page: i64 = query
+                        .iter()
+                        .find(|(key, _)| key == "page")
+                        .unwrap()
+                        .1
+                        .parse()
+                        .unwrap();
+
+                    let items: Vec<i64> = (page * 2 - 1..=page * 2)
+                        .filter(|item| *item <= 5)
+                        .collect();
+                    let body = serde_json::to_string(&items).unwrap();
+                    // GitLab sends an empty header on the last page
+                    let next_page = if page < 3 {
+                        (page + 1).to_string()
+                    } else {
+                        String::new()
+                    };
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nX-Next-Page: {next_page}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                } else {
+                    "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                        .to_string()
+                };
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        url
+    }
+
+    #[tokio::test]
+    async fn get_all_follows_next_page() {
+        let url = serve().await;
+        let items: Vec<i64> = get_all(&reqwest::Client::new(), &url, "Test")
+            .await
+            .unwrap();
+        assert_eq!(items, [1, 2, 3, 4, 5]);
+    }
+
+    #[tokio::test]
+    async fn missing_endpoints_are_not_found() {
+        let url = serve().await.join("/missing").unwrap();
+        let result: Result<Vec<i64>, _> = get_all(&reqwest::Client::new(), &url, "Test").await;
+        assert!(matches!(result, Err(AppError::NotFound(message)) if message == "Test /missing"));
+    }
+}
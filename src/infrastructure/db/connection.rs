@@ -1,32 +1,25 @@
-use directories::ProjectDirs;
 use sqlx::{
     SqlitePool,
     sqlite::{SqliteConnectOptions, SqlitePoolOptions},
 };
-use std::str::FromStr;
-
-use crate::error::AppError;
-
-pub async fn init_db() -> Result<SqlitePool, AppError> {
-    let proj_dirs = match ProjectDirs::from("", "", "devsec") {
-        Some(proj_dirs) => proj_dirs,
-        None => {
-            eprintln!("Could not find home directory");
-            std::process::exit(1);
-        }
-    };
-
-    let data_dir = proj_dirs.data_dir();
-
-    std::fs::create_dir_all(data_dir)?;
-
-    let db_url = if cfg!(debug_assertions) {
-        "sqlite://devsec.db".to_string()
-    } else {
-        format!("sqlite://{}/devsec.db", data_dir.display())
-    };
+use std::path::{Path, PathBuf};
+
+use crate::{error::AppError, infrastructure::utils::dirs::data_dir};
+
+/// Database file given by `--db` or the config, else the default one in the
+/// data directory (the working directory for debug builds).
+pub fn db_path(path: Option<&Path>) -> Result<PathBuf, AppError> {
+    Ok(match path {
+        Some(path) => path.to_path_buf(),
+        None if cfg!(debug_assertions) => PathBuf::from("devsec.db"),
+        None => data_dir()?.join("devsec.db"),
+    })
+}
 
-    let opts = SqliteConnectOptions::from_str(&db_url)?
+/// Opens and migrates the database at `path`, creating it if necessary.
+pub async fn init_db(path: &Path) -> Result<SqlitePool, AppError> {
+    let opts = SqliteConnectOptions::new()
+        .filename(path)
         .create_if_missing(true)
         .foreign_keys(true);
 
@@ -39,3 +32,14 @@ pub async fn init_db() -> Result<SqlitePool, AppError> {
 
     Ok(pool)
 }
+
+/// Opens the existing database at `path` without writing to it, leaving
+/// pending migrations unapplied.
+pub async fn open_db_read_only(path: &Path) -> Result<SqlitePool, AppError> {
+    let opts = SqliteConnectOptions::new().filename(path).read_only(true);
+
+    Ok(SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(opts)
+        .await?)
+}
@@ -0,0 +1,180 @@
+use std::path::Path;
+
+use serde::Serialize;
+use sqlx::SqlitePool;
+use tabled::Tabled;
+use time::OffsetDateTime;
+
+use crate::{error::AppError, infrastructure::utils::repositories::display_offset_datetime};
+
+#[derive(Tabled, Debug, Serialize)]
+pub struct DatabaseInfo {
+    pub path: String,
+    /// File size in bytes
+    pub size: u64,
+    /// Version of the latest applied migration
+    pub schema_version: i64,
+    /// Migrations the next command other than `db info` applies
+    #[tabled(display("display_pending_migrations"))]
+    pub pending_migrations: Vec<PendingMigration>,
+    #[tabled(display("display_last_sync"))]
+    pub last_sync: Option<LastSync>,
+    #[tabled(display("display_row_counts"))]
+    pub row_counts: Vec<RowCount>,
+}
+
+/// The most recent completed sync run.
+#[derive(Debug, Serialize)]
+pub struct LastSync {
+    pub source: String,
+    pub scope: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub finished_at: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PendingMigration {
+    pub version: i64,
+    pub description: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RowCount {
+    pub table: &'static str,
+    pub rows: i64,
+}
+
+/// Row counts `db info` reports: name, table and a filter on `deleted_at`.
+const COUNTED_TABLES: &[(&str, &str, Option<&str>)] = &[
+    ("codebases", "codebases", Some("deleted_at IS NULL")),
+    (
+        "deleted_codebases",
+        "codebases",
+        Some("deleted_at IS NOT NULL"),
+    ),
+    ("sync_runs", "sync_runs", None),
+    ("codebase_snapshots", "codebase_snapshots", None),
+    ("branch_rules", "branch_rules", None),
+    ("secret_findings", "secret_findings", None),
+    ("mirrors", "mirrors", None),
+    ("dependencies", "dependencies", None),
+    ("advisories", "advisories", None),
+    ("credentials", "credentials", None),
+];
+
+/// Reads the database as it is, it may not be migrated to the latest schema.
+pub async fn get_database_info(pool: &SqlitePool, path: &Path) -> Result<DatabaseInfo, AppError> {
+    // The migrations table is created by sqlx at runtime
+    let applied: Vec<i64> = if has_table(pool, "_sqlx_migrations").await? {
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success = TRUE")
+            .fetch_all(pool)
+            .await?
+    } else {
+        Vec::new()
+    };
+    let pending_migrations = sqlx::migrate!()
+        .iter()
+        .filter(|migration| {
+            !migration.migration_type.is_down_migration() && !applied.contains(&migration.version)
+        })
+        .map(|migration| PendingMigration {
+            version: migration.version,
+            description: migration.description.to_string(),
+        })
+        .collect();
+
+    let last_sync = if has_table(pool, "sync_runs").await? {
+        sqlx::query_as!(
+            LastSync,
+            r#"
+        SELECT
+            source,
+            scope,
+            finished_at as "finished_at!: OffsetDateTime"
+        FROM sync_runs
+        WHERE status = 'completed' AND finished_at IS NOT NULL
+        ORDER BY finished_at DESC
+        LIMIT 1
+        "#
+        )
+        .fetch_optional(pool)
+        .await?
+    } else {
+        None
+    };
+
+    let mut row_counts = Vec::new();
+    for (table, relation, filter) in COUNTED_TABLES {
+        // Tables and columns of pending migrations are missing
+        if !has_table(pool, relation).await?
+            || filter.is_some() && !has_column(pool, relation, "deleted_at").await?
+        {
+            continue;
+        }
+        let query = match filter {
+            Some(filter) => format!("SELECT COUNT(*) FROM {relation} WHERE {filter}"),
+            None => format!("SELECT COUNT(*) FROM {relation}"),
+        };
+        let rows: i64 = sqlx::query_scalar(&query).fetch_one(pool).await?;
+        row_counts.push(RowCount { table, rows });
+    }
+
+    Ok(DatabaseInfo {
+        path: path.display().to_string(),
+        size: std::fs::metadata(path)?.len(),
+        schema_version: applied.into_iter().max().unwrap_or_default(),
+        pending_migrations,
+        last_sync,
+        row_counts,
+    })
+}
+
+async fn has_table(pool: &SqlitePool, table: &str) -> Result<bool, AppError> {
+    Ok(sqlx::query_scalar(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = ?",
+    )
+    .bind(table)
+    .fetch_one(pool)
+    .await?)
+}
+
+async fn has_column(pool: &SqlitePool, table: &str, column: &str) -> Result<bool, AppError> {
+    Ok(
+        sqlx::query_scalar("SELECT COUNT(*) > 0 FROM pragma_table_info(?) WHERE name = ?")
+            .bind(table)
+            .bind(column)
+            .fetch_one(pool)
+            .await?,
+    )
+}
+
+fn display_last_sync(last_sync: &Option<LastSync>) -> String {
+    match last_sync {
+        Some(sync) => format!(
+            "{} {} at {}",
+            sync.source,
+            sync.scope,
+            display_offset_datetime(&sync.finished_at)
+        ),
+        None => "never".to_string(),
+    }
+}
+
+fn display_row_counts(row_counts: &[RowCount]) -> String {
+    row_counts
+        .iter()
+        .map(|count| format!("{}: {}", count.table, count.rows))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn display_pending_migrations(pending_migrations: &[PendingMigration]) -> String {
+    if pending_migrations.is_empty() {
+        return "none".to_string();
+    }
+    pending_migrations
+        .iter()
+        .map(|migration| format!("{} {}", migration.version, migration.description))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
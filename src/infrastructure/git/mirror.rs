@@ -0,0 +1,342 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+use crate::error::AppError;
+
+/// A bare mirror clone of a repository.
+pub struct GitMirror {
+    dir: PathBuf,
+}
+
+impl GitMirror {
+    /// Fetches the mirror in `dir`, cloning it from `url` first if needed.
+    /// `url` may be anything `git clone` accepts, local bare repositories
+    /// included.
+    pub fn fetch(url: &str, dir: &Path) -> Result<Self, AppError> {
+        if dir.exists() {
+            let mirror = Self {
+                dir: dir.to_path_buf(),
+            };
+            mirror.git(&["remote", "set-url", "--", "origin", url])?;
+            mirror.git(&["fetch", "--prune", "--quiet", "origin"])?;
+            return Ok(mirror);
+        }
+
+        if let Some(parent) = dir.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // Clone next to the target so an interrupted clone is never mistaken
+        // for a mirror
+        let partial = dir.with_extension("partial");
+        if partial.exists() {
+            std::fs::remove_dir_all(&partial)?;
+        }
+
+        // `--` keeps URLs starting with a dash from being read as options
+        let output = git_command()
+            .args(["clone", "--mirror", "--quiet", "--", url])
+            .arg(&partial)
+            .output()?;
+        if !output.status.success() {
+            let _ = std::fs::remove_dir_all(&partial);
+            return Err(git_error(&output.stderr));
+        }
+        std::fs::rename(&partial, dir)?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    /// An existing mirror, without fetching it.
+    pub fn open(dir: &Path) -> Option<Self> {
+        dir.is_dir().then(|| Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    /// Bytes the mirror takes up on disk.
+    pub fn disk_size(&self) -> Result<u64, AppError> {
+        Ok(dir_size(&self.dir)?)
+    }
+
+    /// Commit `HEAD` points to, unset for empty repositories.
+    pub fn head(&self) -> Result<Option<String>, AppError> {
+        let output = git_command()
+            .arg("-C")
+            .arg(&self.dir)
+            .args(["rev-parse", "--verify", "--quiet", "HEAD^{commit}"])
+            .output()?;
+
+        if !output.status.success() {
+            return Ok(None);
+        }
+        Ok(Some(
+            String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        ))
+    }
+
+    /// Calls `f` with the path and content of every text file at `HEAD` whose
+    /// path passes `filter` and that is at most `max_size` bytes large.
+    /// Returns the paths and sizes of the files skipped for their size.
+    pub fn for_each_head_file(
+        &self,
+        filter: impl Fn(&str) -> bool,
+        max_size: u64,
+        mut f: impl FnMut(&str, &str),
+    ) -> Result<Vec<(String, u64)>, AppError> {
+        let mut oversized = Vec::new();
+        if self.head()?.is_none() {
+            return Ok(oversized);
+        }
+
+        // <mode> SP <type> SP <object> SP <size> TAB <path> NUL
+        let listing = self.git(&["ls-tree", "-r", "-z", "--long", "HEAD"])?;
+        let mut blobs = Vec::new();
+        for entry in listing.split(|byte| *byte == 0) {
+            let entry = String::from_utf8_lossy(entry);
+            let Some((meta, path)) = entry.split_once('\t') else {
+                continue;
+            };
+            let fields: Vec<&str> = meta.split_whitespace().collect();
+            let [_, "blob", object, size] = fields[..] else {
+                continue;
+            };
+            let Ok(size) = size.parse::<u64>() else {
+                continue;
+            };
+            if !filter(path) {
+                continue;
+            }
+            if size > max_size {
+                oversized.push((path.to_string(), size));
+            } else {
+                blobs.push((path.to_string(), object.to_string()));
+            }
+        }
+
+        let mut reader = BlobReader::new(&self.dir)?;
+        for (path, object) in blobs {
+            let content = reader.read(&object)?;
+
+            // Skip binary files
+            if content.contains(&0) {
+                continue;
+            }
+            f(&path, &String::from_utf8_lossy(&content));
+        }
+
+        Ok(oversized)
+    }
+
+    /// Calls `f` with the commit, path, line number and text of every line
+    /// added by any commit reachable from a ref.
+    pub fn for_each_added_line(
+        &self,
+        mut f: impl FnMut(&str, &str, i64, &str),
+    ) -> Result<(), AppError> {
+        if self.head()?.is_none() {
+            return Ok(());
+        }
+
+        let mut child = git_command()
+            .arg("-C")
+            .arg(&self.dir)
+            // Paths with non-ASCII characters would be quoted and escaped
+            .args(["-c", "core.quotePath=false"])
+            .args([
+                "log",
+                "--all",
+                "--patch",
+                "--unified=0",
+                "--no-color",
+                "--no-ext-diff",
+                "--no-renames",
+                "--format=commit %H",
+            ])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let mut lines = BufReader::new(stdout);
+
+        let mut commit = String::new();
+        let mut file: Option<String> = None;
+        // Between `diff --git` and the first hunk, where `+++` names the file
+        let mut in_header = false;
+        let mut line_number = 0;
+        let mut buffer = Vec::new();
+
+        loop {
+            buffer.clear();
+            if lines.read_until(b'\n', &mut buffer)? == 0 {
+                break;
+            }
+            let line = String::from_utf8_lossy(&buffer);
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if let Some(sha) = line.strip_prefix("commit ") {
+                commit = sha.to_string();
+                file = None;
+            } else if line.starts_with("diff --git ") {
+                in_header = true;
+                file = None;
+            } else if let Some(path) = line.strip_prefix("+++ ").filter(|_| in_header) {
+                // `/dev/null` for deleted files
+                file = path.strip_prefix("b/").map(str::to_string);
+            } else if line.starts_with("@@ ") {
+                in_header = false;
+                // @@ -<old> +<start>[,<count>] @@
+                line_number = line
+                    .split_whitespace()
+                    .find_map(|range| range.strip_prefix('+'))
+                    .and_then(|range| range.split(',').next())
+                    .and_then(|start| start.parse().ok())
+                    .unwrap_or_default();
+            } else if let Some(added) = line.strip_prefix('+').filter(|_| !in_header) {
+                let Some(path) = &file else {
+                    continue;
+                };
+                f(&commit, path, line_number, added);
+                line_number += 1;
+            }
+        }
+
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(AppError::GitError(format!(
+                "git log failed in {}",
+                self.dir.display()
+            )));
+        }
+        Ok(())
+    }
+
+    fn git(&self, args: &[&str]) -> Result<Vec<u8>, AppError> {
+        let output = git_command().arg("-C").arg(&self.dir).args(args).output()?;
+
+        if !output.status.success() {
+            return Err(git_error(&output.stderr));
+        }
+        Ok(output.stdout)
+    }
+}
+
+/// Reads blobs through a single `git cat-file --batch` process.
+struct BlobReader {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl BlobReader {
+    fn new(dir: &Path) -> Result<Self, AppError> {
+        let mut child = git_command()
+            .arg("-C")
+            .arg(dir)
+            .args(["cat-file", "--batch"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        Ok(Self {
+            stdin: child.stdin.take().expect("stdin is piped"),
+            stdout: BufReader::new(child.stdout.take().expect("stdout is piped")),
+            child,
+        })
+    }
+
+    fn read(&mut self, object: &str) -> Result<Vec<u8>, AppError> {
+        writeln!(self.stdin, "{object}")?;
+        self.stdin.flush()?;
+
+        // <object> SP <type> SP <size> LF <content> LF
+        let mut header = String::new();
+        self.stdout.read_line(&mut header)?;
+        let size: usize = header
+            .split_whitespace()
+            .nth(2)
+            .and_then(|size| size.parse().ok())
+            .ok_or_else(|| AppError::GitError(format!("unexpected object {}", header.trim())))?;
+
+        let mut content = vec![0; size + 1];
+        self.stdout.read_exact(&mut content)?;
+        content.truncate(size);
+
+        Ok(content)
+    }
+}
+
+impl Drop for BlobReader {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn git_command() -> Command {
+    let mut command = Command::new("git");
+    // Fail instead of waiting for credentials nobody will type
+    command.env("GIT_TERMINAL_PROMPT", "0").stdin(Stdio::null());
+    command
+}
+
+fn git_error(stderr: &[u8]) -> AppError {
+    AppError::GitError(String::from_utf8_lossy(stderr).trim().to_string())
+}
+
+/// Where the mirror of a repository lives below `root`, `<source>/<path>.git`.
+pub fn mirror_path(root: &Path, source: &str, path: &str) -> PathBuf {
+    // `gitlab:host:port` sources contain colons, which Windows doesn't allow
+    root.join(source.replace(':', "_"))
+        .join(format!("{path}.git"))
+}
+
+fn dir_size(dir: &Path) -> std::io::Result<u64> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(size)
+}
+
+/// Whether `dir` exists below `root` once symlinks and `..` are resolved.
+/// Mirror paths are read from the database, which may be any file.
+pub fn is_below(dir: &Path, root: &Path) -> bool {
+    match (dir.canonicalize(), root.canonicalize()) {
+        (Ok(dir), Ok(root)) => dir != root && dir.starts_with(root),
+        _ => false,
+    }
+}
+
+/// Mirror directories below `root`, including clones that never finished.
+pub fn find_mirror_dirs(root: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    if !root.is_dir() {
+        return Ok(dirs);
+    }
+
+    for entry in std::fs::read_dir(root)? {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("git" | "partial") => dirs.push(path),
+            _ => dirs.extend(find_mirror_dirs(&path)?),
+        }
+    }
+    Ok(dirs)
+}
@@ -0,0 +1,145 @@
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Number, Value};
+use yaml_rust2::{Yaml, YamlLoader};
+
+/// Reads a TOML or YAML file, picked by its extension. Errors name the file
+/// and are meant to be wrapped in the caller's error variant.
+pub fn read_config_file<T: DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let content =
+        std::fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+
+    let parsed = match extension.as_deref() {
+        Some("toml") => toml::from_str(&content).map_err(|err| err.message().to_string()),
+        Some("yaml" | "yml") => from_yaml(&content),
+        _ => Err("unsupported format, expected a .toml, .yaml or .yml file".to_string()),
+    };
+    parsed.map_err(|err| format!("{}: {err}", path.display()))
+}
+
+/// Deserializes the first document of `content`. An empty file is an empty
+/// mapping, like an empty TOML file.
+fn from_yaml<T: DeserializeOwned>(content: &str) -> Result<T, String> {
+    let documents = YamlLoader::load_from_str(content).map_err(|err| err.to_string())?;
+    let value = match documents.into_iter().next() {
+        Some(document) => to_json(document)?,
+        None => Value::Object(Map::new()),
+    };
+    serde_json::from_value(value).map_err(|err| err.to_string())
+}
+
+fn to_json(yaml: Yaml) -> Result<Value, String> {
+    Ok(match yaml {
+        Yaml::Null => Value::Null,
+        Yaml::Boolean(value) => Value::Bool(value),
+        Yaml::Integer(value) => Value::Number(value.into()),
+        Yaml::Real(value) => match yaml_real(&value) {
+            Some(number) => Value::Number(number),
+            None => Value::String(value),
+        },
+        Yaml::String(value) => Value::String(value),
+        Yaml::Array(values) => {
+            Value::Array(values.into_iter().map(to_json).collect::<Result<_, _>>()?)
+        }
+        Yaml::Hash(entries) => Value::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| Ok((to_key(key)?, to_json(value)?)))
+                .collect::<Result<_, String>>()?,
+        ),
+        Yaml::Alias(_) | Yaml::BadValue => return Err("unsupported YAML value".to_string()),
+    })
+}
+
+fn yaml_real(value: &str) -> Option<Number> {
+    Number::from_f64(value.parse().ok()?)
+}
+
+fn to_key(key: Yaml) -> Result<String, String> {
+    match key {
+        Yaml::String(key) | Yaml::Real(key) => Ok(key),
+        Yaml::Integer(key) => Ok(key.to_string()),
+        Yaml::Boolean(key) => Ok(key.to_string()),
+        _ => Err("mapping keys must be scalars".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Rules {
+        #[serde(default)]
+        rules: Vec<Rule>,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Rule {
+        id: String,
+        enabled: bool,
+        weight: f64,
+        limit: Option<i64>,
+    }
+
+    fn read(name: &str, content: &str) -> Result<Rules, String> {
+        let dir = std::env::temp_dir().join(format!("devsec-config-file-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        let rules = read_config_file(&path);
+        std::fs::remove_file(path).unwrap();
+        rules
+    }
+
+    fn rules() -> Rules {
+        Rules {
+            rules: vec![Rule {
+                id: "a".to_string(),
+                enabled: true,
+                weight: 1.5,
+                limit: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn toml_and_yaml_read_the_same() {
+        let toml = "[[rules]]\nid = \"a\"\nenabled = true\nweight = 1.5\n";
+        let yaml =
+            "# comment\nrules:\n  - id: a\n    enabled: true\n    weight: 1.5\n    limit: ~\n";
+        assert_eq!(read("rules.toml", toml).unwrap(), rules());
+        assert_eq!(read("rules.yaml", yaml).unwrap(), rules());
+        assert_eq!(read("rules.YML", yaml).unwrap(), rules());
+    }
+
+    #[test]
+    fn empty_files_use_defaults() {
+        assert_eq!(read("empty.toml", "").unwrap(), Rules { rules: vec![] });
+        assert_eq!(read("empty.yml", "").unwrap(), Rules { rules: vec![] });
+    }
+
+    #[test]
+    fn errors_name_the_file() {
+        let error = read("rules.json", "{}").unwrap_err();
+        assert!(
+            error.ends_with("rules.json: unsupported format, expected a .toml, .yaml or .yml file")
+        );
+
+        let error = read("missing.yml", "rules:\n  - id: a\n").unwrap_err();
+        assert!(
+            error.ends_with("missing.yml: missing field `enabled`"),
+            "{error}"
+        );
+
+        let error = read("broken.yml", "rules: [").unwrap_err();
+        assert!(error.contains("broken.yml: "), "{error}");
+    }
+}
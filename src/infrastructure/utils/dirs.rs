@@ -0,0 +1,38 @@
+use std::path::{Path, PathBuf};
+
+use directories::ProjectDirs;
+
+use crate::error::AppError;
+
+/// Directory devsec keeps its database and repository mirrors in.
+pub fn data_dir() -> Result<PathBuf, AppError> {
+    let proj_dirs = ProjectDirs::from("", "", "devsec")
+        .ok_or_else(|| AppError::NotFound("home directory".to_string()))?;
+
+    let data_dir = proj_dirs.data_dir().to_path_buf();
+    std::fs::create_dir_all(&data_dir)?;
+
+    Ok(data_dir)
+}
+
+/// Directory the mirror clones of the repositories in the database at
+/// `db_path` are kept in, `mirrors/<name>-<hash of its path>` in the data
+/// directory. Databases thus never share, or prune, each other's mirrors.
+pub fn mirrors_dir(db_path: &Path) -> Result<PathBuf, AppError> {
+    // Mirror paths are stored, so they must not depend on the working directory
+    let db_path = std::path::absolute(db_path)?;
+    let stem = db_path.file_stem().unwrap_or_default().to_string_lossy();
+
+    // FNV-1a, unlike `DefaultHasher` stable across Rust releases
+    let hash = db_path
+        .as_os_str()
+        .as_encoded_bytes()
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+
+    Ok(data_dir()?
+        .join("mirrors")
+        .join(format!("{stem}-{hash:016x}")))
+}
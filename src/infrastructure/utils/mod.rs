@@ -1,2 +1,6 @@
+pub mod config_file;
+pub mod dirs;
 pub mod progress_bar;
 pub mod repositories;
+pub mod sarif;
+pub mod sbom;
@@ -6,3 +6,23 @@ pub fn display_offset_datetime(offset_datetime: &OffsetDateTime) -> String {
         Err(error) => panic!("Could not format OffsetDateTime: {}", error),
     }
 }
+
+pub fn display_list(items: &[String]) -> String {
+    items.join("\n")
+}
+
+pub fn display_optional(value: &Option<String>) -> String {
+    value.clone().unwrap_or_default()
+}
+
+/// Abbreviated commit hash, like `git log --oneline`
+pub fn display_commit(commit: &Option<String>) -> String {
+    commit
+        .as_deref()
+        .map(|commit| commit.chars().take(12).collect())
+        .unwrap_or_default()
+}
+
+pub fn display_score(score: &Option<f64>) -> String {
+    score.map(|score| format!("{score:.2}")).unwrap_or_default()
+}
@@ -0,0 +1,321 @@
+use serde::Serialize;
+
+use crate::domain::policy::Severity;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const SARIF_VERSION: &str = "2.1.0";
+
+/// A finding that can be reported as a SARIF result.
+pub trait Finding {
+    fn rule_id(&self) -> &str;
+    fn rule_description(&self) -> &str;
+    fn severity(&self) -> Severity;
+    fn message(&self) -> String;
+    /// URI of the artifact the finding is about, e.g. the repository web URL
+    fn location(&self) -> &str;
+}
+
+#[derive(Serialize, Debug)]
+pub struct SarifLog {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<Run>,
+}
+
+#[derive(Serialize, Debug)]
+struct Run {
+    tool: Tool,
+    results: Vec<SarifResult>,
+}
+
+#[derive(Serialize, Debug)]
+struct Tool {
+    driver: Driver,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Driver {
+    name: &'static str,
+    version: &'static str,
+    information_uri: &'static str,
+    rules: Vec<ReportingDescriptor>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ReportingDescriptor {
+    id: String,
+    short_description: Message,
+    default_configuration: Configuration,
+    properties: Properties,
+}
+
+#[derive(Serialize, Debug)]
+struct Configuration {
+    level: &'static str,
+}
+
+#[derive(Serialize, Debug)]
+struct Properties {
+    /// Numeric severity understood by GitHub code scanning and others
+    #[serde(rename = "security-severity", skip_serializing_if = "Option::is_none")]
+    security_severity: Option<&'static str>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    rule_id: String,
+    rule_index: usize,
+    level: &'static str,
+    message: Message,
+    locations: Vec<Location>,
+}
+
+#[derive(Serialize, Debug)]
+struct Message {
+    text: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Location {
+    physical_location: PhysicalLocation,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PhysicalLocation {
+    artifact_location: ArtifactLocation,
+}
+
+#[derive(Serialize, Debug)]
+struct ArtifactLocation {
+    uri: String,
+}
+
+/// Builds a SARIF 2.1.0 log with a single run of devsec, with one rule entry
+/// per distinct rule id among the findings.
+pub fn to_sarif<F: Finding>(findings: &[F]) -> SarifLog {
+    let mut rules: Vec<ReportingDescriptor> = Vec::new();
+    let mut results = Vec::new();
+
+    for finding in findings {
+        let rule_index = match rules.iter().position(|rule| rule.id == finding.rule_id()) {
+            Some(index) => index,
+            None => {
+                rules.push(ReportingDescriptor {
+                    id: finding.rule_id().to_string(),
+                    short_description: Message {
+                        text: finding.rule_description().to_string(),
+                    },
+                    default_configuration: Configuration {
+                        level: level(finding.severity()),
+                    },
+                    properties: Properties {
+                        security_severity: security_severity(finding.severity()),
+                    },
+                });
+                rules.len() - 1
+            }
+        };
+
+        results.push(SarifResult {
+            rule_id: finding.rule_id().to_string(),
+            rule_index,
+            level: level(finding.severity()),
+            message: Message {
+                text: finding.message(),
+            },
+            locations: vec![Location {
+                physical_location: PhysicalLocation {
+                    artifact_location: ArtifactLocation {
+                        uri: finding.location().to_string(),
+                    },
+                },
+            }],
+        });
+    }
+
+    SarifLog {
+        schema: SARIF_SCHEMA,
+        version: SARIF_VERSION,
+        runs: vec![Run {
+            tool: Tool {
+                driver: Driver {
+                    name: env!("CARGO_PKG_NAME"),
+                    version: env!("CARGO_PKG_VERSION"),
+                    information_uri: env!("CARGO_PKG_HOMEPAGE"),
+                    rules,
+                },
+            },
+            results,
+        }],
+    }
+}
+
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical | Severity::High => "error",
+        Severity::Unknown | Severity::Medium => "warning",
+        Severity::Low => "note",
+    }
+}
+
+/// Unset for unrated findings rather than made up.
+fn security_severity(severity: Severity) -> Option<&'static str> {
+    match severity {
+        Severity::Unknown => None,
+        Severity::Critical => Some("9.0"),
+        Severity::High => Some("7.0"),
+        Severity::Medium => Some("5.0"),
+        Severity::Low => Some("3.0"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    struct TestFinding {
+        rule: &'static str,
+        severity: Severity,
+        uri: &'static str,
+    }
+
+    impl Finding for TestFinding {
+        fn rule_id(&self) -> &str {
+            self.rule
+        }
+
+        fn rule_description(&self) -> &str {
+            "Rule description"
+        }
+
+        fn severity(&self) -> Severity {
+            self.severity
+        }
+
+        fn message(&self) -> String {
+            format!("{} in {}", self.rule, self.uri)
+        }
+
+        fn location(&self) -> &str {
+            self.uri
+        }
+    }
+
+    fn sarif(findings: &[(&'static str, Severity, &'static str)]) -> Value {
+        let findings: Vec<TestFinding> = findings
+            .iter()
+            .map(|&(rule, severity, uri)| TestFinding {
+                rule,
+                severity,
+                uri,
+            })
+            .collect();
+        serde_json::to_value(to_sarif(&findings)).unwrap()
+    }
+
+    #[test]
+    fn rules_are_listed_once_and_referenced_by_index() {
+        let log = sarif(&[
+            ("a", Severity::High, "https://gitlab.com/grp/one"),
+            ("b", Severity::Low, "https://gitlab.com/grp/one"),
+            ("a", Severity::High, "https://gitlab.com/grp/two"),
+        ]);
+        assert_eq!(log["version"], "2.1.0");
+
+        let run = &log["runs"][0];
+        let rule_ids: Vec<&Value> = run["tool"]["driver"]["rules"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|rule| &rule["id"])
+            .collect();
+        assert_eq!(rule_ids, [&json!("a"), &json!("b")]);
+
+        let results: Vec<(&Value, &Value, &Value)> = run["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| {
+                (
+                    &result["ruleId"],
+                    &result["ruleIndex"],
+                    &result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+                )
+            })
+            .collect();
+        assert_eq!(
+            results,
+            [
+                (&json!("a"), &json!(0), &json!("https://gitlab.com/grp/one")),
+                (&json!("b"), &json!(1), &json!("https://gitlab.com/grp/one")),
+                (&json!("a"), &json!(0), &json!("https://gitlab.com/grp/two")),
+            ]
+        );
+        assert_eq!(
+            run["results"][2]["message"]["text"],
+            "a in https://gitlab.com/grp/two"
+        );
+    }
+
+    #[test]
+    fn severities_map_to_levels() {
+        let log = sarif(&[
+            ("critical", Severity::Critical, "u"),
+            ("high", Severity::High, "u"),
+            ("medium", Severity::Medium, "u"),
+            ("low", Severity::Low, "u"),
+            ("unknown", Severity::Unknown, "u"),
+        ]);
+        let run = &log["runs"][0];
+
+        let levels: Vec<&Value> = run["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| &result["level"])
+            .collect();
+        assert_eq!(
+            levels,
+            [
+                &json!("error"),
+                &json!("error"),
+                &json!("warning"),
+                &json!("note"),
+                &json!("warning")
+            ]
+        );
+
+        let rules: Vec<(&Value, &Value)> = run["tool"]["driver"]["rules"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|rule| (&rule["defaultConfiguration"]["level"], &rule["properties"]))
+            .collect();
+        assert_eq!(
+            rules,
+            [
+                (&json!("error"), &json!({"security-severity": "9.0"})),
+                (&json!("error"), &json!({"security-severity": "7.0"})),
+                (&json!("warning"), &json!({"security-severity": "5.0"})),
+                (&json!("note"), &json!({"security-severity": "3.0"})),
+                (&json!("warning"), &json!({})),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_findings_still_make_a_run() {
+        let log = sarif(&[]);
+        assert_eq!(log["runs"][0]["results"], json!([]));
+        assert_eq!(log["runs"][0]["tool"]["driver"]["rules"], json!([]));
+        assert_eq!(log["runs"][0]["tool"]["driver"]["name"], "devsec");
+    }
+}
@@ -0,0 +1,575 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+use time::OffsetDateTime;
+
+use crate::domain::sbom::{SbomScope, SbomSubject, purl, split_name};
+
+const CYCLONEDX_SPEC_VERSION: &str = "1.5";
+const SPDX_VERSION: &str = "SPDX-2.3";
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CycloneDxBom {
+    bom_format: &'static str,
+    spec_version: &'static str,
+    serial_number: String,
+    version: u32,
+    metadata: Metadata,
+    components: Vec<Component>,
+    dependencies: Vec<Dependency>,
+}
+
+#[derive(Serialize, Debug)]
+struct Metadata {
+    #[serde(with = "time::serde::rfc3339")]
+    timestamp: OffsetDateTime,
+    tools: Tools,
+    component: Component,
+}
+
+#[derive(Serialize, Debug)]
+struct Tools {
+    components: Vec<Component>,
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct Component {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(rename = "bom-ref", skip_serializing_if = "Option::is_none")]
+    bom_ref: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    purl: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    external_references: Vec<ExternalReference>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    properties: Vec<Property>,
+}
+
+#[derive(Serialize, Debug)]
+struct ExternalReference {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    url: String,
+}
+
+#[derive(Serialize, Debug)]
+struct Property {
+    name: String,
+    value: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Dependency {
+    #[serde(rename = "ref")]
+    reference: String,
+    depends_on: Vec<String>,
+}
+
+/// Builds a CycloneDX 1.5 BOM. A repository is the component the BOM
+/// describes, the codebases of a group are components of an application
+/// named after it. Dependencies are listed once, however many codebases use
+/// them.
+pub fn to_cyclonedx(scope: &SbomScope, subjects: &[SbomSubject]) -> CycloneDxBom {
+    let mut libraries: BTreeMap<String, Component> = BTreeMap::new();
+    let mut applications = Vec::new();
+    let mut dependencies = Vec::new();
+
+    for subject in subjects {
+        let codebase = &subject.codebase;
+        let bom_ref = format!("codebase:{}", codebase.path);
+
+        let mut depends_on = BTreeSet::new();
+        for dependency in &subject.dependencies {
+            let purl = purl(dependency);
+            let (group, name) = split_name(dependency);
+
+            libraries.entry(purl.clone()).or_insert_with(|| Component {
+                kind: "library",
+                bom_ref: Some(purl.clone()),
+                group: group.map(str::to_string),
+                name: name.to_string(),
+                version: dependency.version.clone(),
+                purl: Some(purl.clone()),
+                ..Default::default()
+            });
+            depends_on.insert(purl);
+        }
+
+        applications.push(Component {
+            kind: "application",
+            bom_ref: Some(bom_ref.clone()),
+            name: codebase.path.clone(),
+            description: codebase.description.clone(),
+            external_references: vec![ExternalReference {
+                kind: "vcs",
+                url: codebase.web_url.clone(),
+            }],
+            properties: subject
+                .languages
+                .iter()
+                .map(|(language, percentage)| Property {
+                    name: format!("devsec:language:{language}"),
+                    value: format!("{percentage:.1}"),
+                })
+                .collect(),
+            ..Default::default()
+        });
+        dependencies.push(Dependency {
+            reference: bom_ref,
+            depends_on: depends_on.into_iter().collect(),
+        });
+    }
+
+    let (component, mut components) = if let SbomScope::Repository(_) = scope {
+        (applications.remove(0), Vec::new())
+    } else {
+        let name = scope.name();
+        let bom_ref = format!("group:{name}");
+        dependencies.insert(
+            0,
+            Dependency {
+                reference: bom_ref.clone(),
+                depends_on: applications
+                    .iter()
+                    .filter_map(|application| application.bom_ref.clone())
+                    .collect(),
+            },
+        );
+        let component = Component {
+            kind: "application",
+            bom_ref: Some(bom_ref),
+            name: name.to_string(),
+            ..Default::default()
+        };
+        (component, applications)
+    };
+    components.extend(libraries.into_values());
+
+    CycloneDxBom {
+        bom_format: "CycloneDX",
+        spec_version: CYCLONEDX_SPEC_VERSION,
+        serial_number: format!("urn:uuid:{}", random_uuid()),
+        version: 1,
+        metadata: Metadata {
+            timestamp: OffsetDateTime::now_utc(),
+            tools: Tools {
+                components: vec![Component {
+                    kind: "application",
+                    name: env!("CARGO_PKG_NAME").to_string(),
+                    version: Some(env!("CARGO_PKG_VERSION").to_string()),
+                    ..Default::default()
+                }],
+            },
+            component,
+        },
+        components,
+        dependencies,
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SpdxDocument {
+    spdx_version: &'static str,
+    data_license: &'static str,
+    #[serde(rename = "SPDXID")]
+    spdx_id: &'static str,
+    name: String,
+    document_namespace: String,
+    creation_info: CreationInfo,
+    packages: Vec<SpdxPackage>,
+    relationships: Vec<Relationship>,
+}
+
+#[derive(Serialize, Debug)]
+struct CreationInfo {
+    #[serde(with = "time::serde::rfc3339")]
+    created: OffsetDateTime,
+    creators: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SpdxPackage {
+    #[serde(rename = "SPDXID")]
+    spdx_id: String,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version_info: Option<String>,
+    download_location: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    homepage: Option<String>,
+    files_analyzed: bool,
+    primary_package_purpose: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    external_refs: Vec<SpdxExternalRef>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SpdxExternalRef {
+    reference_category: &'static str,
+    reference_type: &'static str,
+    reference_locator: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Relationship {
+    spdx_element_id: String,
+    relationship_type: &'static str,
+    related_spdx_element: String,
+}
+
+/// Builds an SPDX 2.3 document describing every codebase, each depending on
+/// its packages. SPDX has no properties, languages go in the comment.
+pub fn to_spdx(scope: &SbomScope, subjects: &[SbomSubject]) -> SpdxDocument {
+    let name = scope.name();
+    let mut packages = Vec::new();
+    let mut package_ids: BTreeMap<String, String> = BTreeMap::new();
+    let mut relationships = Vec::new();
+
+    for (index, subject) in subjects.iter().enumerate() {
+        let codebase = &subject.codebase;
+        let codebase_id = format!("SPDXRef-Codebase-{}", index + 1);
+
+        let languages: Vec<String> = subject
+            .languages
+            .iter()
+            .map(|(language, percentage)| format!("{language} {percentage:.1}%"))
+            .collect();
+        packages.push(SpdxPackage {
+            spdx_id: codebase_id.clone(),
+            name: codebase.path.clone(),
+            version_info: None,
+            download_location: codebase.web_url.clone(),
+            homepage: Some(codebase.web_url.clone()),
+            files_analyzed: false,
+            primary_package_purpose: "APPLICATION",
+            description: codebase.description.clone(),
+            comment: (!languages.is_empty())
+                .then(|| format!("Languages: {}", languages.join(", "))),
+            external_refs: Vec::new(),
+        });
+        relationships.push(Relationship {
+            spdx_element_id: "SPDXRef-DOCUMENT".to_string(),
+            relationship_type: "DESCRIBES",
+            related_spdx_element: codebase_id.clone(),
+        });
+
+        let mut depends_on = BTreeSet::new();
+        for dependency in &subject.dependencies {
+            let purl = purl(dependency);
+            let next_id = format!("SPDXRef-Package-{}", package_ids.len() + 1);
+            let package_id = package_ids.entry(purl.clone()).or_insert_with(|| {
+                packages.push(SpdxPackage {
+                    spdx_id: next_id.clone(),
+                    name: dependency.name.clone(),
+                    version_info: dependency.version.clone(),
+                    download_location: "NOASSERTION".to_string(),
+                    homepage: None,
+                    files_analyzed: false,
+                    primary_package_purpose: "LIBRARY",
+                    description: None,
+                    comment: None,
+                    external_refs: vec![SpdxExternalRef {
+                        reference_category: "PACKAGE-MANAGER",
+                        reference_type: "purl",
+                        reference_locator: purl,
+                    }],
+                });
+                next_id
+            });
+            depends_on.insert(package_id.clone());
+        }
+
+        relationships.extend(depends_on.into_iter().map(|package_id| Relationship {
+            spdx_element_id: codebase_id.clone(),
+            relationship_type: "DEPENDS_ON",
+            related_spdx_element: package_id,
+        }));
+    }
+
+    let uuid = random_uuid();
+    SpdxDocument {
+        spdx_version: SPDX_VERSION,
+        data_license: "CC0-1.0",
+        spdx_id: "SPDXRef-DOCUMENT",
+        name: name.to_string(),
+        document_namespace: format!(
+            "https://spdx.org/spdxdocs/{}-{uuid}",
+            name.replace('/', "-")
+        ),
+        creation_info: CreationInfo {
+            // SPDX timestamps have no fractional seconds
+            created: OffsetDateTime::now_utc()
+                .replace_nanosecond(0)
+                .unwrap_or_else(|_| OffsetDateTime::now_utc()),
+            creators: vec![format!(
+                "Tool: {}-{}",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            )],
+        },
+        packages,
+        relationships,
+    }
+}
+
+/// Random (version 4) UUID, identifying a single SBOM.
+fn random_uuid() -> String {
+    let bits = rand::random::<u128>() & !(0xf000 << 64) & !(0xc << 60);
+    let bits = bits | (0x4000 << 64) | (0x8 << 60);
+    let hex = format!("{bits:032x}");
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::domain::{
+        dependency::{Ecosystem, NewDependency},
+        repository::Codebase,
+    };
+
+    fn subject(path: &str, dependencies: &[(Ecosystem, &str, &str)]) -> SbomSubject {
+        SbomSubject {
+            codebase: Codebase {
+                id: 1,
+                external_id: 1,
+                source: "gitlab".to_string(),
+                path: path.to_string(),
+                web_url: format!("https://gitlab.com/{path}"),
+                description: None,
+                created_at: OffsetDateTime::UNIX_EPOCH,
+                updated_at: OffsetDateTime::UNIX_EPOCH,
+                pushed_at: OffsetDateTime::UNIX_EPOCH,
+                size: 0,
+                commit_count: 1,
+                private: true,
+                archived: false,
+                default_branch: Some("main".to_string()),
+                clone_url: None,
+            },
+            languages: vec![("Rust".to_string(), 87.5)],
+            dependencies: dependencies
+                .iter()
+                .map(|&(ecosystem, name, version)| NewDependency {
+                    ecosystem,
+                    name: name.to_string(),
+                    version: Some(version.to_string()),
+                    manifest: "package.json".to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    fn subjects() -> Vec<SbomSubject> {
+        vec![
+            subject(
+                "grp/web",
+                &[
+                    (Ecosystem::Npm, "@babel/core", "7.1.0"),
+                    (Ecosystem::Npm, "left-pad", "1.3.0"),
+                ],
+            ),
+            subject(
+                "grp/api",
+                &[
+                    (Ecosystem::Maven, "org.slf4j:slf4j-api", "2.0.9"),
+                    (Ecosystem::Npm, "left-pad", "1.3.0"),
+                ],
+            ),
+        ]
+    }
+
+    fn strings<'a>(values: impl IntoIterator<Item = &'a Value>) -> Vec<&'a str> {
+        values
+            .into_iter()
+            .map(|value| value.as_str().unwrap())
+            .collect()
+    }
+
+    /// Checks the version 4 and RFC 4122 variant bits of a UUID.
+    fn assert_uuid_v4(uuid: &str) {
+        let groups: Vec<&str> = uuid.split('-').collect();
+        assert_eq!(
+            groups.iter().map(|group| group.len()).collect::<Vec<_>>(),
+            [8, 4, 4, 4, 12],
+            "{uuid}"
+        );
+        assert!(
+            uuid.chars()
+                .all(|char| char == '-' || char.is_ascii_hexdigit())
+        );
+        assert!(groups[2].starts_with('4'), "{uuid}");
+        assert!(groups[3].starts_with(['8', '9', 'a', 'b']), "{uuid}");
+    }
+
+    #[test]
+    fn random_uuids_are_version_4() {
+        for _ in 0..100 {
+            assert_uuid_v4(&random_uuid());
+        }
+        assert_ne!(random_uuid(), random_uuid());
+    }
+
+    #[test]
+    fn cyclonedx_lists_shared_dependencies_once() {
+        let bom = serde_json::to_value(to_cyclonedx(
+            &SbomScope::Group("grp".to_string()),
+            &subjects(),
+        ))
+        .unwrap();
+
+        assert_eq!(bom["specVersion"], "1.5");
+        assert_uuid_v4(
+            bom["serialNumber"]
+                .as_str()
+                .unwrap()
+                .strip_prefix("urn:uuid:")
+                .unwrap(),
+        );
+        assert_eq!(bom["metadata"]["component"]["bom-ref"], "group:grp");
+
+        let components = bom["components"].as_array().unwrap();
+        assert_eq!(
+            strings(components.iter().map(|component| &component["bom-ref"])),
+            [
+                "codebase:grp/web",
+                "codebase:grp/api",
+                "pkg:maven/org.slf4j/slf4j-api@2.0.9",
+                "pkg:npm/%40babel/core@7.1.0",
+                "pkg:npm/left-pad@1.3.0",
+            ]
+        );
+        assert_eq!(
+            components[2],
+            json!({
+                "type": "library",
+                "bom-ref": "pkg:maven/org.slf4j/slf4j-api@2.0.9",
+                "group": "org.slf4j",
+                "name": "slf4j-api",
+                "version": "2.0.9",
+                "purl": "pkg:maven/org.slf4j/slf4j-api@2.0.9",
+            })
+        );
+        assert_eq!(components[3]["group"], "@babel");
+        assert_eq!(
+            components[0]["properties"],
+            json!([{"name": "devsec:language:Rust", "value": "87.5"}])
+        );
+
+        let dependencies = bom["dependencies"].as_array().unwrap();
+        assert_eq!(dependencies[0]["ref"], "group:grp");
+        assert_eq!(
+            strings(dependencies[0]["dependsOn"].as_array().unwrap()),
+            ["codebase:grp/web", "codebase:grp/api"]
+        );
+        assert_eq!(
+            strings(dependencies[2]["dependsOn"].as_array().unwrap()),
+            [
+                "pkg:maven/org.slf4j/slf4j-api@2.0.9",
+                "pkg:npm/left-pad@1.3.0"
+            ]
+        );
+    }
+
+    #[test]
+    fn cyclonedx_describes_a_single_repository() {
+        let bom = serde_json::to_value(to_cyclonedx(
+            &SbomScope::Repository("grp/web".to_string()),
+            &subjects()[..1],
+        ))
+        .unwrap();
+
+        assert_eq!(bom["metadata"]["component"]["bom-ref"], "codebase:grp/web");
+        assert_eq!(bom["components"].as_array().unwrap().len(), 2);
+        assert_eq!(bom["dependencies"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn spdx_lists_shared_dependencies_once() {
+        let document = serde_json::to_value(to_spdx(
+            &SbomScope::Group("acme/grp".to_string()),
+            &subjects(),
+        ))
+        .unwrap();
+
+        let namespace = document["documentNamespace"].as_str().unwrap();
+        assert_uuid_v4(
+            namespace
+                .strip_prefix("https://spdx.org/spdxdocs/acme-grp-")
+                .unwrap(),
+        );
+
+        let packages = document["packages"].as_array().unwrap();
+        assert_eq!(
+            strings(packages.iter().map(|package| &package["SPDXID"])),
+            [
+                "SPDXRef-Codebase-1",
+                "SPDXRef-Package-1",
+                "SPDXRef-Package-2",
+                "SPDXRef-Codebase-2",
+                "SPDXRef-Package-3",
+            ]
+        );
+        assert_eq!(packages[0]["comment"], "Languages: Rust 87.5%");
+        assert_eq!(
+            packages[1]["externalRefs"][0]["referenceLocator"],
+            "pkg:npm/%40babel/core@7.1.0"
+        );
+        assert_eq!(
+            packages[4]["externalRefs"][0]["referenceLocator"],
+            "pkg:maven/org.slf4j/slf4j-api@2.0.9"
+        );
+
+        let relationships: Vec<(&str, &str, &str)> = document["relationships"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|relationship| {
+                (
+                    relationship["spdxElementId"].as_str().unwrap(),
+                    relationship["relationshipType"].as_str().unwrap(),
+                    relationship["relatedSpdxElement"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            relationships,
+            [
+                ("SPDXRef-DOCUMENT", "DESCRIBES", "SPDXRef-Codebase-1"),
+                ("SPDXRef-Codebase-1", "DEPENDS_ON", "SPDXRef-Package-1"),
+                ("SPDXRef-Codebase-1", "DEPENDS_ON", "SPDXRef-Package-2"),
+                ("SPDXRef-DOCUMENT", "DESCRIBES", "SPDXRef-Codebase-2"),
+                ("SPDXRef-Codebase-2", "DEPENDS_ON", "SPDXRef-Package-2"),
+                ("SPDXRef-Codebase-2", "DEPENDS_ON", "SPDXRef-Package-3"),
+            ]
+        );
+    }
+}
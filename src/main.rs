@@ -1,15 +1,61 @@
+mod credentials;
 mod domain;
 mod error;
 mod infrastructure;
 mod repository;
 mod service;
+mod settings;
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use clap::{Parser, Subcommand};
-use domain::statistics::get_repository_statistics;
+use credentials::{TokenArgs, resolve_token, store_secret};
+use domain::{
+    branch_protection::{count_unknown_branch_rules, get_branch_protection_findings},
+    dependency::{Ecosystem, PackageSpec},
+    mirror::{DiskQuota, MirrorOptions, Transport},
+    policy::load_policy,
+    sbom::{SbomFormat, SbomScope},
+    secret::{ScanOptions, load_allowlist},
+    statistics::{StatsGrouping, get_grouped_statistics, get_repository_statistics},
+    sync::{Since, SyncOptions},
+};
 use error::AppError;
-use infrastructure::{api::gitlab::client::GitLabClient, db::connection::init_db};
-use repository::codebase_repository::{CodebaseRepository, SqliteCodebaseRepository};
-use service::codebase_service::CodebaseService;
+use infrastructure::{
+    api::{
+        github::client::GitHubClient,
+        gitlab::client::{GITLAB_DEFAULT_URL, GitLabClient, GitLabInstance},
+        graphql::DEFAULT_TIMEOUT,
+    },
+    db::{
+        connection::{db_path, init_db, open_db_read_only},
+        info::get_database_info,
+    },
+    utils::{
+        dirs::mirrors_dir,
+        sarif::to_sarif,
+        sbom::{to_cyclonedx, to_spdx},
+    },
+};
+use repository::{
+    advisory_repository::{AdvisoryRepository, SqliteAdvisoryRepository},
+    codebase_repository::{CodebaseRepository, SqliteCodebaseRepository},
+    credential_repository::{CredentialRepository, SqliteCredentialRepository},
+    dependency_repository::{DependencyRepository, SqliteDependencyRepository},
+    mirror_repository::{MirrorRepository, SqliteMirrorRepository},
+    secret_repository::{SecretRepository, SqliteSecretRepository},
+    sync_repository::{SqliteSyncRepository, SyncRepository},
+};
+use service::{
+    advisory_service::AdvisoryService, codebase_service::CodebaseService,
+    credential_service::CredentialService, dependency_service::DependencyService,
+    history_service::HistoryService, mirror_service::MirrorService, policy_service::PolicyService,
+    secret_service::SecretService,
+};
+use settings::{Profile, config_path, init_config, load_config, set_config};
 use sqlx::SqlitePool;
 use tabled::{
     Table,
@@ -19,6 +65,23 @@ use tabled::{
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
+    #[arg(
+        long,
+        global = true,
+        env = "DEVSEC_PROFILE",
+        help = "Config profile to use [default: the config's profile]"
+    )]
+    profile: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "path",
+        env = "DEVSEC_DB",
+        help = "SQLite database to use [default: the profile's db or devsec.db in the data directory]"
+    )]
+    db: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -29,12 +92,29 @@ enum Commands {
         #[command(subcommand)]
         service: UpdateServices,
     },
-    // Stats {
-    //     #[arg(long, help = "Return stats as JSON")]
-    //     json: bool,
-    // },
+    Stats {
+        #[arg(long, help = "Return stats as JSON")]
+        json: bool,
+
+        #[arg(long, value_enum, help = "Break statistics down by group")]
+        by: Option<StatsGrouping>,
+
+        #[arg(
+            short = 'n',
+            long,
+            default_value_t = 5,
+            help = "Number of repositories in top lists"
+        )]
+        top: i64,
+    },
     Search {
-        #[arg(short, long, value_name = "search query")]
+        #[arg(
+            short,
+            long,
+            value_name = "search query",
+            allow_hyphen_values = true,
+            help = "Free text combined with filters like lang:rust size:>100MB pushed:<1y private:true archived:false path:backend/*"
+        )]
         query: String,
 
         #[arg(long, help = "Return result as json")]
@@ -51,36 +131,588 @@ enum Commands {
         )]
         limit: i64,
     },
+    Check {
+        #[arg(
+            short,
+            long,
+            value_name = "TOML or YAML file",
+            help = "Policy file with the rules to evaluate"
+        )]
+        policy: PathBuf,
+
+        #[arg(long, help = "Return result as json")]
+        json: bool,
+
+        #[arg(long, conflicts_with = "json", help = "Return result as SARIF 2.1.0")]
+        sarif: bool,
+    },
+    Report {
+        #[command(subcommand)]
+        report: Reports,
+    },
+    Scan {
+        #[command(subcommand)]
+        scan: Scans,
+    },
+    Mirror {
+        #[command(subcommand)]
+        mirror: Mirrors,
+    },
+    Deps {
+        #[command(subcommand)]
+        deps: Dependencies,
+    },
+    Advisories {
+        #[command(subcommand)]
+        advisories: Advisories,
+    },
+    Export {
+        #[command(subcommand)]
+        export: Exports,
+    },
+    Config {
+        #[command(subcommand)]
+        config: Configs,
+    },
+    Db {
+        #[command(subcommand)]
+        db: Databases,
+    },
+    Diff {
+        #[arg(
+            long,
+            value_name = "run id | age",
+            help = "Baseline sync run id or age like 7d [default: previous sync]"
+        )]
+        since: Option<Since>,
+
+        #[arg(
+            long,
+            default_value_t = 50.0,
+            help = "Minimum size growth in percent to report"
+        )]
+        min_growth: f64,
+
+        #[arg(long, help = "Return result as json")]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum Reports {
+    /// Repositories whose default branch is unprotected or allows force pushes
+    BranchProtection {
+        #[arg(long, help = "Return result as json")]
+        json: bool,
+
+        #[arg(long, conflicts_with = "json", help = "Return result as SARIF 2.1.0")]
+        sarif: bool,
+    },
+    /// Secrets found by the latest scan of every repository
+    Secrets {
+        #[arg(long, help = "Return result as json")]
+        json: bool,
+
+        #[arg(long, conflicts_with = "json", help = "Return result as SARIF 2.1.0")]
+        sarif: bool,
+    },
+    /// Access tokens and deploy credentials that never expire, can write or
+    /// went unused, as collected by `update gitlab --credentials`
+    Credentials {
+        #[arg(long, help = "Return result as json")]
+        json: bool,
+
+        #[arg(long, conflicts_with = "json", help = "Return result as SARIF 2.1.0")]
+        sarif: bool,
+
+        #[arg(
+            long,
+            value_name = "days",
+            default_value_t = 90,
+            help = "Report access tokens not used for this many days"
+        )]
+        unused_days: i64,
+    },
+    /// Dependencies affected by imported advisories
+    Vulnerabilities {
+        #[arg(long, help = "Return result as json")]
+        json: bool,
+
+        #[arg(long, conflicts_with = "json", help = "Return result as SARIF 2.1.0")]
+        sarif: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum Scans {
+    /// Clone or fetch repositories and scan them for secrets
+    Secrets {
+        #[arg(
+            short,
+            long,
+            value_name = "search query",
+            default_value = "",
+            allow_hyphen_values = true,
+            help = "Only scan repositories matching the query [default: all]"
+        )]
+        query: String,
+
+        #[arg(long, help = "Scan every commit, not only the files at HEAD")]
+        history: bool,
+
+        #[arg(
+            long,
+            value_enum,
+            default_value_t,
+            help = "How repositories are cloned"
+        )]
+        transport: Transport,
+
+        #[arg(long, help = "Scan the existing mirrors without fetching them")]
+        offline: bool,
+
+        #[arg(
+            long,
+            value_name = "TOML or YAML file",
+            help = "Paths, patterns and fingerprints of findings to ignore"
+        )]
+        allowlist: Option<PathBuf>,
+
+        #[arg(
+            long,
+            default_value_t = 4,
+            value_parser = clap::value_parser!(u16).range(1..),
+            help = "Number of repositories scanned at once"
+        )]
+        concurrency: u16,
+
+        #[arg(long, help = "Return result as json")]
+        json: bool,
+
+        #[arg(long, conflicts_with = "json", help = "Return result as SARIF 2.1.0")]
+        sarif: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum Mirrors {
+    /// Clone or fetch repositories into the local mirror cache
+    Sync {
+        #[arg(
+            short,
+            long,
+            value_name = "search query",
+            default_value = "",
+            allow_hyphen_values = true,
+            help = "Only mirror repositories matching the query [default: all]"
+        )]
+        query: String,
+
+        #[arg(
+            long,
+            value_enum,
+            default_value_t,
+            help = "How repositories are cloned"
+        )]
+        transport: Transport,
+
+        #[arg(
+            long,
+            value_name = "size",
+            help = "Don't clone repositories once the mirrors take up this much, e.g. 20GB"
+        )]
+        quota: Option<DiskQuota>,
+
+        #[arg(
+            long,
+            default_value_t = 4,
+            value_parser = clap::value_parser!(u16).range(1..),
+            help = "Number of repositories fetched at once"
+        )]
+        concurrency: u16,
+    },
+    /// Remove mirrors of deleted repositories, or evict mirrors to fit a quota
+    Prune {
+        #[arg(
+            long,
+            value_name = "size",
+            help = "Evict mirrors of the least recently pushed repositories until the rest fit"
+        )]
+        quota: Option<DiskQuota>,
+    },
+    /// List mirrored repositories with their last fetched commit and size
+    Status {
+        #[arg(long, help = "Return result as json")]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum Dependencies {
+    /// Read dependencies from the manifests and lockfiles of mirrored repositories
+    Update {
+        #[arg(
+            short,
+            long,
+            value_name = "search query",
+            default_value = "",
+            allow_hyphen_values = true,
+            help = "Only read repositories matching the query [default: all]"
+        )]
+        query: String,
+
+        #[arg(long, help = "Read mirrors again even if HEAD hasn't moved")]
+        force: bool,
+    },
+    /// Find every repository using a package
+    Search {
+        #[arg(value_name = "package[@version]")]
+        package: PackageSpec,
+
+        #[arg(long, value_enum, help = "Only match packages of this ecosystem")]
+        ecosystem: Option<Ecosystem>,
+
+        #[arg(long, help = "Return result as json")]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum Advisories {
+    /// Import OSV advisories from a directory or zip file
+    Import {
+        #[arg(value_name = "directory | zip file")]
+        path: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum Exports {
+    /// Software bill of materials from the dependency inventory
+    Sbom {
+        #[arg(long, value_enum, default_value_t)]
+        format: SbomFormat,
+
+        #[arg(long, value_name = "path", help = "Only this repository, e.g. grp/app")]
+        repo: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "path",
+            conflicts_with = "repo",
+            help = "Every repository below this group or organization"
+        )]
+        group: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum Configs {
+    /// Write a commented config file
+    Init {
+        #[arg(long, help = "Overwrite an existing config file")]
+        force: bool,
+    },
+    /// Print the settings of the selected profile
+    Show,
+    /// Set a setting of the selected profile, or a top-level one without a profile
+    Set {
+        #[arg(value_name = "key", help = "e.g. gitlab.url, gitlab.groups or format")]
+        key: String,
+
+        #[arg(
+            value_name = "value",
+            help = "Lists are comma separated, an empty value removes the setting"
+        )]
+        value: String,
+    },
+    /// Encrypt a token read from stdin into the secrets file, for use as `token_secret`
+    SetSecret {
+        #[arg(
+            value_name = "name",
+            help = "e.g. gitlab, an empty token removes the secret"
+        )]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum Databases {
+    /// Path, schema version, row counts and last sync of the database
+    Info {
+        #[arg(long, help = "Return result as json")]
+        json: bool,
+    },
 }
 
 #[derive(Subcommand)]
 enum UpdateServices {
     Gitlab {
-        #[arg(long, value_name = "GITLAB_TOKEN", env = "GITLAB_TOKEN")]
-        auth: String,
+        #[arg(
+            long,
+            value_name = "GITLAB_TOKEN",
+            help = "Token, deprecated in favour of --token-file, --token-stdin or the environment"
+        )]
+        auth: Option<String>,
 
-        #[arg(short, long, value_name = "GitLab group id")]
-        group_id: String,
+        #[arg(long, value_name = "path", help = "Read the token from a file")]
+        token_file: Option<PathBuf>,
+
+        #[arg(long, help = "Read the token from stdin")]
+        token_stdin: bool,
+
+        #[arg(
+            short,
+            long,
+            value_name = "GitLab group id",
+            help = "Group to sync [default: the profile's groups]"
+        )]
+        group_id: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "GITLAB_URL",
+            help = "Base URL of the GitLab instance [default: the profile's url, GITLAB_URL or https://gitlab.com]"
+        )]
+        url: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "PEM file",
+            help = "Additional CA certificates to trust [default: the profile's ca_cert or GITLAB_CA_CERT]"
+        )]
+        ca_cert: Option<PathBuf>,
+
+        #[arg(long, help = "Accept invalid or self-signed TLS certificates")]
+        insecure: bool,
+
+        #[arg(
+            long,
+            value_name = "seconds",
+            help = "Timeout of a single API request [default: 30]"
+        )]
+        timeout: Option<u64>,
+
+        #[arg(
+            long,
+            value_name = "days",
+            default_value_t = 14,
+            help = "Warn if the token expires within this many days"
+        )]
+        expiry_warning: i64,
+
+        #[arg(
+            long,
+            help = "Also collect access tokens, deploy keys and deploy tokens, needs the Maintainer role"
+        )]
+        credentials: bool,
+
+        #[arg(long, help = "Delete repositories that no longer exist upstream")]
+        prune: bool,
+
+        #[arg(
+            long,
+            help = "Continue the last sync of the scope if it did not complete"
+        )]
+        resume: bool,
+
+        #[arg(
+            long,
+            help = "Fetch every repository instead of only recently active ones"
+        )]
+        full: bool,
+
+        #[arg(
+            long,
+            default_value_t = 4,
+            value_parser = clap::value_parser!(u16).range(1..),
+            help = "Number of fetched pages that may wait to be written"
+        )]
+        prefetch: u16,
+    },
+    Github {
+        #[arg(
+            long,
+            value_name = "GITHUB_TOKEN",
+            help = "Token, deprecated in favour of --token-file, --token-stdin or the environment"
+        )]
+        auth: Option<String>,
+
+        #[arg(long, value_name = "path", help = "Read the token from a file")]
+        token_file: Option<PathBuf>,
+
+        #[arg(long, help = "Read the token from stdin")]
+        token_stdin: bool,
+
+        #[arg(
+            short,
+            long,
+            value_name = "GitHub organization",
+            help = "Organization to sync [default: the profile's organizations]"
+        )]
+        org: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "seconds",
+            help = "Timeout of a single API request [default: 30]"
+        )]
+        timeout: Option<u64>,
+
+        #[arg(long, help = "Delete repositories that no longer exist upstream")]
+        prune: bool,
+
+        #[arg(
+            long,
+            help = "Continue the last sync of the scope if it did not complete"
+        )]
+        resume: bool,
+
+        #[arg(
+            long,
+            help = "Fetch every repository instead of only recently active ones"
+        )]
+        full: bool,
+
+        #[arg(
+            long,
+            default_value_t = 4,
+            value_parser = clap::value_parser!(u16).range(1..),
+            help = "Number of fetched pages that may wait to be written"
+        )]
+        prefetch: u16,
     },
 }
 
 #[tokio::main]
 async fn main() -> Result<(), AppError> {
     let cli = Cli::parse();
-    let pool = init_db().await?;
 
-    let codebase_repository = Box::new(SqliteCodebaseRepository::new(pool));
+    // Managed without a database, and even if the config doesn't load
+    let command = match cli.command {
+        Some(Commands::Config { config }) => {
+            return config_command(cli.profile.as_deref(), config);
+        }
+        command => command,
+    };
+
+    let profile = load_config()?.profile(cli.profile.as_deref())?;
+    let db_path = db_path(cli.db.as_deref().or(profile.db.as_deref()))?;
+    let prefer_json = profile.prefers_json();
+
+    // Inspecting a database must not create it
+    let command = match command {
+        Some(Commands::Db { db }) => return db_command(&db_path, db, prefer_json).await,
+        command => command,
+    };
+
+    let pool = init_db(&db_path).await?;
+    let mirrors_dir = mirrors_dir(&db_path)?;
+
+    let codebase_repository = Box::new(SqliteCodebaseRepository::new(pool.clone()));
+    let sync_repository = Box::new(SqliteSyncRepository::new(pool.clone()));
+    let secret_repository = Box::new(SqliteSecretRepository::new(pool.clone()));
+    let mirror_repository = Box::new(SqliteMirrorRepository::new(pool.clone()));
+    let dependency_repository = Box::new(SqliteDependencyRepository::new(pool.clone()));
+    let advisory_repository = Box::new(SqliteAdvisoryRepository::new(pool.clone()));
+    let credential_repository = Box::new(SqliteCredentialRepository::new(pool.clone()));
 
-    match cli.command {
-        Some(Commands::Update { service }) => update(codebase_repository, service).await?,
-        //Some(Commands::Stats { json }) => stats(&pool, json).await?,
+    match command {
+        Some(Commands::Update { service }) => {
+            update(
+                codebase_repository,
+                sync_repository,
+                credential_repository,
+                &profile,
+                service,
+            )
+            .await?
+        }
+        Some(Commands::Stats { json, by, top }) => {
+            stats(&pool, json || prefer_json, by, top).await?
+        }
         Some(Commands::Search {
             query,
             json,
             include_archived,
             limit,
-        }) => search(codebase_repository, &query, json, include_archived, limit).await?,
-        None => {}
+        }) => {
+            search(
+                codebase_repository,
+                sync_repository,
+                &query,
+                json || prefer_json,
+                include_archived,
+                limit,
+            )
+            .await?
+        }
+        Some(Commands::Check {
+            policy,
+            json,
+            sarif,
+        }) => check(codebase_repository, &policy, json || prefer_json, sarif).await?,
+        Some(Commands::Report { report }) => {
+            report_command(
+                &pool,
+                codebase_repository,
+                mirror_repository,
+                secret_repository,
+                advisory_repository,
+                report,
+                prefer_json,
+            )
+            .await?
+        }
+        Some(Commands::Scan { scan }) => {
+            scan_command(
+                codebase_repository,
+                mirror_repository,
+                secret_repository,
+                mirrors_dir,
+                scan,
+                prefer_json,
+            )
+            .await?
+        }
+        Some(Commands::Mirror { mirror }) => {
+            mirror_command(
+                codebase_repository,
+                mirror_repository,
+                mirrors_dir,
+                mirror,
+                prefer_json,
+            )
+            .await?
+        }
+        Some(Commands::Deps { deps }) => {
+            deps_command(
+                codebase_repository,
+                mirror_repository,
+                dependency_repository,
+                deps,
+                prefer_json,
+            )
+            .await?
+        }
+        Some(Commands::Advisories { advisories }) => {
+            advisories_command(advisory_repository, advisories).await?
+        }
+        Some(Commands::Export { export }) => {
+            export_command(
+                codebase_repository,
+                mirror_repository,
+                dependency_repository,
+                export,
+            )
+            .await?
+        }
+        Some(Commands::Diff {
+            since,
+            min_growth,
+            json,
+        }) => diff(sync_repository, since, min_growth, json || prefer_json).await?,
+        Some(Commands::Config { .. }) | Some(Commands::Db { .. }) | None => {}
     };
 
     Ok(())
@@ -88,20 +720,182 @@ async fn main() -> Result<(), AppError> {
 
 async fn update(
     codebase_repository: Box<dyn CodebaseRepository>,
+    sync_repository: Box<dyn SyncRepository>,
+    credential_repository: Box<dyn CredentialRepository>,
+    profile: &Profile,
     service: UpdateServices,
 ) -> Result<(), AppError> {
     match service {
-        UpdateServices::Gitlab { auth, group_id } => {
-            let gitlab_client = GitLabClient::new(&auth);
-            let codebase_service = CodebaseService::new(codebase_repository, gitlab_client);
-            codebase_service.update_from_gitlab(&group_id).await?;
+        UpdateServices::Gitlab {
+            auth,
+            token_file,
+            token_stdin,
+            group_id,
+            url,
+            ca_cert,
+            insecure,
+            timeout,
+            expiry_warning,
+            credentials,
+            prune,
+            resume,
+            full,
+            prefetch,
+        } => {
+            let config = profile.gitlab.clone();
+            let auth = resolve_token(
+                "gitlab",
+                TokenArgs {
+                    auth,
+                    file: token_file,
+                    stdin: token_stdin,
+                },
+                &config.token,
+            )?;
+            let groups = match group_id {
+                Some(group_id) => vec![group_id],
+                None => config.groups,
+            };
+            if groups.is_empty() {
+                return Err(AppError::InvalidConfig(
+                    "no GitLab group, pass --group-id or set gitlab.groups".to_string(),
+                ));
+            }
+
+            let instance = GitLabInstance {
+                // The environment may be set up for another instance than the profile's
+                url: url
+                    .or(config.url)
+                    .or_else(|| {
+                        std::env::var("GITLAB_URL")
+                            .ok()
+                            .filter(|url| !url.is_empty())
+                    })
+                    .unwrap_or_else(|| GITLAB_DEFAULT_URL.to_string()),
+                ca_cert: ca_cert.or(config.ca_cert).or_else(|| {
+                    std::env::var_os("GITLAB_CA_CERT")
+                        .filter(|path| !path.is_empty())
+                        .map(PathBuf::from)
+                }),
+                accept_invalid_certs: insecure || config.accept_invalid_certs.unwrap_or(false),
+                timeout: timeout
+                    .or(config.timeout)
+                    .map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_TIMEOUT),
+            };
+            let gitlab_client = GitLabClient::new(&auth, &instance)?;
+            let options = SyncOptions {
+                prune,
+                resume,
+                full,
+                prefetch: prefetch.into(),
+            };
+            let codebase_service = CodebaseService::new(codebase_repository, sync_repository);
+            codebase_service
+                .check_gitlab_token(&gitlab_client, expiry_warning)
+                .await?;
+            let credential_service = CredentialService::new(credential_repository);
+            for group_id in &groups {
+                codebase_service
+                    .update_from_gitlab(&gitlab_client, group_id, &options)
+                    .await?;
+
+                if credentials {
+                    // The group may be given by id or in another case than its path
+                    let group = gitlab_client.get_group(group_id).await?;
+                    let codebases = codebase_service
+                        .find_in_group(gitlab_client.source(), &group.full_path)
+                        .await?;
+                    if codebases.is_empty() {
+                        eprintln!(
+                            "No synced projects in {}, collecting only group credentials",
+                            group.full_path
+                        );
+                    }
+                    let summary = credential_service
+                        .update_from_gitlab(&gitlab_client, &group, &codebases)
+                        .await?;
+                    println!(
+                        "Collected {} credentials of {} projects and groups in {}, {} skipped",
+                        summary.credentials, summary.owners, group.full_path, summary.skipped
+                    );
+                }
+            }
+        }
+        UpdateServices::Github {
+            auth,
+            token_file,
+            token_stdin,
+            org,
+            timeout,
+            prune,
+            resume,
+            full,
+            prefetch,
+        } => {
+            let config = profile.github.clone();
+            let auth = resolve_token(
+                "github",
+                TokenArgs {
+                    auth,
+                    file: token_file,
+                    stdin: token_stdin,
+                },
+                &config.token,
+            )?;
+            let orgs = match org {
+                Some(org) => vec![org],
+                None => config.orgs,
+            };
+            if orgs.is_empty() {
+                return Err(AppError::InvalidConfig(
+                    "no GitHub organization, pass --org or set github.orgs".to_string(),
+                ));
+            }
+
+            let timeout = timeout
+                .or(config.timeout)
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_TIMEOUT);
+            let github_client = GitHubClient::new(&auth, timeout);
+            let options = SyncOptions {
+                prune,
+                resume,
+                full,
+                prefetch: prefetch.into(),
+            };
+            let codebase_service = CodebaseService::new(codebase_repository, sync_repository);
+            for org in &orgs {
+                codebase_service
+                    .update_from_github(&github_client, org, &options)
+                    .await?;
+            }
         }
     }
     Ok(())
 }
 
-async fn stats(pool: &SqlitePool, json: bool) -> Result<(), AppError> {
-    let data = get_repository_statistics(pool).await?;
+async fn stats(
+    pool: &SqlitePool,
+    json: bool,
+    by: Option<StatsGrouping>,
+    top: i64,
+) -> Result<(), AppError> {
+    if let Some(grouping) = by {
+        let data = get_grouped_statistics(pool, grouping).await?;
+
+        if json {
+            println!("{}", serde_json::to_string(&data)?);
+        } else {
+            let mut table = Table::new(&data);
+            table.with(Style::modern());
+            println!("{table}");
+        }
+
+        return Ok(());
+    }
+
+    let data = get_repository_statistics(pool, top).await?;
 
     if json {
         println!("{}", serde_json::to_string(&data)?);
@@ -117,13 +911,13 @@ async fn stats(pool: &SqlitePool, json: bool) -> Result<(), AppError> {
 
 async fn search(
     codebase_repository: Box<dyn CodebaseRepository>,
+    sync_repository: Box<dyn SyncRepository>,
     query: &str,
     json: bool,
     include_archived: bool,
     limit: i64,
 ) -> Result<(), AppError> {
-    let gitlab_client = GitLabClient::new("");
-    let codebase_service = CodebaseService::new(codebase_repository, gitlab_client);
+    let codebase_service = CodebaseService::new(codebase_repository, sync_repository);
     let data = codebase_service
         .search(query, include_archived, limit)
         .await?;
@@ -137,3 +931,394 @@ async fn search(
     }
     Ok(())
 }
+
+async fn check(
+    codebase_repository: Box<dyn CodebaseRepository>,
+    policy: &Path,
+    json: bool,
+    sarif: bool,
+) -> Result<(), AppError> {
+    let policy = load_policy(policy)?;
+    let policy_service = PolicyService::new(codebase_repository);
+    let data = policy_service.check(&policy).await?;
+
+    if sarif {
+        println!("{}", serde_json::to_string(&to_sarif(&data))?);
+    } else if json {
+        println!("{}", serde_json::to_string(&data)?);
+    } else {
+        let mut table = Table::new(&data);
+        table.with(Style::modern());
+        println!("{table}");
+    }
+
+    // Fail CI pipelines that gate on the policy
+    if !data.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+async fn report_command(
+    pool: &SqlitePool,
+    codebase_repository: Box<dyn CodebaseRepository>,
+    mirror_repository: Box<dyn MirrorRepository>,
+    secret_repository: Box<dyn SecretRepository>,
+    advisory_repository: Box<dyn AdvisoryRepository>,
+    report: Reports,
+    prefer_json: bool,
+) -> Result<(), AppError> {
+    match report {
+        Reports::BranchProtection { json, sarif } => {
+            let data = get_branch_protection_findings(pool).await?;
+
+            if sarif {
+                println!("{}", serde_json::to_string(&to_sarif(&data))?);
+            } else if json || prefer_json {
+                println!("{}", serde_json::to_string(&data)?);
+            } else {
+                let mut table = Table::new(&data);
+                table.with(Style::modern());
+                println!("{table}");
+            }
+
+            let unknown = count_unknown_branch_rules(pool).await?;
+            if unknown > 0 {
+                eprintln!(
+                    "{unknown} repositories were left out, the token could not read their branch rules"
+                );
+            }
+        }
+        Reports::Secrets { json, sarif } => {
+            let secret_service =
+                SecretService::new(codebase_repository, mirror_repository, secret_repository);
+            let data = secret_service.findings().await?;
+
+            if sarif {
+                println!("{}", serde_json::to_string(&to_sarif(&data))?);
+            } else if json || prefer_json {
+                println!("{}", serde_json::to_string(&data)?);
+            } else {
+                let mut table = Table::new(&data);
+                table.with(Style::modern());
+                println!("{table}");
+            }
+        }
+        Reports::Credentials {
+            json,
+            sarif,
+            unused_days,
+        } => {
+            let credential_repository = SqliteCredentialRepository::new(pool.clone());
+            let credential_service = CredentialService::new(Box::new(credential_repository));
+            let data = credential_service.findings(unused_days).await?;
+
+            if sarif {
+                println!("{}", serde_json::to_string(&to_sarif(&data))?);
+            } else if json || prefer_json {
+                println!("{}", serde_json::to_string(&data)?);
+            } else {
+                let mut table = Table::new(&data);
+                table.with(Style::modern());
+                println!("{table}");
+            }
+        }
+        Reports::Vulnerabilities { json, sarif } => {
+            let advisory_service = AdvisoryService::new(advisory_repository);
+            let data = advisory_service.vulnerabilities().await?;
+
+            if sarif {
+                println!("{}", serde_json::to_string(&to_sarif(&data))?);
+            } else if json || prefer_json {
+                println!("{}", serde_json::to_string(&data)?);
+            } else {
+                let mut table = Table::new(&data);
+                table.with(Style::modern());
+                println!("{table}");
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn scan_command(
+    codebase_repository: Box<dyn CodebaseRepository>,
+    mirror_repository: Box<dyn MirrorRepository>,
+    secret_repository: Box<dyn SecretRepository>,
+    mirrors_dir: PathBuf,
+    scan: Scans,
+    prefer_json: bool,
+) -> Result<(), AppError> {
+    match scan {
+        Scans::Secrets {
+            query,
+            history,
+            transport,
+            offline,
+            allowlist,
+            concurrency,
+            json,
+            sarif,
+        } => {
+            let allowlist = match allowlist {
+                Some(path) => load_allowlist(&path)?,
+                None => Default::default(),
+            };
+            let options = ScanOptions {
+                mirrors_dir,
+                history,
+                transport,
+                offline,
+                concurrency: concurrency.into(),
+            };
+            let secret_service =
+                SecretService::new(codebase_repository, mirror_repository, secret_repository);
+            let summary = secret_service.scan(&query, allowlist, &options).await?;
+            let data = summary.findings;
+
+            if sarif {
+                println!("{}", serde_json::to_string(&to_sarif(&data))?);
+            } else if json || prefer_json {
+                println!("{}", serde_json::to_string(&data)?);
+            } else {
+                let mut table = Table::new(&data);
+                table.with(Style::modern());
+                println!("{table}");
+            }
+
+            // Fail CI pipelines on leaked secrets, and on repositories that
+            // went unscanned
+            if !data.is_empty() {
+                std::process::exit(1);
+            }
+            if summary.failed > 0 {
+                eprintln!("{} repositories could not be scanned", summary.failed);
+                std::process::exit(2);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn mirror_command(
+    codebase_repository: Box<dyn CodebaseRepository>,
+    mirror_repository: Box<dyn MirrorRepository>,
+    mirrors_dir: PathBuf,
+    mirror: Mirrors,
+    prefer_json: bool,
+) -> Result<(), AppError> {
+    let mirror_service = MirrorService::new(codebase_repository, mirror_repository);
+
+    match mirror {
+        Mirrors::Sync {
+            query,
+            transport,
+            quota,
+            concurrency,
+        } => {
+            let options = MirrorOptions {
+                mirrors_dir,
+                transport,
+                quota,
+                concurrency: concurrency.into(),
+            };
+            let summary = mirror_service.sync(&query, &options).await?;
+
+            println!(
+                "Cloned {}, fetched {}, failed {} repositories",
+                summary.cloned, summary.fetched, summary.failed
+            );
+            if summary.over_quota > 0 {
+                println!("Skipped {} repositories over the quota", summary.over_quota);
+            }
+        }
+        Mirrors::Prune { quota } => {
+            let summary = mirror_service.prune(&mirrors_dir, quota).await?;
+            println!(
+                "Removed {} mirrors, freed {} bytes",
+                summary.removed, summary.freed
+            );
+        }
+        Mirrors::Status { json } => {
+            let data = mirror_service.status().await?;
+
+            if json || prefer_json {
+                println!("{}", serde_json::to_string(&data)?);
+            } else {
+                let mut table = Table::new(&data);
+                table.with(Style::modern());
+                println!("{table}");
+
+                let size: i64 = data.iter().map(|mirror| mirror.size).sum();
+                println!("{} mirrors, {size} bytes on disk", data.len());
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn deps_command(
+    codebase_repository: Box<dyn CodebaseRepository>,
+    mirror_repository: Box<dyn MirrorRepository>,
+    dependency_repository: Box<dyn DependencyRepository>,
+    deps: Dependencies,
+    prefer_json: bool,
+) -> Result<(), AppError> {
+    let dependency_service = DependencyService::new(
+        codebase_repository,
+        mirror_repository,
+        dependency_repository,
+    );
+
+    match deps {
+        Dependencies::Update { query, force } => {
+            let summary = dependency_service.update(&query, force).await?;
+            println!(
+                "Read {} dependencies from {} repositories, {} unchanged",
+                summary.dependencies, summary.scanned, summary.unchanged
+            );
+        }
+        Dependencies::Search {
+            package,
+            ecosystem,
+            json,
+        } => {
+            let data = dependency_service.search(&package, ecosystem).await?;
+
+            if json || prefer_json {
+                println!("{}", serde_json::to_string(&data)?);
+            } else {
+                let mut table = Table::new(&data);
+                table.with(Style::modern());
+                println!("{table}");
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn advisories_command(
+    advisory_repository: Box<dyn AdvisoryRepository>,
+    advisories: Advisories,
+) -> Result<(), AppError> {
+    let advisory_service = AdvisoryService::new(advisory_repository);
+
+    match advisories {
+        Advisories::Import { path } => {
+            let summary = advisory_service.import(&path).await?;
+            println!(
+                "Imported {} advisories, removed {} withdrawn, ignored {} of unsupported ecosystems",
+                summary.imported, summary.withdrawn, summary.ignored
+            );
+            if summary.invalid > 0 {
+                println!("Skipped {} invalid files", summary.invalid);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn export_command(
+    codebase_repository: Box<dyn CodebaseRepository>,
+    mirror_repository: Box<dyn MirrorRepository>,
+    dependency_repository: Box<dyn DependencyRepository>,
+    export: Exports,
+) -> Result<(), AppError> {
+    match export {
+        Exports::Sbom {
+            format,
+            repo,
+            group,
+        } => {
+            let scope = match (repo, group) {
+                (Some(path), _) => SbomScope::Repository(path),
+                (None, Some(group)) => SbomScope::Group(group.trim_end_matches('/').to_string()),
+                (None, None) => SbomScope::All,
+            };
+            let dependency_service = DependencyService::new(
+                codebase_repository,
+                mirror_repository,
+                dependency_repository,
+            );
+            let subjects = dependency_service.sbom(&scope).await?;
+
+            match format {
+                SbomFormat::CyclonedxJson => println!(
+                    "{}",
+                    serde_json::to_string(&to_cyclonedx(&scope, &subjects))?
+                ),
+                SbomFormat::SpdxJson => {
+                    println!("{}", serde_json::to_string(&to_spdx(&scope, &subjects))?)
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn config_command(profile: Option<&str>, config: Configs) -> Result<(), AppError> {
+    match config {
+        Configs::Init { force } => {
+            let path = init_config(force)?;
+            println!("Wrote {}", path.display());
+        }
+        Configs::Show => {
+            let settings = load_config()?.profile(profile)?;
+            let settings = toml::to_string(&settings)
+                .map_err(|err| AppError::InvalidConfig(err.to_string()))?;
+
+            println!("# {}", config_path()?.display());
+            print!("{settings}");
+        }
+        Configs::Set { key, value } => {
+            let path = set_config(profile, &key, &value)?;
+            println!("Updated {}", path.display());
+        }
+        Configs::SetSecret { name } => {
+            let path = store_secret(&name)?;
+            println!("Updated {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+async fn db_command(path: &Path, db: Databases, prefer_json: bool) -> Result<(), AppError> {
+    match db {
+        Databases::Info { json } => {
+            if !path.exists() {
+                return Err(AppError::NotFound(format!("database {}", path.display())));
+            }
+            let pool = open_db_read_only(path).await?;
+            let data = get_database_info(&pool, &std::path::absolute(path)?).await?;
+
+            if json || prefer_json {
+                println!("{}", serde_json::to_string(&data)?);
+            } else {
+                let mut table = Table::new(vec![&data]);
+                table.with(Style::modern());
+                table.with((Rotate::Left, Rotate::Top));
+                println!("{table}");
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn diff(
+    sync_repository: Box<dyn SyncRepository>,
+    since: Option<Since>,
+    min_growth: f64,
+    json: bool,
+) -> Result<(), AppError> {
+    let history_service = HistoryService::new(sync_repository);
+    let data = history_service.diff(since, min_growth).await?;
+
+    if json {
+        println!("{}", serde_json::to_string(&data)?);
+    } else {
+        let mut table = Table::new(&data);
+        table.with(Style::modern());
+        println!("{table}");
+    }
+    Ok(())
+}
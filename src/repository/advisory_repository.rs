@@ -0,0 +1,161 @@
+use async_trait::async_trait;
+use sqlx::{SqlitePool, types::Json};
+use time::OffsetDateTime;
+
+use crate::domain::{
+    advisory::{AffectedPackage, NewAdvisory, RangeEvent, Vulnerability},
+    dependency::Ecosystem,
+    policy::Severity,
+};
+
+#[async_trait]
+pub trait AdvisoryRepository {
+    /// Inserts advisories, replacing those already imported with the same id.
+    async fn save_advisories(&self, advisories: &[NewAdvisory]) -> Result<(), sqlx::Error>;
+    async fn delete_advisories(&self, ids: &[String]) -> Result<(), sqlx::Error>;
+    /// Dependency versions of every codebase that still exists matched
+    /// against the affected versions of the imported advisories.
+    async fn find_vulnerabilities(&self) -> Result<Vec<Vulnerability>, sqlx::Error>;
+}
+
+pub struct SqliteAdvisoryRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteAdvisoryRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl AdvisoryRepository for SqliteAdvisoryRepository {
+    async fn save_advisories(&self, advisories: &[NewAdvisory]) -> Result<(), sqlx::Error> {
+        let imported_at = OffsetDateTime::now_utc();
+        let mut tx = self.pool.begin().await?;
+
+        for advisory in advisories {
+            // Cascades to the affected packages
+            sqlx::query!(
+                r#"
+                DELETE FROM advisories WHERE id = ?
+                "#,
+                advisory.id
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            let aliases = Json(&advisory.aliases);
+            sqlx::query!(
+                r#"
+                INSERT INTO advisories (id, summary, aliases, severity, imported_at)
+                VALUES ( ?, ?, ?, ?, ? )
+                "#,
+                advisory.id,
+                advisory.summary,
+                aliases,
+                advisory.severity,
+                imported_at,
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            for package in &advisory.packages {
+                let ranges = Json(&package.ranges);
+                let versions = Json(&package.versions);
+                sqlx::query!(
+                    r#"
+                    INSERT INTO advisory_packages (advisory_id, ecosystem, name, ranges, versions)
+                    VALUES ( ?, ?, ?, ?, ? )
+                    "#,
+                    advisory.id,
+                    package.ecosystem,
+                    package.name,
+                    ranges,
+                    versions,
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        tx.commit().await
+    }
+
+    async fn delete_advisories(&self, ids: &[String]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        for id in ids {
+            sqlx::query!(
+                r#"
+                DELETE FROM advisories WHERE id = ?
+                "#,
+                id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await
+    }
+
+    async fn find_vulnerabilities(&self) -> Result<Vec<Vulnerability>, sqlx::Error> {
+        // Names are compared exactly, Go module paths and npm names are case
+        // sensitive. PyPI names are normalized on both sides.
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                c.path,
+                c.web_url,
+                d.ecosystem as "ecosystem: Ecosystem",
+                d.name,
+                d.version as "version!",
+                d.manifest,
+                a.id as "advisory_id!",
+                a.summary,
+                a.aliases as "aliases: Json<Vec<String>>",
+                a.severity as "severity: Severity",
+                p.ranges as "ranges: Json<Vec<Vec<RangeEvent>>>",
+                p.versions as "versions: Json<Vec<String>>"
+            FROM dependencies d
+            JOIN codebases c ON c.id = d.codebase_id
+            JOIN advisory_packages p ON p.name = d.name AND p.ecosystem = d.ecosystem
+            JOIN advisories a ON a.id = p.advisory_id
+            WHERE c.deleted_at IS NULL
+            AND d.version IS NOT NULL
+            ORDER BY c.path, d.manifest, d.name, d.version, a.id
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                let package = AffectedPackage {
+                    ecosystem: row.ecosystem,
+                    name: row.name,
+                    ranges: row.ranges.0,
+                    versions: row.versions.0,
+                };
+                if !package.affects(&row.version) {
+                    return None;
+                }
+
+                Some(Vulnerability {
+                    path: row.path,
+                    advisory_id: row.advisory_id,
+                    severity: row.severity,
+                    ecosystem: package.ecosystem,
+                    fixed: package.fixed_after(&row.version),
+                    name: package.name,
+                    version: row.version,
+                    manifest: row.manifest,
+                    aliases: row.aliases.0,
+                    summary: row.summary,
+                    web_url: row.web_url,
+                })
+            })
+            .collect())
+    }
+}
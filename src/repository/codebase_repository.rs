@@ -1,25 +1,32 @@
 use async_trait::async_trait;
-use sqlx::SqlitePool;
+use sqlx::{QueryBuilder, Sqlite, SqliteConnection, SqlitePool};
 
-use crate::domain::repository::{Codebase, CodebaseLanguage, NewCodebase, ProgrammingLanguage};
+use crate::domain::{
+    branch_protection::NewBranchRule,
+    repository::{Codebase, CodebaseRecord, NewCodebase, SearchResult, SecuritySettings},
+    search_query::{FilterKind, SearchQuery, Term},
+};
 
 #[async_trait]
 pub trait CodebaseRepository {
-    async fn save(&self, new_codebase: NewCodebase) -> Result<Codebase, sqlx::Error>;
-    async fn add_language(
+    /// Upserts every codebase with its languages and records a snapshot for
+    /// the run, all in a single transaction.
+    async fn save_batch(
         &self,
-        codebase: &Codebase,
-        lang: (&str, f64),
-    ) -> Result<CodebaseLanguage, sqlx::Error>;
-    async fn count(&self) -> Result<i64, sqlx::Error>;
+        run_id: i64,
+        records: Vec<CodebaseRecord>,
+    ) -> Result<(), sqlx::Error>;
     async fn find_all(&self) -> Result<Vec<Codebase>, sqlx::Error>;
-    async fn find_by_id(&self, id: i64) -> Result<Option<Codebase>, sqlx::Error>;
+    /// Languages of a codebase with their share in percent, largest first.
+    async fn find_languages(&self, codebase_id: i64) -> Result<Vec<(String, f64)>, sqlx::Error>;
     async fn search(
         &self,
-        query: &str,
+        query: &SearchQuery,
         include_archived: bool,
         limit: i64,
-    ) -> Result<Vec<Codebase>, sqlx::Error>;
+    ) -> Result<Vec<SearchResult>, sqlx::Error>;
+    /// Every codebase matching the query, archived ones included.
+    async fn find_matching(&self, query: &SearchQuery) -> Result<Vec<Codebase>, sqlx::Error>;
 }
 
 pub struct SqliteCodebaseRepository {
@@ -34,77 +41,27 @@ impl SqliteCodebaseRepository {
 
 #[async_trait]
 impl CodebaseRepository for SqliteCodebaseRepository {
-    async fn save(&self, new_codebase: NewCodebase) -> Result<Codebase, sqlx::Error> {
-        sqlx::query_as!(
-            Codebase,
-            r#"
-            INSERT INTO codebases
-            (
-                external_id,
-                source,
-                path,
-                description,
-                created_at,
-                updated_at,
-                pushed_at,
-                web_url,
-                private,
-                archived,
-                size,
-                commit_count
-            )
-            VALUES ( ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ? )
-            ON CONFLICT (external_id, source) DO UPDATE
-            SET
-                path = excluded.path,
-                source = excluded.source,
-                description = excluded.description,
-                created_at = excluded.created_at,
-                updated_at = excluded.updated_at,
-                pushed_at = excluded.pushed_at,
-                web_url = excluded.web_url,
-                private = excluded.private,
-                archived = excluded.archived,
-                size = excluded.size,
-                commit_count = excluded.commit_count
-            RETURNING
-                id,
-                external_id,
-                source,
-                path,
-                description,
-                created_at as "created_at: _",
-                updated_at as "updated_at: _",
-                pushed_at as "pushed_at: _",
-                web_url,
-                private,
-                archived,
-                size,
-                commit_count
-            "#,
-            new_codebase.external_id,
-            new_codebase.source,
-            new_codebase.path,
-            new_codebase.description,
-            new_codebase.created_at,
-            new_codebase.updated_at,
-            new_codebase.pushed_at,
-            new_codebase.web_url,
-            new_codebase.private,
-            new_codebase.archived,
-            new_codebase.size,
-            new_codebase.commit_count,
-        )
-        .fetch_one(&self.pool)
-        .await
-    }
+    async fn save_batch(
+        &self,
+        run_id: i64,
+        records: Vec<CodebaseRecord>,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
 
-    async fn count(&self) -> Result<i64, sqlx::Error> {
-        let row = sqlx::query!(r#"SELECT COUNT(id) as count FROM codebases"#)
-            .fetch_one(&self.pool)
-            .await?;
+        for record in &records {
+            let codebase = upsert_codebase(&mut tx, &record.codebase).await?;
+
+            for (name, percentage) in &record.languages {
+                upsert_language(&mut tx, codebase.id, name, *percentage).await?;
+            }
+
+            replace_branch_rules(&mut tx, codebase.id, record.branch_rules.as_deref()).await?;
+            save_security_settings(&mut tx, codebase.id, record.security_settings.as_ref()).await?;
 
-        Ok(row.count)
+            record_snapshot(&mut tx, run_id, codebase.id).await?;
+        }
+
+        tx.commit().await
     }
 
     async fn find_all(&self) -> Result<Vec<Codebase>, sqlx::Error> {
@@ -124,94 +81,53 @@ impl CodebaseRepository for SqliteCodebaseRepository {
                 private,
                 archived,
                 size,
-                commit_count
+                commit_count,
+                default_branch,
+                clone_url
             FROM codebases
+            WHERE deleted_at IS NULL
             "#
         )
         .fetch_all(&self.pool)
         .await
     }
 
-    async fn find_by_id(&self, id: i64) -> Result<Option<Codebase>, sqlx::Error> {
-        sqlx::query_as!(
-            Codebase,
-            r#"
-            SELECT
-                id,
-                external_id,
-                source,
-                path,
-                description,
-                created_at as "created_at: _",
-                updated_at as "updated_at: _",
-                pushed_at as "pushed_at: _",
-                web_url,
-                private,
-                archived,
-                size,
-                commit_count
-            FROM codebases WHERE id = ?
-            "#,
-            id
-        )
-        .fetch_optional(&self.pool)
-        .await
-    }
-
-    async fn add_language(
-        &self,
-        codebase: &Codebase,
-        lang: (&str, f64),
-    ) -> Result<CodebaseLanguage, sqlx::Error> {
-        let (name, percentage) = lang;
-
-        let mut tx = self.pool.begin().await?;
-
-        let language = sqlx::query_as!(
-            ProgrammingLanguage,
+    async fn find_languages(&self, codebase_id: i64) -> Result<Vec<(String, f64)>, sqlx::Error> {
+        let rows = sqlx::query!(
             r#"
-            INSERT INTO programming_languages (name)
-            VALUES (?)
-            ON CONFLICT (name)
-            DO UPDATE
-            SET
-                name = excluded.name
-            RETURNING id, name
+            SELECT l.name, cl.percentage
+            FROM codebase_languages cl
+            JOIN programming_languages l ON l.id = cl.language_id
+            WHERE cl.codebase_id = ?
+            ORDER BY cl.percentage DESC, l.name
             "#,
-            name
+            codebase_id
         )
-        .fetch_one(&mut *tx)
+        .fetch_all(&self.pool)
         .await?;
 
-        let codebase_language = sqlx::query_as!(
-            CodebaseLanguage,
-            r#"
-            INSERT INTO codebase_languages (codebase_id, language_id, percentage)
-            VALUES (?, ?, ?)
-            ON CONFLICT (codebase_id, language_id)
-            DO UPDATE SET percentage = excluded.percentage
-            RETURNING codebase_id, language_id, percentage
-            "#,
-            codebase.id,
-            language.id,
-            percentage,
-        )
-        .fetch_one(&mut *tx)
-        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.name, row.percentage))
+            .collect())
+    }
 
-        tx.commit().await?;
+    async fn find_matching(&self, query: &SearchQuery) -> Result<Vec<Codebase>, sqlx::Error> {
+        // A negative LIMIT lifts the limit in SQLite
+        let results = self.search(query, true, -1).await?;
 
-        Ok(codebase_language)
+        Ok(results.into_iter().map(|result| result.codebase).collect())
     }
 
     async fn search(
         &self,
-        query: &str,
+        query: &SearchQuery,
         include_archived: bool,
         limit: i64,
-    ) -> Result<Vec<Codebase>, sqlx::Error> {
-        let results: Vec<Codebase> = sqlx::query_as!(
-            Codebase,
+    ) -> Result<Vec<SearchResult>, sqlx::Error> {
+        let match_expression = fts_match_expression(query, false);
+
+        let mut builder = QueryBuilder::<Sqlite>::new(
             r#"
             SELECT
                 c.id,
@@ -219,28 +135,498 @@ impl CodebaseRepository for SqliteCodebaseRepository {
                 c.source,
                 c.path,
                 c.description,
-                c.created_at as "created_at: _",
-                c.updated_at as "updated_at: _",
-                c.pushed_at as "pushed_at: _",
+                c.created_at,
+                c.updated_at,
+                c.pushed_at,
                 c.web_url,
                 c.private,
                 c.archived,
                 c.size,
-                c.commit_count
-            FROM codebases c
-            JOIN codebases_fts ON codebases_fts.rowid = c.id
-            WHERE codebases_fts LIKE '%' + ? + '%'
-            AND (CASE WHEN ? THEN 1 ELSE c.archived = FALSE END)
-            ORDER BY bm25(codebases_fts)
-            LIMIT ?
+                c.commit_count,
+                c.default_branch,
+                c.clone_url,
             "#,
-            query,
-            include_archived,
-            limit,
+        );
+
+        // bm25() and snippet() are only available in the context of a MATCH
+        if match_expression.is_some() {
+            builder.push(
+                r#"
+                -bm25(codebases_fts) AS score,
+                snippet(codebases_fts, -1, '[', ']', '…', 48) AS snippet
+                "#,
+            );
+        } else {
+            builder.push(" NULL AS score, NULL AS snippet");
+        }
+
+        builder.push(" FROM codebases c");
+
+        if !query.terms.is_empty() {
+            builder.push(" JOIN codebases_fts ON codebases_fts.rowid = c.id");
+        }
+
+        builder.push(" WHERE c.deleted_at IS NULL");
+
+        if let Some(expression) = &match_expression {
+            builder.push(" AND codebases_fts MATCH ");
+            builder.push_bind(expression.clone());
+        }
+
+        if let Some(expression) = fts_match_expression(query, true) {
+            builder.push(
+                " AND c.id NOT IN (SELECT rowid FROM codebases_fts WHERE codebases_fts MATCH ",
+            );
+            builder.push_bind(expression);
+            builder.push(")");
+        }
+
+        push_short_terms(&mut builder, query);
+        push_filters(&mut builder, query);
+
+        if !include_archived && !query.has_archived_filter() {
+            builder.push(" AND c.archived = FALSE");
+        }
+
+        if match_expression.is_some() {
+            builder.push(" ORDER BY bm25(codebases_fts)");
+        } else {
+            builder.push(" ORDER BY c.path");
+        }
+
+        builder.push(" LIMIT ");
+        builder.push_bind(limit);
+
+        builder
+            .build_query_as::<SearchResult>()
+            .fetch_all(&self.pool)
+            .await
+    }
+}
+
+async fn upsert_codebase(
+    conn: &mut SqliteConnection,
+    new_codebase: &NewCodebase,
+) -> Result<Codebase, sqlx::Error> {
+    sqlx::query_as!(
+        Codebase,
+        r#"
+        INSERT INTO codebases
+        (
+            external_id,
+            source,
+            path,
+            description,
+            created_at,
+            updated_at,
+            pushed_at,
+            web_url,
+            private,
+            archived,
+            size,
+            commit_count,
+            default_branch,
+            clone_url
         )
-        .fetch_all(&self.pool)
+        VALUES ( ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ? )
+        ON CONFLICT (external_id, source) DO UPDATE
+        SET
+            path = excluded.path,
+            source = excluded.source,
+            description = excluded.description,
+            created_at = excluded.created_at,
+            updated_at = excluded.updated_at,
+            pushed_at = excluded.pushed_at,
+            web_url = excluded.web_url,
+            private = excluded.private,
+            archived = excluded.archived,
+            size = excluded.size,
+            commit_count = excluded.commit_count,
+            default_branch = excluded.default_branch,
+            clone_url = excluded.clone_url,
+            deleted_at = NULL
+        RETURNING
+            id,
+            external_id,
+            source,
+            path,
+            description,
+            created_at as "created_at: _",
+            updated_at as "updated_at: _",
+            pushed_at as "pushed_at: _",
+            web_url,
+            private,
+            archived,
+            size,
+            commit_count,
+            default_branch,
+            clone_url
+        "#,
+        new_codebase.external_id,
+        new_codebase.source,
+        new_codebase.path,
+        new_codebase.description,
+        new_codebase.created_at,
+        new_codebase.updated_at,
+        new_codebase.pushed_at,
+        new_codebase.web_url,
+        new_codebase.private,
+        new_codebase.archived,
+        new_codebase.size,
+        new_codebase.commit_count,
+        new_codebase.default_branch,
+        new_codebase.clone_url,
+    )
+    .fetch_one(&mut *conn)
+    .await
+}
+
+async fn upsert_language(
+    conn: &mut SqliteConnection,
+    codebase_id: i64,
+    name: &str,
+    percentage: f64,
+) -> Result<(), sqlx::Error> {
+    let language_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO programming_languages (name)
+        VALUES (?)
+        ON CONFLICT (name)
+        DO UPDATE
+        SET
+            name = excluded.name
+        RETURNING id
+        "#,
+        name
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO codebase_languages (codebase_id, language_id, percentage)
+        VALUES (?, ?, ?)
+        ON CONFLICT (codebase_id, language_id)
+        DO UPDATE SET percentage = excluded.percentage
+        "#,
+        codebase_id,
+        language_id,
+        percentage,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Replaces the branch rules of the codebase and records whether they are
+/// known, `None` meaning they could not be read.
+async fn replace_branch_rules(
+    conn: &mut SqliteConnection,
+    codebase_id: i64,
+    branch_rules: Option<&[NewBranchRule]>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM branch_rules WHERE codebase_id = ?
+        "#,
+        codebase_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let known = branch_rules.is_some();
+    sqlx::query!(
+        r#"
+        UPDATE codebases SET branch_rules_known = ? WHERE id = ?
+        "#,
+        known,
+        codebase_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    for rule in branch_rules.unwrap_or_default() {
+        let branch_rule_id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO branch_rules
+            (
+                codebase_id,
+                name,
+                is_default,
+                is_protected,
+                allow_force_push,
+                code_owner_approval_required
+            )
+            VALUES ( ?, ?, ?, ?, ?, ? )
+            RETURNING id
+            "#,
+            codebase_id,
+            rule.name,
+            rule.is_default,
+            rule.is_protected,
+            rule.allow_force_push,
+            rule.code_owner_approval_required,
+        )
+        .fetch_one(&mut *conn)
         .await?;
 
-        Ok(results)
+        let access_levels = rule
+            .push_access_levels
+            .iter()
+            .map(|level| ("push", level))
+            .chain(
+                rule.merge_access_levels
+                    .iter()
+                    .map(|level| ("merge", level)),
+            );
+
+        for (action, access_level) in access_levels {
+            sqlx::query!(
+                r#"
+                INSERT INTO branch_rule_access_levels
+                (branch_rule_id, action, access_level, description)
+                VALUES ( ?, ?, ?, ? )
+                "#,
+                branch_rule_id,
+                action,
+                access_level.level,
+                access_level.description,
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
     }
+
+    Ok(())
+}
+
+async fn save_security_settings(
+    conn: &mut SqliteConnection,
+    codebase_id: i64,
+    settings: Option<&SecuritySettings>,
+) -> Result<(), sqlx::Error> {
+    let Some(settings) = settings else {
+        sqlx::query!(
+            r#"
+            DELETE FROM codebase_security_settings WHERE codebase_id = ?
+            "#,
+            codebase_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        return Ok(());
+    };
+
+    sqlx::query!(
+        r#"
+        INSERT INTO codebase_security_settings
+        (
+            codebase_id,
+            approvals_required,
+            secret_push_protection,
+            dependency_scanning,
+            pipeline_must_succeed
+        )
+        VALUES ( ?, ?, ?, ?, ? )
+        ON CONFLICT (codebase_id) DO UPDATE
+        SET
+            approvals_required = excluded.approvals_required,
+            secret_push_protection = excluded.secret_push_protection,
+            dependency_scanning = excluded.dependency_scanning,
+            pipeline_must_succeed = excluded.pipeline_must_succeed
+        "#,
+        codebase_id,
+        settings.approvals_required,
+        settings.secret_push_protection,
+        settings.dependency_scanning,
+        settings.pipeline_must_succeed,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Records the current state of the codebase, languages included, for the run.
+async fn record_snapshot(
+    conn: &mut SqliteConnection,
+    run_id: i64,
+    codebase_id: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO codebase_snapshots
+        (
+            run_id,
+            codebase_id,
+            path,
+            pushed_at,
+            private,
+            archived,
+            size,
+            commit_count,
+            languages
+        )
+        SELECT
+            ?,
+            c.id,
+            c.path,
+            c.pushed_at,
+            c.private,
+            c.archived,
+            c.size,
+            c.commit_count,
+            IFNULL((
+                SELECT json_group_object(l.name, cl.percentage)
+                FROM codebase_languages cl
+                JOIN programming_languages l ON cl.language_id = l.id
+                WHERE cl.codebase_id = c.id
+            ), '{}')
+        FROM codebases c
+        WHERE c.id = ?
+        ON CONFLICT (run_id, codebase_id) DO UPDATE
+        SET
+            path = excluded.path,
+            pushed_at = excluded.pushed_at,
+            private = excluded.private,
+            archived = excluded.archived,
+            size = excluded.size,
+            commit_count = excluded.commit_count,
+            languages = excluded.languages
+        "#,
+        run_id,
+        codebase_id,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// The trigram tokenizer can't match terms shorter than three characters,
+/// those are matched with LIKE against the indexed columns instead.
+const MIN_TRIGRAM_TERM_LENGTH: usize = 3;
+
+fn is_short_term(term: &Term) -> bool {
+    term.text.chars().count() < MIN_TRIGRAM_TERM_LENGTH
+}
+
+/// Builds an FTS5 MATCH expression from the (negated or plain) free-text terms,
+/// quoting each term so that user input is never interpreted as FTS syntax.
+fn fts_match_expression(query: &SearchQuery, negated: bool) -> Option<String> {
+    let terms: Vec<String> = query
+        .terms
+        .iter()
+        .filter(|term| term.negated == negated && !is_short_term(term))
+        .map(|term| format!("\"{}\"", term.text.replace('"', "\"\"")))
+        .collect();
+
+    if terms.is_empty() {
+        return None;
+    }
+
+    // Negated terms are excluded if any of them match, plain terms must all match
+    Some(terms.join(if negated { " OR " } else { " " }))
+}
+
+fn push_short_terms(builder: &mut QueryBuilder<'_, Sqlite>, query: &SearchQuery) {
+    for term in query.terms.iter().filter(|term| is_short_term(term)) {
+        let pattern = format!(
+            "%{}%",
+            term.text
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+
+        builder.push(if term.negated { " AND NOT (" } else { " AND (" });
+
+        let mut columns = builder.separated(" OR ");
+        for column in ["path", "description", "languages"] {
+            columns.push(format!("IFNULL(codebases_fts.{column}, '') LIKE "));
+            columns.push_bind_unseparated(pattern.clone());
+            columns.push_unseparated(" ESCAPE '\\'");
+        }
+
+        builder.push(")");
+    }
+}
+
+fn push_filters(builder: &mut QueryBuilder<'_, Sqlite>, query: &SearchQuery) {
+    for filter in &query.filters {
+        builder.push(if filter.negated {
+            " AND NOT ("
+        } else {
+            " AND ("
+        });
+
+        match &filter.kind {
+            FilterKind::Language(name) => {
+                builder.push(
+                    r#"EXISTS (
+                        SELECT 1 FROM codebase_languages cl
+                        JOIN programming_languages l ON l.id = cl.language_id
+                        WHERE cl.codebase_id = c.id AND l.name = "#,
+                );
+                builder.push_bind(name.clone());
+                builder.push(" COLLATE NOCASE)");
+            }
+            FilterKind::Size(comparison, size) => {
+                builder.push(format!("c.size {} ", comparison.as_sql()));
+                builder.push_bind(*size);
+            }
+            FilterKind::Pushed(comparison, datetime) => {
+                builder.push(format!(
+                    "julianday(c.pushed_at) {} julianday(",
+                    comparison.as_sql()
+                ));
+                builder.push_bind(*datetime);
+                builder.push(")");
+            }
+            FilterKind::Private(private) => {
+                builder.push("c.private = ");
+                builder.push_bind(*private);
+            }
+            FilterKind::Archived(archived) => {
+                builder.push("c.archived = ");
+                builder.push_bind(*archived);
+            }
+            FilterKind::Approvals(comparison, approvals) => {
+                builder.push(format!(
+                    "EXISTS (SELECT 1 FROM codebase_security_settings s WHERE s.codebase_id = c.id AND s.approvals_required {} ",
+                    comparison.as_sql()
+                ));
+                builder.push_bind(*approvals);
+                builder.push(")");
+            }
+            FilterKind::SecretPushProtection(enabled) => {
+                push_security_setting(builder, "secret_push_protection", *enabled);
+            }
+            FilterKind::DependencyScanning(enabled) => {
+                push_security_setting(builder, "dependency_scanning", *enabled);
+            }
+            FilterKind::PipelineRequired(required) => {
+                push_security_setting(builder, "pipeline_must_succeed", *required);
+            }
+            FilterKind::Path(pattern) => {
+                builder.push("c.path GLOB ");
+                builder.push_bind(pattern.clone());
+                builder.push(" OR c.path GLOB '*/' || ");
+                builder.push_bind(pattern.clone());
+            }
+        }
+
+        builder.push(")");
+    }
+}
+
+/// Unknown settings never match, but negated filters do include them.
+fn push_security_setting(builder: &mut QueryBuilder<'_, Sqlite>, column: &str, value: bool) {
+    builder.push(format!(
+        "EXISTS (SELECT 1 FROM codebase_security_settings s WHERE s.codebase_id = c.id AND s.{column} = "
+    ));
+    builder.push_bind(value);
+    builder.push(")");
 }
@@ -0,0 +1,117 @@
+use async_trait::async_trait;
+use sqlx::{SqlitePool, types::Json};
+use time::{Date, OffsetDateTime};
+
+use crate::domain::credential::{Credential, CredentialKind, CredentialOwner, NewCredential};
+
+#[async_trait]
+pub trait CredentialRepository {
+    /// Replaces the credentials of a project or group.
+    async fn replace_credentials(
+        &self,
+        owner: &CredentialOwner,
+        credentials: &[NewCredential],
+    ) -> Result<(), sqlx::Error>;
+    /// Credentials of groups and of codebases that weren't deleted, ordered by
+    /// owner, kind and name.
+    async fn find_all(&self) -> Result<Vec<Credential>, sqlx::Error>;
+}
+
+pub struct SqliteCredentialRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteCredentialRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl CredentialRepository for SqliteCredentialRepository {
+    async fn replace_credentials(
+        &self,
+        owner: &CredentialOwner,
+        credentials: &[NewCredential],
+    ) -> Result<(), sqlx::Error> {
+        let collected_at = OffsetDateTime::now_utc();
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM credentials
+            WHERE source = ? AND owner_path = ? AND codebase_id IS ?
+            "#,
+            owner.source,
+            owner.path,
+            owner.codebase_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for credential in credentials {
+            let scopes = Json(&credential.scopes);
+            sqlx::query!(
+                r#"
+                INSERT INTO credentials (
+                    source, owner_path, web_url, codebase_id, kind, external_id, name,
+                    scopes, created_at, expires_at, last_used_at, collected_at
+                )
+                VALUES ( ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ? )
+                "#,
+                owner.source,
+                owner.path,
+                owner.web_url,
+                owner.codebase_id,
+                credential.kind,
+                credential.external_id,
+                credential.name,
+                scopes,
+                credential.created_at,
+                credential.expires_at,
+                credential.last_used_at,
+                collected_at,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await
+    }
+
+    async fn find_all(&self) -> Result<Vec<Credential>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                cr.owner_path,
+                cr.web_url,
+                cr.kind as "kind: CredentialKind",
+                cr.name,
+                cr.scopes as "scopes: Json<Vec<String>>",
+                cr.created_at as "created_at: OffsetDateTime",
+                cr.expires_at as "expires_at: Date",
+                cr.last_used_at as "last_used_at: OffsetDateTime"
+            FROM credentials cr
+            LEFT JOIN codebases c ON c.id = cr.codebase_id
+            WHERE cr.codebase_id IS NULL OR c.deleted_at IS NULL
+            ORDER BY cr.owner_path, cr.kind, cr.name
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Credential {
+                owner_path: row.owner_path,
+                web_url: row.web_url,
+                kind: row.kind,
+                name: row.name,
+                scopes: row.scopes.0,
+                created_at: row.created_at,
+                expires_at: row.expires_at,
+                last_used_at: row.last_used_at,
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,165 @@
+use async_trait::async_trait;
+use sqlx::{QueryBuilder, Sqlite, SqlitePool};
+use time::OffsetDateTime;
+
+use crate::domain::dependency::{DependencyUsage, Ecosystem, NewDependency};
+
+#[async_trait]
+pub trait DependencyRepository {
+    /// Replaces the dependencies of a codebase with those found at `head_commit`.
+    async fn replace_dependencies(
+        &self,
+        codebase_id: i64,
+        head_commit: &str,
+        dependencies: &[NewDependency],
+    ) -> Result<(), sqlx::Error>;
+    /// Dependencies of a codebase, ordered by ecosystem, name and version.
+    async fn find_by_codebase(&self, codebase_id: i64) -> Result<Vec<NewDependency>, sqlx::Error>;
+    /// Commit the dependencies of a codebase were last read at.
+    async fn find_scanned_commit(&self, codebase_id: i64) -> Result<Option<String>, sqlx::Error>;
+    /// Repositories using a package, any version unless one is given. Names
+    /// match case-insensitively.
+    async fn find_usages(
+        &self,
+        name: &str,
+        version: Option<&str>,
+        ecosystem: Option<Ecosystem>,
+    ) -> Result<Vec<DependencyUsage>, sqlx::Error>;
+}
+
+pub struct SqliteDependencyRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteDependencyRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl DependencyRepository for SqliteDependencyRepository {
+    async fn replace_dependencies(
+        &self,
+        codebase_id: i64,
+        head_commit: &str,
+        dependencies: &[NewDependency],
+    ) -> Result<(), sqlx::Error> {
+        let scanned_at = OffsetDateTime::now_utc();
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM dependencies WHERE codebase_id = ?
+            "#,
+            codebase_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for dependency in dependencies {
+            sqlx::query!(
+                r#"
+                INSERT INTO dependencies (codebase_id, ecosystem, name, version, manifest)
+                VALUES ( ?, ?, ?, ?, ? )
+                "#,
+                codebase_id,
+                dependency.ecosystem,
+                dependency.name,
+                dependency.version,
+                dependency.manifest,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query!(
+            r#"
+            INSERT INTO dependency_scans (codebase_id, head_commit, scanned_at)
+            VALUES ( ?, ?, ? )
+            ON CONFLICT (codebase_id) DO UPDATE
+            SET
+                head_commit = excluded.head_commit,
+                scanned_at = excluded.scanned_at
+            "#,
+            codebase_id,
+            head_commit,
+            scanned_at,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await
+    }
+
+    async fn find_by_codebase(&self, codebase_id: i64) -> Result<Vec<NewDependency>, sqlx::Error> {
+        sqlx::query_as!(
+            NewDependency,
+            r#"
+            SELECT
+                ecosystem as "ecosystem: Ecosystem",
+                name,
+                version,
+                manifest
+            FROM dependencies
+            WHERE codebase_id = ?
+            ORDER BY ecosystem, name, version, manifest
+            "#,
+            codebase_id
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn find_scanned_commit(&self, codebase_id: i64) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT head_commit FROM dependency_scans WHERE codebase_id = ?
+            "#,
+            codebase_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn find_usages(
+        &self,
+        name: &str,
+        version: Option<&str>,
+        ecosystem: Option<Ecosystem>,
+    ) -> Result<Vec<DependencyUsage>, sqlx::Error> {
+        let mut builder = QueryBuilder::<Sqlite>::new(
+            r#"
+            SELECT
+                c.path,
+                d.ecosystem,
+                d.name,
+                d.version,
+                d.manifest,
+                c.web_url
+            FROM dependencies d
+            JOIN codebases c ON c.id = d.codebase_id
+            WHERE c.deleted_at IS NULL
+            AND d.name = "#,
+        );
+        builder.push_bind(name.to_string());
+        builder.push(" COLLATE NOCASE");
+
+        if let Some(version) = version {
+            builder.push(" AND d.version = ");
+            builder.push_bind(version.to_string());
+        }
+
+        if let Some(ecosystem) = ecosystem {
+            builder.push(" AND d.ecosystem = ");
+            builder.push_bind(ecosystem);
+        }
+
+        builder.push(" ORDER BY c.path, d.manifest, d.version");
+
+        builder
+            .build_query_as::<DependencyUsage>()
+            .fetch_all(&self.pool)
+            .await
+    }
+}
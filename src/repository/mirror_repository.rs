@@ -0,0 +1,88 @@
+use async_trait::async_trait;
+use sqlx::SqlitePool;
+use time::OffsetDateTime;
+
+use crate::domain::mirror::{Mirror, NewMirror};
+
+#[async_trait]
+pub trait MirrorRepository {
+    /// Records a fetch of a codebase's mirror.
+    async fn save(&self, mirror: &NewMirror) -> Result<(), sqlx::Error>;
+    async fn delete(&self, codebase_id: i64) -> Result<(), sqlx::Error>;
+    /// Every mirror, most recently pushed repositories first.
+    async fn find_all(&self) -> Result<Vec<Mirror>, sqlx::Error>;
+}
+
+pub struct SqliteMirrorRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteMirrorRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl MirrorRepository for SqliteMirrorRepository {
+    async fn save(&self, mirror: &NewMirror) -> Result<(), sqlx::Error> {
+        let fetched_at = OffsetDateTime::now_utc();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO mirrors (codebase_id, dir, head_commit, size, fetched_at)
+            VALUES ( ?, ?, ?, ?, ? )
+            ON CONFLICT (codebase_id) DO UPDATE
+            SET
+                dir = excluded.dir,
+                head_commit = excluded.head_commit,
+                size = excluded.size,
+                fetched_at = excluded.fetched_at
+            "#,
+            mirror.codebase_id,
+            mirror.dir,
+            mirror.head_commit,
+            mirror.size,
+            fetched_at,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, codebase_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            DELETE FROM mirrors WHERE codebase_id = ?
+            "#,
+            codebase_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_all(&self) -> Result<Vec<Mirror>, sqlx::Error> {
+        sqlx::query_as!(
+            Mirror,
+            r#"
+            SELECT
+                m.codebase_id,
+                c.path,
+                m.head_commit,
+                m.size,
+                m.fetched_at as "fetched_at: _",
+                c.deleted_at IS NOT NULL as "deleted!: bool",
+                m.dir,
+                c.pushed_at as "pushed_at: _"
+            FROM mirrors m
+            JOIN codebases c ON c.id = m.codebase_id
+            ORDER BY c.pushed_at DESC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+}
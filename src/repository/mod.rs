@@ -1 +1,7 @@
+pub mod advisory_repository;
 pub mod codebase_repository;
+pub mod credential_repository;
+pub mod dependency_repository;
+pub mod mirror_repository;
+pub mod secret_repository;
+pub mod sync_repository;
@@ -0,0 +1,132 @@
+use async_trait::async_trait;
+use sqlx::SqlitePool;
+use time::OffsetDateTime;
+
+use crate::domain::{
+    policy::Severity,
+    secret::{SecretFinding, blob_url, find_rule},
+};
+
+#[async_trait]
+pub trait SecretRepository {
+    /// Replaces the findings of a codebase with those of its latest scan.
+    async fn replace_findings(
+        &self,
+        codebase_id: i64,
+        findings: &[SecretFinding],
+    ) -> Result<(), sqlx::Error>;
+    /// Findings of the latest scan of every codebase that still exists.
+    async fn find_all(&self) -> Result<Vec<SecretFinding>, sqlx::Error>;
+}
+
+pub struct SqliteSecretRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteSecretRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl SecretRepository for SqliteSecretRepository {
+    async fn replace_findings(
+        &self,
+        codebase_id: i64,
+        findings: &[SecretFinding],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM secret_findings WHERE codebase_id = ?
+            "#,
+            codebase_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for finding in findings {
+            sqlx::query!(
+                r#"
+                INSERT INTO secret_findings
+                (
+                    codebase_id,
+                    rule_id,
+                    file,
+                    line,
+                    commit_sha,
+                    secret,
+                    fingerprint,
+                    scanned_at
+                )
+                VALUES ( ?, ?, ?, ?, ?, ?, ?, ? )
+                "#,
+                codebase_id,
+                finding.rule_id,
+                finding.file,
+                finding.line,
+                finding.commit_sha,
+                finding.secret,
+                finding.fingerprint,
+                finding.scanned_at,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await
+    }
+
+    async fn find_all(&self) -> Result<Vec<SecretFinding>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                c.path,
+                c.source,
+                c.web_url,
+                c.default_branch,
+                f.rule_id,
+                f.file,
+                f.line,
+                f.commit_sha,
+                f.secret,
+                f.fingerprint,
+                f.scanned_at as "scanned_at: OffsetDateTime"
+            FROM secret_findings f
+            JOIN codebases c ON c.id = f.codebase_id
+            WHERE c.deleted_at IS NULL
+            ORDER BY c.path, f.file, f.line
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let rev = row
+                    .commit_sha
+                    .as_deref()
+                    .or(row.default_branch.as_deref())
+                    .unwrap_or("HEAD");
+
+                SecretFinding {
+                    web_url: blob_url(&row.source, &row.web_url, rev, &row.file, row.line),
+                    path: row.path,
+                    severity: find_rule(&row.rule_id)
+                        .map(|rule| rule.severity)
+                        .unwrap_or(Severity::Medium),
+                    rule_id: row.rule_id,
+                    file: row.file,
+                    line: row.line,
+                    commit_sha: row.commit_sha,
+                    secret: row.secret,
+                    fingerprint: row.fingerprint,
+                    scanned_at: row.scanned_at,
+                }
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,375 @@
+use async_trait::async_trait;
+use sqlx::SqlitePool;
+use time::OffsetDateTime;
+
+use crate::domain::sync::{CodebaseSnapshot, SyncKind, SyncRun, SyncStatus};
+
+#[async_trait]
+pub trait SyncRepository {
+    async fn start_run(
+        &self,
+        source: &str,
+        scope: &str,
+        kind: SyncKind,
+    ) -> Result<SyncRun, sqlx::Error>;
+    async fn finish_run(&self, run_id: i64, status: SyncStatus) -> Result<(), sqlx::Error>;
+    async fn save_cursor(&self, run_id: i64, end_cursor: &str) -> Result<(), sqlx::Error>;
+    async fn find_resumable_run(
+        &self,
+        source: &str,
+        scope: &str,
+    ) -> Result<Option<SyncRun>, sqlx::Error>;
+    async fn reopen_run(&self, run_id: i64) -> Result<(), sqlx::Error>;
+    async fn count_snapshots(&self, run_id: i64) -> Result<i64, sqlx::Error>;
+    async fn save_watermark(&self, run_id: i64) -> Result<(), sqlx::Error>;
+    async fn find_watermark(
+        &self,
+        source: &str,
+        scope: &str,
+    ) -> Result<Option<OffsetDateTime>, sqlx::Error>;
+    async fn mark_unseen_deleted(&self, run: &SyncRun) -> Result<u64, sqlx::Error>;
+    async fn prune_deleted(&self, run: &SyncRun) -> Result<u64, sqlx::Error>;
+    async fn find_run(&self, id: i64) -> Result<Option<SyncRun>, sqlx::Error>;
+    async fn find_completed_runs(&self) -> Result<Vec<SyncRun>, sqlx::Error>;
+    async fn find_snapshots_as_of(
+        &self,
+        run: &SyncRun,
+    ) -> Result<Vec<CodebaseSnapshot>, sqlx::Error>;
+}
+
+pub struct SqliteSyncRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteSyncRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl SyncRepository for SqliteSyncRepository {
+    async fn start_run(
+        &self,
+        source: &str,
+        scope: &str,
+        kind: SyncKind,
+    ) -> Result<SyncRun, sqlx::Error> {
+        let started_at = OffsetDateTime::now_utc();
+
+        sqlx::query_as!(
+            SyncRun,
+            r#"
+            INSERT INTO sync_runs (source, scope, started_at, status, kind)
+            VALUES (?, ?, ?, 'running', ?)
+            RETURNING
+                id,
+                source,
+                scope,
+                started_at as "started_at: _",
+                kind as "kind: _",
+                end_cursor
+            "#,
+            source,
+            scope,
+            started_at,
+            kind,
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn finish_run(&self, run_id: i64, status: SyncStatus) -> Result<(), sqlx::Error> {
+        let finished_at = OffsetDateTime::now_utc();
+
+        sqlx::query!(
+            r#"
+            UPDATE sync_runs SET finished_at = ?, status = ? WHERE id = ?
+            "#,
+            finished_at,
+            status,
+            run_id,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn save_cursor(&self, run_id: i64, end_cursor: &str) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE sync_runs SET end_cursor = ? WHERE id = ?
+            "#,
+            end_cursor,
+            run_id,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_resumable_run(
+        &self,
+        source: &str,
+        scope: &str,
+    ) -> Result<Option<SyncRun>, sqlx::Error> {
+        // Only the latest run of the scope can be resumed, and only if it
+        // failed or was interrupted
+        sqlx::query_as!(
+            SyncRun,
+            r#"
+            SELECT
+                id,
+                source,
+                scope,
+                started_at as "started_at: _",
+                kind as "kind: _",
+                end_cursor
+            FROM sync_runs
+            WHERE id = (SELECT MAX(id) FROM sync_runs WHERE source = ? AND scope = ?)
+            AND status != 'completed'
+            "#,
+            source,
+            scope,
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn reopen_run(&self, run_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE sync_runs SET finished_at = NULL, status = 'running' WHERE id = ?
+            "#,
+            run_id,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn count_snapshots(&self, run_id: i64) -> Result<i64, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"SELECT COUNT(*) as count FROM codebase_snapshots WHERE run_id = ?"#,
+            run_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.count)
+    }
+
+    async fn save_watermark(&self, run_id: i64) -> Result<(), sqlx::Error> {
+        // Push and update timestamps come from the upstream API, which keeps
+        // the watermark independent of the local clock
+        sqlx::query!(
+            r#"
+            UPDATE sync_runs
+            SET watermark = (
+                SELECT MAX(MAX(c.updated_at), MAX(s.pushed_at))
+                FROM codebase_snapshots s
+                JOIN codebases c ON c.id = s.codebase_id
+                WHERE s.run_id = ? AND s.deleted = FALSE
+            )
+            WHERE id = ?
+            "#,
+            run_id,
+            run_id,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_watermark(
+        &self,
+        source: &str,
+        scope: &str,
+    ) -> Result<Option<OffsetDateTime>, sqlx::Error> {
+        // Incremental runs build on a complete listing, so there is no
+        // watermark before the first full run
+        let row = sqlx::query!(
+            r#"
+            SELECT MAX(watermark) as "watermark: OffsetDateTime"
+            FROM sync_runs
+            WHERE source = ? AND scope = ? AND status = 'completed'
+            HAVING SUM(kind = 'full') > 0
+            "#,
+            source,
+            scope,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.and_then(|row| row.watermark))
+    }
+
+    async fn mark_unseen_deleted(&self, run: &SyncRun) -> Result<u64, sqlx::Error> {
+        let deleted_at = OffsetDateTime::now_utc();
+
+        let mut tx = self.pool.begin().await?;
+
+        // Record a tombstone for every live codebase in the run's scope that
+        // the run did not see, then flag exactly those codebases
+        sqlx::query!(
+            r#"
+            INSERT INTO codebase_snapshots
+            (
+                run_id,
+                codebase_id,
+                path,
+                pushed_at,
+                private,
+                archived,
+                size,
+                commit_count,
+                deleted
+            )
+            SELECT
+                ?,
+                c.id,
+                c.path,
+                c.pushed_at,
+                c.private,
+                c.archived,
+                c.size,
+                c.commit_count,
+                TRUE
+            FROM codebases c
+            WHERE c.source = ?
+            AND c.deleted_at IS NULL
+            AND (
+                LOWER(c.path) = LOWER(?)
+                OR LOWER(SUBSTR(c.path, 1, LENGTH(?) + 1)) = LOWER(?) || '/'
+            )
+            AND c.id NOT IN (SELECT codebase_id FROM codebase_snapshots WHERE run_id = ?)
+            "#,
+            run.id,
+            run.source,
+            run.scope,
+            run.scope,
+            run.scope,
+            run.id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE codebases SET deleted_at = ?
+            WHERE id IN (
+                SELECT codebase_id FROM codebase_snapshots WHERE run_id = ? AND deleted = TRUE
+            )
+            "#,
+            deleted_at,
+            run.id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn prune_deleted(&self, run: &SyncRun) -> Result<u64, sqlx::Error> {
+        // Their snapshots, tombstones included, are kept for `diff`
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM codebases
+            WHERE source = ?
+            AND deleted_at IS NOT NULL
+            AND (
+                LOWER(path) = LOWER(?)
+                OR LOWER(SUBSTR(path, 1, LENGTH(?) + 1)) = LOWER(?) || '/'
+            )
+            "#,
+            run.source,
+            run.scope,
+            run.scope,
+            run.scope,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn find_run(&self, id: i64) -> Result<Option<SyncRun>, sqlx::Error> {
+        sqlx::query_as!(
+            SyncRun,
+            r#"
+            SELECT
+                id,
+                source,
+                scope,
+                started_at as "started_at: _",
+                kind as "kind: _",
+                end_cursor
+            FROM sync_runs WHERE id = ?
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn find_completed_runs(&self) -> Result<Vec<SyncRun>, sqlx::Error> {
+        sqlx::query_as!(
+            SyncRun,
+            r#"
+            SELECT
+                id,
+                source,
+                scope,
+                started_at as "started_at: _",
+                kind as "kind: _",
+                end_cursor
+            FROM sync_runs
+            WHERE status = 'completed'
+            ORDER BY id
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn find_snapshots_as_of(
+        &self,
+        run: &SyncRun,
+    ) -> Result<Vec<CodebaseSnapshot>, sqlx::Error> {
+        // Latest snapshot of every codebase of the run's source and scope,
+        // taken by this run or any earlier one
+        sqlx::query_as!(
+            CodebaseSnapshot,
+            r#"
+            SELECT
+                codebase_id as "codebase_id!",
+                path as "path!",
+                private as "private!: bool",
+                archived as "archived!: bool",
+                size as "size!",
+                deleted as "deleted!: bool"
+            FROM (
+                SELECT
+                    s.*,
+                    ROW_NUMBER() OVER (PARTITION BY s.codebase_id ORDER BY s.run_id DESC) AS rn
+                FROM codebase_snapshots s
+                JOIN sync_runs r ON r.id = s.run_id
+                WHERE r.source = ? AND r.scope = ? AND s.run_id <= ?
+            )
+            WHERE rn = 1
+            ORDER BY path
+            "#,
+            run.source,
+            run.scope,
+            run.id,
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+}
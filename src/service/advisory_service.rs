@@ -0,0 +1,109 @@
+use std::{fs::File, io::Read, path::Path};
+
+use crate::{
+    domain::advisory::{AdvisoryImportSummary, OsvAdvisory, Vulnerability},
+    error::AppError,
+    repository::advisory_repository::AdvisoryRepository,
+};
+
+pub struct AdvisoryService {
+    advisory_repository: Box<dyn AdvisoryRepository>,
+}
+
+impl AdvisoryService {
+    pub fn new(advisory_repository: Box<dyn AdvisoryRepository>) -> Self {
+        Self {
+            advisory_repository,
+        }
+    }
+
+    /// Imports the OSV advisories of a directory, e.g. an extracted dump, or
+    /// of a zip file like the `all.zip` osv.dev publishes per ecosystem.
+    /// Advisories imported before are replaced, withdrawn ones removed.
+    pub async fn import(&self, path: &Path) -> Result<AdvisoryImportSummary, AppError> {
+        let mut summary = AdvisoryImportSummary::default();
+        let mut advisories = Vec::new();
+        let mut withdrawn = Vec::new();
+
+        for_each_json_file(path, &mut |name, content| {
+            let advisory: OsvAdvisory = match serde_json::from_slice(content) {
+                Ok(advisory) => advisory,
+                Err(err) => {
+                    eprintln!("Skipping {name}: {err}");
+                    summary.invalid += 1;
+                    return;
+                }
+            };
+
+            if advisory.withdrawn.is_some() {
+                withdrawn.push(advisory.id);
+                summary.withdrawn += 1;
+                return;
+            }
+            match advisory.into_advisory() {
+                Some(advisory) => {
+                    advisories.push(advisory);
+                    summary.imported += 1;
+                }
+                None => summary.ignored += 1,
+            }
+        })?;
+
+        self.advisory_repository
+            .save_advisories(&advisories)
+            .await?;
+        self.advisory_repository
+            .delete_advisories(&withdrawn)
+            .await?;
+
+        Ok(summary)
+    }
+
+    /// Dependencies of every repository with a version affected by an
+    /// imported advisory, most severe first. Reads the database only.
+    pub async fn vulnerabilities(&self) -> Result<Vec<Vulnerability>, AppError> {
+        let mut vulnerabilities = self.advisory_repository.find_vulnerabilities().await?;
+
+        // An advisory may list the same package more than once
+        vulnerabilities.dedup_by(|a, b| {
+            (&a.path, &a.manifest, &a.name, &a.version, &a.advisory_id)
+                == (&b.path, &b.manifest, &b.name, &b.version, &b.advisory_id)
+        });
+        vulnerabilities.sort_by(|a, b| b.severity.cmp(&a.severity).then(a.path.cmp(&b.path)));
+
+        Ok(vulnerabilities)
+    }
+}
+
+/// Calls `f` with the name and content of every `.json` file below a
+/// directory or in a zip file.
+fn for_each_json_file(path: &Path, f: &mut dyn FnMut(&str, &[u8])) -> Result<(), AppError> {
+    if path.is_dir() {
+        let mut entries: Vec<_> = std::fs::read_dir(path)?.collect::<Result<_, _>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let entry_path = entry.path();
+            if entry_path.is_dir() {
+                for_each_json_file(&entry_path, f)?;
+            } else if entry_path.extension().is_some_and(|ext| ext == "json") {
+                let content = std::fs::read(&entry_path)?;
+                f(&entry_path.display().to_string(), &content);
+            }
+        }
+        return Ok(());
+    }
+
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        if !file.is_file() || !file.name().ends_with(".json") {
+            continue;
+        }
+
+        let mut content = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut content)?;
+        f(file.name(), &content);
+    }
+    Ok(())
+}
@@ -1,123 +1,620 @@
-use indicatif::{ProgressBar, ProgressStyle};
+use std::future::Future;
+
+use indicatif::ProgressBar;
+use time::{Duration, OffsetDateTime};
+use tokio::sync::mpsc;
 
 use crate::{
-    domain::repository::{Codebase, NewCodebase},
+    domain::{
+        branch_protection::{AccessLevel, NewBranchRule},
+        repository::{Codebase, CodebaseRecord, NewCodebase, SearchResult, SecuritySettings},
+        search_query::SearchQuery,
+        sync::{SyncKind, SyncOptions, SyncOrder, SyncRun, SyncStatus},
+    },
     error::AppError,
     infrastructure::{
-        api::gitlab::client::{
-            GitLabClient,
-            model::{Project, Visibility},
+        api::{
+            github::client::{GitHubClient, model::Repository},
+            gitlab::client::{
+                GitLabClient,
+                model::{AccessLevelConnection, BranchRule, Project, Visibility},
+            },
         },
         utils::progress_bar::style_progress_bar,
     },
-    repository::codebase_repository::CodebaseRepository,
+    repository::{codebase_repository::CodebaseRepository, sync_repository::SyncRepository},
 };
 
+/// Incremental runs look back this far beyond the watermark, in case updates
+/// are committed upstream out of order.
+const WATERMARK_OVERLAP: Duration = Duration::hours(1);
+
+/// Scopes that let a GitLab token read groups and projects.
+const READ_API_SCOPES: [&str; 2] = ["read_api", "api"];
+
+/// One page of codebases fetched from an upstream API.
+struct SyncPage {
+    total_count: i64,
+    records: Vec<CodebaseRecord>,
+    end_cursor: Option<String>,
+    has_next_page: bool,
+}
+
 pub struct CodebaseService {
     codebase_repository: Box<dyn CodebaseRepository>,
-    gitlab_client: GitLabClient,
+    sync_repository: Box<dyn SyncRepository>,
 }
 
 impl CodebaseService {
     pub fn new(
         codebase_repository: Box<dyn CodebaseRepository>,
-        gitlab_client: GitLabClient,
+        sync_repository: Box<dyn SyncRepository>,
     ) -> Self {
         Self {
             codebase_repository,
-            gitlab_client,
+            sync_repository,
         }
     }
 
-    pub async fn update_from_gitlab(&self, group_id: &str) -> Result<(), AppError> {
-        let progress_bar = ProgressBar::new_spinner();
-        style_progress_bar(&progress_bar);
+    /// Fails fast if the token can't sync because it was rejected, expired,
+    /// was revoked or lacks `read_api`, and warns if it expires within
+    /// `expiry_warning` days.
+    pub async fn check_gitlab_token(
+        &self,
+        gitlab_client: &GitLabClient,
+        expiry_warning: i64,
+    ) -> Result<(), AppError> {
+        let token = match gitlab_client.get_token().await {
+            Ok(token) => Some(token),
+            // Not introspectable, looking up the user still validates it
+            Err(AppError::NotFound(_)) => None,
+            Err(err) => return Err(err),
+        };
+        let today = OffsetDateTime::now_utc().date();
 
-        let mut cursor = None;
-        let mut total_processed = 0;
+        if let Some(token) = &token {
+            if token.revoked {
+                return Err(AppError::AuthError(format!(
+                    "GitLab token '{}' was revoked",
+                    token.name
+                )));
+            }
+            if let Some(expires_at) = token.expires_at
+                && expires_at <= today
+            {
+                return Err(AppError::AuthError(format!(
+                    "GitLab token '{}' expired on {expires_at}",
+                    token.name
+                )));
+            }
+            if !token.active {
+                return Err(AppError::AuthError(format!(
+                    "GitLab token '{}' is inactive",
+                    token.name
+                )));
+            }
+            if !token
+                .scopes
+                .iter()
+                .any(|scope| READ_API_SCOPES.contains(&scope.as_str()))
+            {
+                let scopes = if token.scopes.is_empty() {
+                    "none".to_string()
+                } else {
+                    token.scopes.join(", ")
+                };
+                return Err(AppError::AuthError(format!(
+                    "GitLab token '{}' lacks the read_api scope needed to sync, its scopes are {scopes}",
+                    token.name
+                )));
+            }
+        }
 
-        loop {
-            let response = self
-                .gitlab_client
-                .get_projects_after(group_id, cursor.as_deref())
-                .await?;
+        let user = gitlab_client.get_current_user().await?;
 
-            let projects = response.data.group.projects;
-            let page_info = projects.page_info;
+        if let Some(token) = &token
+            && let Some(expires_at) = token.expires_at
+        {
+            let days = (expires_at - today).whole_days();
+            if days <= expiry_warning {
+                eprintln!(
+                    "Warning: GitLab token '{}' of @{} expires in {days} days, on {expires_at}",
+                    token.name, user.username
+                );
+            }
+        }
 
-            // Update progress bar if we know the total
-            if total_processed == 0 && projects.count > 0 {
-                progress_bar.set_length(projects.count as u64);
-                progress_bar.set_style(ProgressStyle::with_template(
-                "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} ({eta})"
-            ).unwrap());
+        Ok(())
+    }
+
+    pub async fn update_from_gitlab(
+        &self,
+        gitlab_client: &GitLabClient,
+        group_id: &str,
+        options: &SyncOptions,
+    ) -> Result<(), AppError> {
+        let (run, stop_at) = self
+            .begin_run(gitlab_client.source(), group_id, options)
+            .await?;
+
+        let client = gitlab_client.clone();
+        let group_id = group_id.to_string();
+
+        let fetch_page = move |cursor: Option<String>, order: Option<SyncOrder>| {
+            let client = client.clone();
+            let group_id = group_id.clone();
+
+            async move {
+                let projects = client
+                    .get_projects_after(&group_id, cursor.as_deref(), order)
+                    .await?;
+
+                Ok(SyncPage {
+                    total_count: projects.count,
+                    records: projects
+                        .nodes
+                        .into_iter()
+                        .map(|project| project_record(project, client.source()))
+                        .collect(),
+                    end_cursor: projects.page_info.end_cursor,
+                    has_next_page: projects.page_info.has_next_page,
+                })
             }
+        };
+
+        self.run_sync(&run, stop_at, options, fetch_page).await
+    }
+
+    pub async fn update_from_github(
+        &self,
+        github_client: &GitHubClient,
+        org: &str,
+        options: &SyncOptions,
+    ) -> Result<(), AppError> {
+        let (run, stop_at) = self.begin_run("github", org, options).await?;
+
+        let client = github_client.clone();
+        let org = org.to_string();
 
-            for project in projects.nodes {
-                // Process project
-                self.process_project(project).await?;
-                total_processed += 1;
-                progress_bar.set_position(total_processed);
+        let fetch_page = move |cursor: Option<String>, order: Option<SyncOrder>| {
+            let client = client.clone();
+            let org = org.clone();
+
+            async move {
+                let repositories = client
+                    .get_repositories_after(&org, cursor.as_deref(), order)
+                    .await?;
+
+                Ok(SyncPage {
+                    total_count: repositories.total_count,
+                    records: repositories
+                        .nodes
+                        .into_iter()
+                        .map(repository_record)
+                        .collect(),
+                    end_cursor: repositories.page_info.end_cursor,
+                    has_next_page: repositories.page_info.has_next_page,
+                })
             }
+        };
 
-            cursor = page_info.end_cursor;
-            if !page_info.has_next_page || cursor.is_none() {
-                break;
+        self.run_sync(&run, stop_at, options, fetch_page).await
+    }
+
+    /// Starts a new run, or with `resume` reopens the last run of the scope if
+    /// it failed or was interrupted. Runs are incremental once a full run of
+    /// the scope has completed, unless `full` or `prune` is set. Also returns
+    /// the timestamp an incremental run stops at.
+    async fn begin_run(
+        &self,
+        source: &str,
+        scope: &str,
+        options: &SyncOptions,
+    ) -> Result<(SyncRun, Option<OffsetDateTime>), AppError> {
+        let watermark = self.sync_repository.find_watermark(source, scope).await?;
+
+        let mut resumed = None;
+        if options.resume {
+            resumed = self
+                .sync_repository
+                .find_resumable_run(source, scope)
+                .await?;
+
+            match &resumed {
+                Some(run) => {
+                    self.sync_repository.reopen_run(run.id).await?;
+                    println!("Resuming sync run {}", run.id);
+                }
+                None => println!(
+                    "No interrupted sync of {scope} to resume, starting from the first page"
+                ),
             }
         }
 
-        progress_bar.finish_with_message(format!("Processed {} repositories", total_processed));
-        Ok(())
-    }
+        let run = match resumed {
+            Some(run) => run,
+            None => {
+                let kind = if options.full || options.prune || watermark.is_none() {
+                    SyncKind::Full
+                } else {
+                    SyncKind::Incremental
+                };
 
-    async fn process_project(&self, project: Project) -> Result<(), AppError> {
-        let (external_id, source) = {
-            let parts: Vec<&str> = project.id.split('/').collect();
-            assert_eq!(parts.len(), 5);
+                self.sync_repository.start_run(source, scope, kind).await?
+            }
+        };
 
-            let src = parts[2].to_string();
-            let id: i64 = parts[4].parse().unwrap();
+        let stop_at = match run.kind {
+            SyncKind::Full => None,
+            SyncKind::Incremental => watermark.map(|watermark| watermark - WATERMARK_OVERLAP),
+        };
 
-            (id, src)
+        Ok((run, stop_at))
+    }
+
+    async fn run_sync<F, Fut>(
+        &self,
+        run: &SyncRun,
+        stop_at: Option<OffsetDateTime>,
+        options: &SyncOptions,
+        fetch_page: F,
+    ) -> Result<(), AppError>
+    where
+        F: FnMut(Option<String>, Option<SyncOrder>) -> Fut + Clone + Send + 'static,
+        Fut: Future<Output = Result<SyncPage, AppError>> + Send,
+    {
+        let mut result = match stop_at {
+            // Both passes stop early, an interrupted run simply starts over
+            Some(stop_at) => {
+                let mut result = Ok(());
+                for order in [SyncOrder::Activity, SyncOrder::Update] {
+                    result = self
+                        .ingest_pages(
+                            run,
+                            None,
+                            Some((order, stop_at)),
+                            options,
+                            fetch_page.clone(),
+                        )
+                        .await;
+                    if result.is_err() {
+                        break;
+                    }
+                }
+                result
+            }
+            None => {
+                self.ingest_pages(run, run.end_cursor.clone(), None, options, fetch_page)
+                    .await
+            }
         };
+        // Only a full run can tell which codebases disappeared
+        if result.is_ok() && run.kind == SyncKind::Full {
+            result = self.remove_unseen(run, options).await;
+        }
+        if result.is_ok() {
+            result = self
+                .sync_repository
+                .save_watermark(run.id)
+                .await
+                .map_err(AppError::Database);
+        }
 
-        let new_codebase = NewCodebase {
-            external_id,
-            source,
-            path: project.full_path,
-            description: project.description,
-            created_at: project.created_at,
-            updated_at: project.updated_at,
-            pushed_at: project.last_activity_at,
-            web_url: project.web_url,
-            private: !matches!(project.visibility, Visibility::Public),
-            archived: project.archived,
-            size: project.statistics.repository_size as i64,
-            commit_count: project.statistics.commit_count as i64,
+        let status = match result {
+            Ok(()) => SyncStatus::Completed,
+            Err(_) => SyncStatus::Failed,
         };
+        self.sync_repository.finish_run(run.id, status).await?;
+
+        result
+    }
+
+    /// Fetches pages one after another in a background task while the previous
+    /// ones are being written, with up to `options.prefetch` pages buffered
+    /// ahead of the writer. Every page is persisted in a single transaction, after which the
+    /// run's cursor is saved so that an interrupted run can be resumed.
+    ///
+    /// Incremental passes receive codebases most recent first in the pass's
+    /// order and stop at the first one older than its timestamp.
+    async fn ingest_pages<F, Fut>(
+        &self,
+        run: &SyncRun,
+        mut cursor: Option<String>,
+        stop_at: Option<(SyncOrder, OffsetDateTime)>,
+        options: &SyncOptions,
+        mut fetch_page: F,
+    ) -> Result<(), AppError>
+    where
+        F: FnMut(Option<String>, Option<SyncOrder>) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<SyncPage, AppError>> + Send,
+    {
+        let (sender, mut receiver) = mpsc::channel(options.prefetch.max(1));
 
-        let codebase = self.codebase_repository.save(new_codebase).await?;
+        let order = stop_at.map(|(order, _)| order);
 
-        for project_language in &project.languages {
-            let lang = (project_language.name.as_str(), project_language.share);
+        let fetcher = tokio::spawn(async move {
+            loop {
+                let mut page = fetch_page(cursor.take(), order).await;
 
+                if let (Ok(page), Some((order, stop_at))) = (&mut page, stop_at) {
+                    let fetched = page.records.len();
+                    page.records.retain(|record| match order {
+                        SyncOrder::Activity => record.codebase.pushed_at >= stop_at,
+                        SyncOrder::Update => record.codebase.updated_at >= stop_at,
+                    });
+
+                    if page.records.len() < fetched {
+                        page.has_next_page = false;
+                    }
+                }
+
+                let next_cursor = match &page {
+                    Ok(page) if page.has_next_page => page.end_cursor.clone(),
+                    _ => None,
+                };
+
+                // The writer hung up, it ran into an error
+                if sender.send(page).await.is_err() {
+                    break;
+                }
+
+                match next_cursor {
+                    Some(next_cursor) => cursor = Some(next_cursor),
+                    None => break,
+                }
+            }
+        });
+
+        let progress_bar = ProgressBar::new_spinner();
+        style_progress_bar(&progress_bar);
+
+        // Codebases already written before the run was interrupted
+        let mut total_processed = self.sync_repository.count_snapshots(run.id).await? as u64;
+        // The total of an incremental run is unknown up front
+        let mut known_total = run.kind == SyncKind::Incremental;
+
+        while let Some(page) = receiver.recv().await {
+            let page = page?;
+
+            // Update progress bar if we know the total
+            if !known_total && page.total_count > 0 {
+                progress_bar.set_length(page.total_count as u64);
+                known_total = true;
+            }
+
+            let saved = page.records.len() as u64;
             self.codebase_repository
-                .add_language(&codebase, lang)
+                .save_batch(run.id, page.records)
                 .await?;
+
+            if let Some(end_cursor) = &page.end_cursor {
+                self.sync_repository.save_cursor(run.id, end_cursor).await?;
+            }
+
+            total_processed += saved;
+            progress_bar.set_position(total_processed);
+        }
+
+        fetcher
+            .await
+            .map_err(|err| AppError::IOError(std::io::Error::other(err)))?;
+
+        // Codebases seen by both passes of an incremental run count once
+        let total_processed = self.sync_repository.count_snapshots(run.id).await?;
+        progress_bar.finish_with_message(format!("Processed {total_processed} repositories"));
+        Ok(())
+    }
+
+    /// After a complete run, flags (or with `prune` deletes) every codebase of
+    /// the run's scope that no longer exists upstream.
+    async fn remove_unseen(&self, run: &SyncRun, options: &SyncOptions) -> Result<(), AppError> {
+        let deleted = self.sync_repository.mark_unseen_deleted(run).await?;
+        if deleted > 0 {
+            println!("Marked {deleted} repositories as deleted");
+        }
+
+        if options.prune {
+            let pruned = self.sync_repository.prune_deleted(run).await?;
+            if pruned > 0 {
+                println!("Pruned {pruned} deleted repositories");
+            }
         }
 
         Ok(())
     }
 
+    /// Codebases of `source` in the group with full path `group` or its
+    /// subgroups. Paths are compared case-insensitively like GitLab does.
+    pub async fn find_in_group(
+        &self,
+        source: &str,
+        group: &str,
+    ) -> Result<Vec<Codebase>, AppError> {
+        let prefix = format!("{}/", group.to_lowercase());
+
+        Ok(self
+            .codebase_repository
+            .find_all()
+            .await?
+            .into_iter()
+            .filter(|codebase| {
+                codebase.source == source && codebase.path.to_lowercase().starts_with(&prefix)
+            })
+            .collect())
+    }
+
     pub async fn search(
         &self,
         query: &str,
         include_archived: bool,
         limit: i64,
-    ) -> Result<Vec<Codebase>, AppError> {
+    ) -> Result<Vec<SearchResult>, AppError> {
+        let query: SearchQuery = query.parse()?;
+
         self.codebase_repository
-            .search(query, include_archived, limit)
+            .search(&query, include_archived, limit)
             .await
             .map_err(AppError::Database)
     }
 }
+
+fn project_record(project: Project, source: &str) -> CodebaseRecord {
+    let external_id: i64 = {
+        let parts: Vec<&str> = project.id.split('/').collect();
+        assert_eq!(parts.len(), 5);
+
+        parts[4].parse().unwrap()
+    };
+
+    let default_branch = project
+        .repository
+        .and_then(|repository| repository.root_ref);
+
+    let codebase = NewCodebase {
+        external_id,
+        source: source.to_string(),
+        path: project.full_path,
+        description: project.description,
+        created_at: project.created_at,
+        updated_at: project.updated_at,
+        pushed_at: project.last_activity_at,
+        web_url: project.web_url,
+        clone_url: Some(project.ssh_url_to_repo),
+        private: !matches!(project.visibility, Visibility::Public),
+        archived: project.archived,
+        size: project.statistics.repository_size as i64,
+        commit_count: project.statistics.commit_count as i64,
+        default_branch,
+    };
+
+    let languages = project
+        .languages
+        .into_iter()
+        .map(|language| (language.name, language.share))
+        .collect();
+
+    // GitLab answers with null rules if the token may not read them. When
+    // there are more rules than fit a page the default branch's may be
+    // missing, so the rules are only known if it is among them.
+    let rules = project.branch_rules.and_then(|connection| {
+        let complete = !connection.page_info.has_next_page
+            || connection.nodes.iter().any(|rule| rule.is_default);
+        complete.then_some(connection.nodes)
+    });
+
+    // Approval rules of the "All branches" rule apply to the default branch too
+    let approvals_required = rules
+        .iter()
+        .flatten()
+        .filter(|rule| rule.is_default || rule.name == "All branches")
+        .filter_map(|rule| rule.approval_rules.as_ref())
+        .flat_map(|connection| &connection.nodes)
+        .map(|approval_rule| approval_rule.approvals_required)
+        .max();
+
+    let security_settings = SecuritySettings {
+        approvals_required,
+        secret_push_protection: project.secret_push_protection_enabled,
+        dependency_scanning: project
+            .security_scanners
+            .and_then(|scanners| scanners.enabled)
+            .map(|enabled| {
+                enabled
+                    .iter()
+                    .any(|scanner| scanner == "DEPENDENCY_SCANNING")
+            }),
+        pipeline_must_succeed: project.only_allow_merge_if_pipeline_succeeds,
+    };
+
+    CodebaseRecord {
+        codebase,
+        languages,
+        branch_rules: rules.map(|rules| rules.into_iter().map(branch_rule).collect()),
+        security_settings: Some(security_settings),
+    }
+}
+
+fn branch_rule(rule: BranchRule) -> NewBranchRule {
+    let access_levels = |connection: Option<AccessLevelConnection>| {
+        connection
+            .map(|connection| connection.nodes)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|level| AccessLevel {
+                level: level.access_level,
+                description: level.access_level_description,
+            })
+            .collect()
+    };
+
+    let mut new_rule = NewBranchRule {
+        name: rule.name,
+        is_default: rule.is_default,
+        // Rules without protection settings, e.g. approval-only rules,
+        // protect nothing
+        is_protected: rule.is_protected && rule.branch_protection.is_some(),
+        allow_force_push: false,
+        code_owner_approval_required: false,
+        push_access_levels: Vec::new(),
+        merge_access_levels: Vec::new(),
+    };
+
+    if let Some(protection) = rule.branch_protection {
+        new_rule.allow_force_push = protection.allow_force_push;
+        new_rule.code_owner_approval_required = protection.code_owner_approval_required;
+        new_rule.push_access_levels = access_levels(protection.push_access_levels);
+        new_rule.merge_access_levels = access_levels(protection.merge_access_levels);
+    }
+
+    new_rule
+}
+
+fn repository_record(repository: Repository) -> CodebaseRecord {
+    let default_branch = repository
+        .default_branch_ref
+        .as_ref()
+        .map(|branch| branch.name.clone());
+
+    let commit_count = repository
+        .default_branch_ref
+        .and_then(|branch| branch.target)
+        .and_then(|target| target.history)
+        .map(|history| history.total_count)
+        .unwrap_or_default();
+
+    let codebase = NewCodebase {
+        external_id: repository.database_id,
+        source: "github".to_string(),
+        path: repository.name_with_owner,
+        description: repository.description,
+        created_at: repository.created_at,
+        updated_at: repository.updated_at,
+        // Empty repositories have never been pushed to
+        pushed_at: repository.pushed_at.unwrap_or(repository.created_at),
+        web_url: repository.url,
+        clone_url: Some(repository.ssh_url),
+        private: repository.is_private,
+        archived: repository.is_archived,
+        size: repository.disk_usage.unwrap_or_default() * 1024,
+        commit_count,
+        default_branch,
+    };
+
+    // GitHub reports bytes per language, GitLab a percentage share
+    let total_size = repository.languages.total_size;
+    let languages = repository
+        .languages
+        .edges
+        .into_iter()
+        .filter(|_| total_size > 0)
+        .map(|edge| (edge.node.name, edge.size as f64 * 100.0 / total_size as f64))
+        .collect();
+
+    // Branch protection and security settings are only collected from GitLab
+    CodebaseRecord {
+        codebase,
+        languages,
+        branch_rules: None,
+        security_settings: None,
+    }
+}
@@ -0,0 +1,183 @@
+use indicatif::ProgressBar;
+use time::{Duration, OffsetDateTime};
+
+use crate::{
+    domain::{
+        credential::{
+            CredentialFinding, CredentialKind, CredentialOwner, CredentialUpdateSummary,
+            NewCredential,
+        },
+        repository::Codebase,
+    },
+    error::AppError,
+    infrastructure::{
+        api::gitlab::client::{GitLabClient, model::GroupDetails, owner_endpoint},
+        utils::{progress_bar::style_progress_bar, repositories::display_offset_datetime},
+    },
+    repository::credential_repository::CredentialRepository,
+};
+
+pub struct CredentialService {
+    credential_repository: Box<dyn CredentialRepository>,
+}
+
+impl CredentialService {
+    pub fn new(credential_repository: Box<dyn CredentialRepository>) -> Self {
+        Self {
+            credential_repository,
+        }
+    }
+
+    /// Collects the active access tokens and deploy tokens of `group` and each
+    /// of its subgroups and the access tokens, deploy keys and deploy tokens
+    /// of each of its `codebases`, replacing those stored before. Listing
+    /// credentials needs the Maintainer or Owner role, projects and groups the
+    /// token may not list them of are skipped and keep their stored
+    /// credentials.
+    pub async fn update_from_gitlab(
+        &self,
+        gitlab_client: &GitLabClient,
+        group: &GroupDetails,
+        codebases: &[Codebase],
+    ) -> Result<CredentialUpdateSummary, AppError> {
+        let mut summary = CredentialUpdateSummary::default();
+
+        let subgroups = gitlab_client
+            .get_descendant_groups(&group.full_path)
+            .await?;
+
+        let progress_bar = ProgressBar::new((codebases.len() + subgroups.len()) as u64 + 1);
+        style_progress_bar(&progress_bar);
+
+        let groups = std::iter::once(group)
+            .chain(&subgroups)
+            .map(|group| CredentialOwner {
+                source: gitlab_client.source().to_string(),
+                path: group.full_path.clone(),
+                web_url: group.web_url.clone(),
+                codebase_id: None,
+            });
+        let owners = groups.chain(codebases.iter().map(|codebase| CredentialOwner {
+            source: codebase.source.clone(),
+            path: codebase.path.clone(),
+            web_url: codebase.web_url.clone(),
+            codebase_id: Some(codebase.id),
+        }));
+        let project_ids = std::iter::repeat_n(None, subgroups.len() + 1)
+            .chain(codebases.iter().map(|codebase| Some(codebase.external_id)));
+
+        for (owner, project_id) in owners.zip(project_ids) {
+            match fetch_credentials(gitlab_client, &owner, project_id).await {
+                Ok(credentials) => {
+                    self.credential_repository
+                        .replace_credentials(&owner, &credentials)
+                        .await?;
+                    summary.owners += 1;
+                    summary.credentials += credentials.len() as u64;
+                }
+                Err(err @ (AppError::AuthError(_) | AppError::NotFound(_))) => {
+                    progress_bar.suspend(|| eprintln!("Skipping {}: {err}", owner.path));
+                    summary.skipped += 1;
+                }
+                Err(err) => return Err(err),
+            }
+            progress_bar.inc(1);
+        }
+
+        progress_bar.finish_and_clear();
+
+        Ok(summary)
+    }
+
+    /// Stored credentials that never expire, deploy credentials with write
+    /// scopes and access tokens unused for `unused_days`.
+    pub async fn findings(&self, unused_days: i64) -> Result<Vec<CredentialFinding>, AppError> {
+        let now = OffsetDateTime::now_utc();
+        let unused_after = Duration::days(unused_days);
+
+        Ok(self
+            .credential_repository
+            .find_all()
+            .await?
+            .into_iter()
+            .flat_map(|credential| {
+                credential
+                    .issues(now, unused_after)
+                    .into_iter()
+                    .map(move |issue| CredentialFinding {
+                        path: credential.owner_path.clone(),
+                        kind: credential.kind,
+                        name: credential.name.clone(),
+                        issue,
+                        scopes: credential.scopes.clone(),
+                        expires_at: credential.expires_at.map(|date| date.to_string()),
+                        last_used_at: credential
+                            .last_used_at
+                            .as_ref()
+                            .map(display_offset_datetime),
+                        web_url: credential.web_url.clone(),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect())
+    }
+}
+
+/// Active credentials of a project, or of a group if `project_id` is unset.
+/// Groups have no deploy keys.
+async fn fetch_credentials(
+    gitlab_client: &GitLabClient,
+    owner: &CredentialOwner,
+    project_id: Option<i64>,
+) -> Result<Vec<NewCredential>, AppError> {
+    let endpoint = owner_endpoint(project_id, &owner.path);
+    let mut credentials = Vec::new();
+
+    for token in gitlab_client.get_access_tokens(&endpoint).await? {
+        if token.active && !token.revoked {
+            credentials.push(NewCredential {
+                kind: CredentialKind::AccessToken,
+                external_id: token.id,
+                name: token.name,
+                scopes: token.scopes,
+                created_at: token.created_at,
+                expires_at: token.expires_at,
+                last_used_at: token.last_used_at,
+            });
+        }
+    }
+
+    for token in gitlab_client.get_deploy_tokens(&endpoint).await? {
+        if !token.revoked && !token.expired {
+            credentials.push(NewCredential {
+                kind: CredentialKind::DeployToken,
+                external_id: token.id,
+                name: token.name,
+                scopes: token.scopes,
+                created_at: None,
+                expires_at: token.expires_at,
+                last_used_at: None,
+            });
+        }
+    }
+
+    if let Some(project_id) = project_id {
+        for key in gitlab_client.get_deploy_keys(project_id).await? {
+            let mut scopes = vec!["read_repository".to_string()];
+            if key.can_push {
+                scopes.push("write_repository".to_string());
+            }
+            credentials.push(NewCredential {
+                kind: CredentialKind::DeployKey,
+                external_id: key.id,
+                name: key.title,
+                scopes,
+                created_at: key.created_at,
+                expires_at: key.expires_at,
+                last_used_at: None,
+            });
+        }
+    }
+
+    Ok(credentials)
+}
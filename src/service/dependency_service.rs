@@ -0,0 +1,170 @@
+use std::{collections::HashSet, path::PathBuf};
+
+use indicatif::ProgressBar;
+
+use crate::{
+    domain::{
+        dependency::{DependencyUpdateSummary, DependencyUsage, Ecosystem, PackageSpec},
+        manifest::{MAX_MANIFEST_SIZE, is_manifest, parse_manifest},
+        sbom::{SbomScope, SbomSubject},
+        search_query::SearchQuery,
+    },
+    error::AppError,
+    infrastructure::{git::mirror::GitMirror, utils::progress_bar::style_progress_bar},
+    repository::{
+        codebase_repository::CodebaseRepository, dependency_repository::DependencyRepository,
+        mirror_repository::MirrorRepository,
+    },
+};
+
+pub struct DependencyService {
+    codebase_repository: Box<dyn CodebaseRepository>,
+    mirror_repository: Box<dyn MirrorRepository>,
+    dependency_repository: Box<dyn DependencyRepository>,
+}
+
+impl DependencyService {
+    pub fn new(
+        codebase_repository: Box<dyn CodebaseRepository>,
+        mirror_repository: Box<dyn MirrorRepository>,
+        dependency_repository: Box<dyn DependencyRepository>,
+    ) -> Self {
+        Self {
+            codebase_repository,
+            mirror_repository,
+            dependency_repository,
+        }
+    }
+
+    /// Reads the manifests at `HEAD` of every mirrored repository matching
+    /// `query`. Mirrors whose `HEAD` hasn't moved since they were last read
+    /// are skipped unless `force` is set.
+    pub async fn update(
+        &self,
+        query: &str,
+        force: bool,
+    ) -> Result<DependencyUpdateSummary, AppError> {
+        let query: SearchQuery = query.parse()?;
+        let matching: HashSet<i64> = self
+            .codebase_repository
+            .find_matching(&query)
+            .await?
+            .into_iter()
+            .map(|codebase| codebase.id)
+            .collect();
+
+        let mirrors: Vec<_> = self
+            .mirror_repository
+            .find_all()
+            .await?
+            .into_iter()
+            .filter(|mirror| !mirror.deleted && matching.contains(&mirror.codebase_id))
+            .collect();
+
+        let mut summary = DependencyUpdateSummary::default();
+
+        let progress_bar = ProgressBar::new(mirrors.len() as u64);
+        style_progress_bar(&progress_bar);
+
+        for mirror in mirrors {
+            let scanned_commit = self
+                .dependency_repository
+                .find_scanned_commit(mirror.codebase_id)
+                .await?;
+            let dir = PathBuf::from(&mirror.dir);
+
+            let result = tokio::task::spawn_blocking(move || {
+                let Some(git_mirror) = GitMirror::open(&dir) else {
+                    return Err(AppError::NotFound(format!("mirror {}", dir.display())));
+                };
+                // Empty repositories have no manifests
+                let Some(head) = git_mirror.head()? else {
+                    return Ok(None);
+                };
+                if !force && scanned_commit.as_deref() == Some(head.as_str()) {
+                    return Ok(None);
+                }
+
+                let mut dependencies = Vec::new();
+                let oversized = git_mirror.for_each_head_file(
+                    is_manifest,
+                    MAX_MANIFEST_SIZE,
+                    |path, content| {
+                        dependencies.extend(parse_manifest(path, content));
+                    },
+                )?;
+                Ok(Some((head, dependencies, oversized)))
+            })
+            .await
+            .map_err(|err| AppError::IOError(std::io::Error::other(err)))?;
+
+            match result {
+                Ok(Some((head, dependencies, oversized))) => {
+                    for (path, size) in oversized {
+                        progress_bar.suspend(|| {
+                            eprintln!(
+                                "Skipping {path} of {}: {size} bytes exceed the manifest size limit",
+                                mirror.path
+                            )
+                        });
+                    }
+                    self.dependency_repository
+                        .replace_dependencies(mirror.codebase_id, &head, &dependencies)
+                        .await?;
+                    summary.scanned += 1;
+                    summary.dependencies += dependencies.len() as u64;
+                }
+                Ok(None) => summary.unchanged += 1,
+                Err(err) => progress_bar.suspend(|| eprintln!("Skipping {}: {err}", mirror.path)),
+            }
+            progress_bar.inc(1);
+        }
+
+        progress_bar.finish_and_clear();
+        Ok(summary)
+    }
+
+    pub async fn search(
+        &self,
+        package: &PackageSpec,
+        ecosystem: Option<Ecosystem>,
+    ) -> Result<Vec<DependencyUsage>, AppError> {
+        self.dependency_repository
+            .find_usages(&package.name, package.version.as_deref(), ecosystem)
+            .await
+            .map_err(AppError::Database)
+    }
+
+    /// The codebases of `scope` with their languages and dependencies, in
+    /// path order. Codebases whose dependencies weren't read are included
+    /// without any.
+    pub async fn sbom(&self, scope: &SbomScope) -> Result<Vec<SbomSubject>, AppError> {
+        let mut codebases: Vec<_> = self
+            .codebase_repository
+            .find_all()
+            .await?
+            .into_iter()
+            .filter(|codebase| scope.includes(codebase))
+            .collect();
+        if codebases.is_empty() {
+            return Err(AppError::NotFound(format!(
+                "no repositories in {}",
+                scope.name()
+            )));
+        }
+        codebases.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let mut subjects = Vec::with_capacity(codebases.len());
+        for codebase in codebases {
+            subjects.push(SbomSubject {
+                languages: self.codebase_repository.find_languages(codebase.id).await?,
+                dependencies: self
+                    .dependency_repository
+                    .find_by_codebase(codebase.id)
+                    .await?,
+                codebase,
+            });
+        }
+        Ok(subjects)
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+
+use time::{Date, OffsetDateTime};
+
+use crate::{
+    domain::sync::{CodebaseChange, Since, SyncRun, diff_snapshots},
+    error::AppError,
+    repository::sync_repository::SyncRepository,
+};
+
+pub struct HistoryService {
+    sync_repository: Box<dyn SyncRepository>,
+}
+
+impl HistoryService {
+    pub fn new(sync_repository: Box<dyn SyncRepository>) -> Self {
+        Self { sync_repository }
+    }
+
+    /// Reports changes between a baseline run and the latest completed run of
+    /// every source and scope. Without `since` the baseline is the previous
+    /// completed run.
+    pub async fn diff(
+        &self,
+        since: Option<Since>,
+        min_growth_percent: f64,
+    ) -> Result<Vec<CodebaseChange>, AppError> {
+        let mut runs_by_scope: BTreeMap<(String, String), Vec<SyncRun>> = BTreeMap::new();
+        for run in self.sync_repository.find_completed_runs().await? {
+            runs_by_scope
+                .entry((run.source.clone(), run.scope.clone()))
+                .or_default()
+                .push(run);
+        }
+
+        let mut pairs = Vec::new();
+
+        match since {
+            Some(Since::Run(run_id)) => {
+                let base = self
+                    .sync_repository
+                    .find_run(run_id)
+                    .await?
+                    .ok_or_else(|| AppError::NotFound(format!("sync run {run_id}")))?;
+
+                if let Some(head) = runs_by_scope
+                    .get(&(base.source.clone(), base.scope.clone()))
+                    .and_then(|runs| runs.last())
+                {
+                    pairs.push((base.clone(), head.clone()));
+                }
+            }
+            since => {
+                let cutoff = match since {
+                    // Ages reaching past any representable date include every run
+                    Some(Since::Age(age)) => Some(
+                        OffsetDateTime::now_utc()
+                            .checked_sub(age)
+                            .unwrap_or(Date::MIN.midnight().assume_utc()),
+                    ),
+                    _ => None,
+                };
+
+                for runs in runs_by_scope.values() {
+                    let Some(head) = runs.last() else { continue };
+
+                    let base = match cutoff {
+                        // Latest run before the cutoff, or the first run if
+                        // syncing started after it
+                        Some(cutoff) => runs
+                            .iter()
+                            .rev()
+                            .find(|run| run.started_at <= cutoff)
+                            .or(runs.first()),
+                        None => runs.iter().rev().nth(1),
+                    };
+
+                    if let Some(base) = base {
+                        pairs.push((base.clone(), head.clone()));
+                    }
+                }
+            }
+        }
+
+        let mut changes = Vec::new();
+
+        for (base_run, head_run) in pairs {
+            if base_run.id >= head_run.id {
+                continue;
+            }
+
+            let base = self.sync_repository.find_snapshots_as_of(&base_run).await?;
+            let head = self.sync_repository.find_snapshots_as_of(&head_run).await?;
+
+            changes.extend(diff_snapshots(
+                &base_run,
+                &head_run,
+                &base,
+                &head,
+                min_growth_percent,
+            ));
+        }
+
+        Ok(changes)
+    }
+}
@@ -0,0 +1,282 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use indicatif::ProgressBar;
+use tokio::task::JoinSet;
+
+use crate::{
+    domain::{
+        mirror::{
+            DiskQuota, Mirror, MirrorOptions, MirrorPruneSummary, MirrorSyncSummary, NewMirror,
+        },
+        repository::Codebase,
+        search_query::SearchQuery,
+    },
+    error::AppError,
+    infrastructure::{
+        git::mirror::{GitMirror, find_mirror_dirs, is_below, mirror_path},
+        utils::progress_bar::style_progress_bar,
+    },
+    repository::{codebase_repository::CodebaseRepository, mirror_repository::MirrorRepository},
+};
+
+/// How long an unfinished clone is assumed to be still running.
+const PARTIAL_CLONE_GRACE: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub struct MirrorService {
+    codebase_repository: Box<dyn CodebaseRepository>,
+    mirror_repository: Box<dyn MirrorRepository>,
+}
+
+impl MirrorService {
+    pub fn new(
+        codebase_repository: Box<dyn CodebaseRepository>,
+        mirror_repository: Box<dyn MirrorRepository>,
+    ) -> Self {
+        Self {
+            codebase_repository,
+            mirror_repository,
+        }
+    }
+
+    /// Clones or fetches the mirror of every repository matching `query`.
+    /// Existing mirrors are fetched first, then new ones are cloned, most
+    /// recently pushed first, as long as they fit the quota.
+    pub async fn sync(
+        &self,
+        query: &str,
+        options: &MirrorOptions,
+    ) -> Result<MirrorSyncSummary, AppError> {
+        let query: SearchQuery = query.parse()?;
+        let root = &options.mirrors_dir;
+        // Mirrors stored outside the mirror directory are cloned anew
+        let mirrors: HashMap<i64, Mirror> = self
+            .mirror_repository
+            .find_all()
+            .await?
+            .into_iter()
+            .filter(|mirror| is_below(Path::new(&mirror.dir), root))
+            .map(|mirror| (mirror.codebase_id, mirror))
+            .collect();
+
+        let mut codebases: Vec<(Codebase, String)> = self
+            .codebase_repository
+            .find_matching(&query)
+            .await?
+            .into_iter()
+            .filter_map(|codebase| {
+                let url = options.transport.clone_url(&codebase)?;
+                Some((codebase, url))
+            })
+            .collect();
+        codebases.sort_by(|(a, _), (b, _)| {
+            let a_mirrored = mirrors.contains_key(&a.id);
+            let b_mirrored = mirrors.contains_key(&b.id);
+            b_mirrored
+                .cmp(&a_mirrored)
+                .then(b.pushed_at.cmp(&a.pushed_at))
+        });
+
+        // Bytes on disk, with the upstream size of clones in flight
+        let mut used: i64 = mirrors.values().map(|mirror| mirror.size).sum();
+        let mut summary = MirrorSyncSummary::default();
+
+        let progress_bar = ProgressBar::new(codebases.len() as u64);
+        style_progress_bar(&progress_bar);
+
+        let mut pending = codebases.into_iter();
+        let mut tasks = JoinSet::new();
+
+        loop {
+            while tasks.len() < options.concurrency.max(1) {
+                let Some((codebase, url)) = pending.next() else {
+                    break;
+                };
+
+                let existing = mirrors.get(&codebase.id);
+                let estimate = match existing {
+                    Some(mirror) => mirror.size,
+                    None => codebase.size,
+                };
+
+                if existing.is_none()
+                    && options
+                        .quota
+                        .is_some_and(|DiskQuota(quota)| used + estimate > quota)
+                {
+                    summary.over_quota += 1;
+                    progress_bar.inc(1);
+                    continue;
+                }
+                if existing.is_none() {
+                    used += estimate;
+                }
+
+                let dir = mirror_dir(root, &codebase, existing);
+                let cloned = existing.is_none();
+                tasks.spawn_blocking(move || {
+                    let result = fetch_mirror(&codebase, &url, &dir);
+                    (codebase, estimate, cloned, result)
+                });
+            }
+
+            let Some(task) = tasks.join_next().await else {
+                break;
+            };
+            let (codebase, estimate, cloned, result) =
+                task.map_err(|err| AppError::IOError(std::io::Error::other(err)))?;
+
+            match result {
+                Ok((_, mirror)) => {
+                    used += mirror.size - estimate;
+                    self.mirror_repository.save(&mirror).await?;
+
+                    if cloned {
+                        summary.cloned += 1;
+                    } else {
+                        summary.fetched += 1;
+                    }
+                }
+                Err(err) => {
+                    if cloned {
+                        used -= estimate;
+                    }
+                    summary.failed += 1;
+                    progress_bar.suspend(|| eprintln!("Skipping {}: {err}", codebase.path));
+                }
+            }
+            progress_bar.inc(1);
+        }
+
+        progress_bar.finish_and_clear();
+        Ok(summary)
+    }
+
+    /// Removes mirrors of repositories deleted upstream and directories below
+    /// `mirrors_dir` no longer tracked, except clones started within a day.
+    /// With a quota, mirrors of the least recently pushed repositories are
+    /// evicted until the rest fits. Nothing outside `mirrors_dir` is removed.
+    pub async fn prune(
+        &self,
+        mirrors_dir: &Path,
+        quota: Option<DiskQuota>,
+    ) -> Result<MirrorPruneSummary, AppError> {
+        let mut summary = MirrorPruneSummary::default();
+        let mut kept = Vec::new();
+
+        for mirror in self.mirror_repository.find_all().await? {
+            if mirror.deleted {
+                self.remove(mirrors_dir, &mirror, &mut summary).await?;
+            } else {
+                kept.push(mirror);
+            }
+        }
+
+        let tracked: HashSet<PathBuf> = kept
+            .iter()
+            .map(|mirror| PathBuf::from(&mirror.dir))
+            .collect();
+        for dir in find_mirror_dirs(mirrors_dir)? {
+            if tracked.contains(&dir) || is_recent_clone(&dir)? || !is_below(&dir, mirrors_dir) {
+                continue;
+            }
+            if let Some(orphan) = GitMirror::open(&dir) {
+                summary.freed += orphan.disk_size()?;
+                std::fs::remove_dir_all(&dir)?;
+                summary.removed += 1;
+            }
+        }
+
+        if let Some(DiskQuota(quota)) = quota {
+            let mut used: i64 = kept.iter().map(|mirror| mirror.size).sum();
+
+            // Most recently pushed first
+            while used > quota {
+                let Some(mirror) = kept.pop() else {
+                    break;
+                };
+                used -= mirror.size;
+                self.remove(mirrors_dir, &mirror, &mut summary).await?;
+            }
+        }
+
+        Ok(summary)
+    }
+
+    pub async fn status(&self) -> Result<Vec<Mirror>, AppError> {
+        self.mirror_repository
+            .find_all()
+            .await
+            .map_err(AppError::Database)
+    }
+
+    async fn remove(
+        &self,
+        mirrors_dir: &Path,
+        mirror: &Mirror,
+        summary: &mut MirrorPruneSummary,
+    ) -> Result<(), AppError> {
+        let dir = Path::new(&mirror.dir);
+        if dir.exists() {
+            if !is_below(dir, mirrors_dir) {
+                eprintln!(
+                    "Not removing {}: outside {}",
+                    dir.display(),
+                    mirrors_dir.display()
+                );
+                return Ok(());
+            }
+            std::fs::remove_dir_all(dir)?;
+        }
+        self.mirror_repository.delete(mirror.codebase_id).await?;
+
+        summary.removed += 1;
+        summary.freed += mirror.size as u64;
+        Ok(())
+    }
+}
+
+/// Where a codebase's mirror lives, where it was cloned to if it was renamed
+/// since. Stored directories outside `root` are never written to.
+pub fn mirror_dir(root: &Path, codebase: &Codebase, existing: Option<&Mirror>) -> PathBuf {
+    match existing {
+        Some(mirror) if is_below(Path::new(&mirror.dir), root) => PathBuf::from(&mirror.dir),
+        _ => mirror_path(root, &codebase.source, &codebase.path),
+    }
+}
+
+/// Whether `dir` is a clone that may still be running.
+fn is_recent_clone(dir: &Path) -> Result<bool, AppError> {
+    if dir
+        .extension()
+        .is_none_or(|extension| extension != "partial")
+    {
+        return Ok(false);
+    }
+    let age = std::fs::metadata(dir)?
+        .modified()?
+        .elapsed()
+        .unwrap_or_default();
+    Ok(age < PARTIAL_CLONE_GRACE)
+}
+
+/// Clones or fetches a mirror and describes its new state. Blocks on `git`.
+pub fn fetch_mirror(
+    codebase: &Codebase,
+    url: &str,
+    dir: &Path,
+) -> Result<(GitMirror, NewMirror), AppError> {
+    let mirror = GitMirror::fetch(url, dir)?;
+
+    let state = NewMirror {
+        codebase_id: codebase.id,
+        dir: dir.display().to_string(),
+        head_commit: mirror.head()?,
+        size: mirror.disk_size()? as i64,
+    };
+
+    Ok((mirror, state))
+}
@@ -1 +1,8 @@
+pub mod advisory_service;
 pub mod codebase_service;
+pub mod credential_service;
+pub mod dependency_service;
+pub mod history_service;
+pub mod mirror_service;
+pub mod policy_service;
+pub mod secret_service;
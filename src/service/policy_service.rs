@@ -0,0 +1,42 @@
+use crate::{
+    domain::{
+        policy::{Policy, Violation},
+        search_query::SearchQuery,
+    },
+    error::AppError,
+    repository::codebase_repository::CodebaseRepository,
+};
+
+pub struct PolicyService {
+    codebase_repository: Box<dyn CodebaseRepository>,
+}
+
+impl PolicyService {
+    pub fn new(codebase_repository: Box<dyn CodebaseRepository>) -> Self {
+        Self {
+            codebase_repository,
+        }
+    }
+
+    /// Evaluates every rule against the synced repositories, archived ones
+    /// included, and returns the violations ordered by rule and path.
+    pub async fn check(&self, policy: &Policy) -> Result<Vec<Violation>, AppError> {
+        let mut violations = Vec::new();
+
+        for rule in &policy.rules {
+            let query: SearchQuery = rule.query.parse()?;
+
+            for codebase in self.codebase_repository.find_matching(&query).await? {
+                violations.push(Violation {
+                    rule_id: rule.id.clone(),
+                    severity: rule.severity,
+                    path: codebase.path,
+                    description: rule.description.clone(),
+                    web_url: codebase.web_url,
+                });
+            }
+        }
+
+        Ok(violations)
+    }
+}
@@ -0,0 +1,308 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use indicatif::ProgressBar;
+use time::OffsetDateTime;
+use tokio::task::JoinSet;
+
+use crate::{
+    domain::{
+        mirror::Mirror,
+        repository::Codebase,
+        search_query::SearchQuery,
+        secret::{
+            Allowlist, MAX_SCANNED_FILE_SIZE, ScanOptions, SecretFinding, SecretScanSummary,
+            blob_url, fingerprint, redact, scan_line,
+        },
+    },
+    error::AppError,
+    infrastructure::{git::mirror::GitMirror, utils::progress_bar::style_progress_bar},
+    repository::{
+        codebase_repository::CodebaseRepository, mirror_repository::MirrorRepository,
+        secret_repository::SecretRepository,
+    },
+    service::mirror_service::{fetch_mirror, mirror_dir},
+};
+
+pub struct SecretService {
+    codebase_repository: Box<dyn CodebaseRepository>,
+    mirror_repository: Box<dyn MirrorRepository>,
+    secret_repository: Box<dyn SecretRepository>,
+}
+
+impl SecretService {
+    pub fn new(
+        codebase_repository: Box<dyn CodebaseRepository>,
+        mirror_repository: Box<dyn MirrorRepository>,
+        secret_repository: Box<dyn SecretRepository>,
+    ) -> Self {
+        Self {
+            codebase_repository,
+            mirror_repository,
+            secret_repository,
+        }
+    }
+
+    /// Fetches the mirror of every repository matching `query` and scans it
+    /// for secrets, or only scans the existing mirrors when offline. Findings
+    /// replace those of the repository's previous scan, repositories that
+    /// fail to fetch keep theirs and are counted as failed.
+    pub async fn scan(
+        &self,
+        query: &str,
+        allowlist: Allowlist,
+        options: &ScanOptions,
+    ) -> Result<SecretScanSummary, AppError> {
+        let query: SearchQuery = query.parse()?;
+        let mirrors: HashMap<i64, Mirror> = self
+            .mirror_repository
+            .find_all()
+            .await?
+            .into_iter()
+            .map(|mirror| (mirror.codebase_id, mirror))
+            .collect();
+
+        let root = &options.mirrors_dir;
+        let mut codebases = Vec::new();
+        for codebase in self.codebase_repository.find_matching(&query).await? {
+            let dir = mirror_dir(root, &codebase, mirrors.get(&codebase.id));
+            let url = if options.offline {
+                if !mirrors.contains_key(&codebase.id) {
+                    continue;
+                }
+                None
+            } else {
+                match options.transport.clone_url(&codebase) {
+                    Some(url) => Some(url),
+                    None => continue,
+                }
+            };
+            codebases.push((codebase, url, dir));
+        }
+
+        let allowlist = Arc::new(allowlist);
+        let history = options.history;
+
+        let progress_bar = ProgressBar::new(codebases.len() as u64);
+        style_progress_bar(&progress_bar);
+
+        let mut pending = codebases.into_iter();
+        let mut tasks = JoinSet::new();
+        let mut summary = SecretScanSummary::default();
+
+        loop {
+            while tasks.len() < options.concurrency.max(1) {
+                let Some((codebase, url, dir)) = pending.next() else {
+                    break;
+                };
+                let allowlist = allowlist.clone();
+
+                tasks.spawn_blocking(move || {
+                    let result = match url {
+                        Some(url) => fetch_mirror(&codebase, &url, &dir)
+                            .map(|(mirror, state)| (mirror, Some(state))),
+                        None => GitMirror::open(&dir)
+                            .map(|mirror| (mirror, None))
+                            .ok_or_else(|| AppError::NotFound(format!("mirror {}", dir.display()))),
+                    }
+                    .and_then(|(mirror, state)| {
+                        let findings = scan_mirror(&codebase, &mirror, &allowlist, history)?;
+                        Ok((findings, state))
+                    });
+                    (codebase, result)
+                });
+            }
+
+            let Some(task) = tasks.join_next().await else {
+                break;
+            };
+            let (codebase, result) =
+                task.map_err(|err| AppError::IOError(std::io::Error::other(err)))?;
+
+            match result {
+                Ok((codebase_findings, state)) => {
+                    if let Some(state) = state {
+                        self.mirror_repository.save(&state).await?;
+                    }
+                    self.secret_repository
+                        .replace_findings(codebase.id, &codebase_findings)
+                        .await?;
+                    summary.findings.extend(codebase_findings);
+                }
+                Err(err) => {
+                    summary.failed += 1;
+                    progress_bar.suspend(|| eprintln!("Skipping {}: {err}", codebase.path));
+                }
+            }
+            progress_bar.inc(1);
+        }
+
+        progress_bar.finish_and_clear();
+
+        summary
+            .findings
+            .sort_by(|a, b| (&a.path, &a.file, a.line).cmp(&(&b.path, &b.file, b.line)));
+        Ok(summary)
+    }
+
+    /// Findings of the latest scan of every repository.
+    pub async fn findings(&self) -> Result<Vec<SecretFinding>, AppError> {
+        self.secret_repository
+            .find_all()
+            .await
+            .map_err(AppError::Database)
+    }
+}
+
+fn scan_mirror(
+    codebase: &Codebase,
+    mirror: &GitMirror,
+    allowlist: &Allowlist,
+    history: bool,
+) -> Result<Vec<SecretFinding>, AppError> {
+    let scanned_at = OffsetDateTime::now_utc();
+    let mut findings = Vec::new();
+    // Secrets still at HEAD are reported there rather than once per commit
+    let mut at_head = HashSet::new();
+
+    let mut check_line = |commit: Option<&str>, file: &str, line: i64, text: &str| {
+        for secret_match in scan_line(text) {
+            let key = (
+                file.to_string(),
+                secret_match.rule.id,
+                secret_match.secret.clone(),
+            );
+            if commit.is_none() {
+                at_head.insert(key);
+            } else if at_head.contains(&key) {
+                continue;
+            }
+
+            let fingerprint = fingerprint(&codebase.path, commit, file, secret_match.rule.id, line);
+            if allowlist.allows(file, &secret_match.secret, &fingerprint) {
+                continue;
+            }
+
+            let rev = commit
+                .or(codebase.default_branch.as_deref())
+                .unwrap_or("HEAD");
+
+            findings.push(SecretFinding {
+                path: codebase.path.clone(),
+                file: file.to_string(),
+                line,
+                rule_id: secret_match.rule.id.to_string(),
+                severity: secret_match.rule.severity,
+                commit_sha: commit.map(str::to_string),
+                secret: redact(&secret_match.secret),
+                fingerprint,
+                scanned_at,
+                web_url: blob_url(&codebase.source, &codebase.web_url, rev, file, line),
+            });
+        }
+    };
+
+    mirror.for_each_head_file(
+        |_| true,
+        MAX_SCANNED_FILE_SIZE,
+        |file, content| {
+            for (index, text) in content.lines().enumerate() {
+                check_line(None, file, index as i64 + 1, text);
+            }
+        },
+    )?;
+
+    if history {
+        mirror.for_each_added_line(|commit, file, line, text| {
+            check_line(Some(commit), file, line, text);
+        })?;
+    }
+
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, process::Command};
+
+    use super::*;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args([
+                "-c",
+                "user.name=devsec",
+                "-c",
+                "user.email=devsec@example.com",
+            ])
+            .args(args)
+            .status()
+            .expect("git must be installed");
+        assert!(status.success(), "git {args:?} failed");
+    }
+
+    fn codebase() -> Codebase {
+        Codebase {
+            id: 1,
+            external_id: 1,
+            source: "gitlab".to_string(),
+            path: "grp/app".to_string(),
+            web_url: "https://gitlab.com/grp/app".to_string(),
+            description: None,
+            created_at: OffsetDateTime::UNIX_EPOCH,
+            updated_at: OffsetDateTime::UNIX_EPOCH,
+            pushed_at: OffsetDateTime::UNIX_EPOCH,
+            size: 0,
+            commit_count: 2,
+            private: true,
+            archived: false,
+            default_branch: Some("main".to_string()),
+            clone_url: None,
+        }
+    }
+
+    #[test]
+    fn scans_a_local_bare_repository() {
+        let root = std::env::temp_dir().join(format!("devsec-scan-{}", std::process::id()));
+        let work = root.join("work");
+        std::fs::create_dir_all(&work).unwrap();
+
+        let token = ["glpat-", "Xy3vQ9rT2kLm8NpW4sZa"].concat();
+        git(&work, &["init", "--quiet"]);
+        std::fs::write(work.join("café.env"), format!("TOKEN={token}\n")).unwrap();
+        std::fs::write(work.join("README.md"), "# app\n").unwrap();
+        git(&work, &["add", "."]);
+        git(&work, &["commit", "--quiet", "-m", "add token"]);
+        std::fs::remove_file(work.join("café.env")).unwrap();
+        std::fs::write(work.join("config.yml"), format!("\ntoken: {token}\n")).unwrap();
+        git(&work, &["add", "--all"]);
+        git(&work, &["commit", "--quiet", "-m", "move token"]);
+        git(&root, &["clone", "--quiet", "--bare", "work", "src.git"]);
+
+        let mirror = GitMirror::fetch(
+            &root.join("src.git").display().to_string(),
+            &root.join("mirror.git"),
+        );
+        let findings = mirror
+            .and_then(|mirror| scan_mirror(&codebase(), &mirror, &Allowlist::default(), true));
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let mut findings: Vec<_> = findings
+            .unwrap()
+            .into_iter()
+            .map(|finding| (finding.file, finding.line, finding.commit_sha.is_some()))
+            .collect();
+        findings.sort();
+        assert_eq!(
+            findings,
+            vec![
+                ("café.env".to_string(), 1, true),
+                ("config.yml".to_string(), 2, false),
+            ]
+        );
+    }
+}
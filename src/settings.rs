@@ -0,0 +1,368 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use toml_edit::{Array, DocumentMut, Item, value};
+
+use crate::error::AppError;
+
+const APP_NAME: &str = "devsec";
+const CONFIG_FILE: &str = "default-config.toml";
+
+/// Written by `config init`, every setting commented out.
+const CONFIG_TEMPLATE: &str = r#"# Top-level settings apply to every profile. A profile selected with
+# `--profile <name>` or DEVSEC_PROFILE overrides the settings it sets.
+# `devsec config set` adds settings above these examples.
+#
+# profile = "client-a"        # profile used when none is selected
+# db = "/srv/devsec/devsec.db"
+# format = "json"             # default output format, table or json
+#
+# [gitlab]
+# url = "https://gitlab.example.com"
+# token_env = "GITLAB_TOKEN"  # environment variable holding the token, or
+# token_file = "/run/secrets/gitlab-token"
+# token_command = "pass show gitlab"
+# token_secret = "gitlab"     # stored with `devsec config set-secret gitlab`
+# groups = ["acme/backend"]   # full paths of groups `update gitlab` syncs without --group-id
+# ca_cert = "/etc/ssl/corp-ca.pem"
+# accept_invalid_certs = false
+# timeout = 30                # seconds per API request
+#
+# [github]
+# token_env = "GITHUB_TOKEN"
+# orgs = ["acme"]             # organizations `update github` syncs without --org
+# timeout = 30
+#
+# [profiles.client-a]
+# db = "/srv/devsec/client-a.db"
+#
+# [profiles.client-a.gitlab]
+# url = "https://gitlab.client-a.com"
+# token_env = "CLIENT_A_GITLAB_TOKEN"
+# groups = ["client-a/platform"]
+"#;
+
+/// Keys `config set` accepts and the type of their values.
+const KEYS: &[(&str, ValueKind)] = &[
+    ("profile", ValueKind::String),
+    ("db", ValueKind::String),
+    ("format", ValueKind::String),
+    ("gitlab.url", ValueKind::String),
+    ("gitlab.token_env", ValueKind::String),
+    ("gitlab.token_file", ValueKind::String),
+    ("gitlab.token_command", ValueKind::String),
+    ("gitlab.token_secret", ValueKind::String),
+    ("gitlab.groups", ValueKind::List),
+    ("gitlab.ca_cert", ValueKind::String),
+    ("gitlab.accept_invalid_certs", ValueKind::Bool),
+    ("gitlab.timeout", ValueKind::Integer),
+    ("github.token_env", ValueKind::String),
+    ("github.token_file", ValueKind::String),
+    ("github.token_command", ValueKind::String),
+    ("github.token_secret", ValueKind::String),
+    ("github.orgs", ValueKind::List),
+    ("github.timeout", ValueKind::Integer),
+];
+
+#[derive(Clone, Copy)]
+enum ValueKind {
+    String,
+    Bool,
+    Integer,
+    /// Comma separated
+    List,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct AppConfig {
+    /// Profile used when `--profile` isn't given
+    pub profile: Option<String>,
+
+    /// Settings of every profile
+    #[serde(flatten)]
+    pub defaults: Profile,
+
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct Profile {
+    /// SQLite database, e.g. a separate inventory per client
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub db: Option<PathBuf>,
+
+    /// Output format of commands supporting `--json`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<OutputFormat>,
+
+    #[serde(default)]
+    pub gitlab: GitLabConfig,
+
+    #[serde(default)]
+    pub github: GitHubConfig,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Table,
+    Json,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct GitLabConfig {
+    /// Base URL of the GitLab instance, e.g. `https://gitlab.example.com`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+
+    #[serde(flatten)]
+    pub token: TokenConfig,
+
+    /// Group ids synced when no group is given
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+
+    /// PEM bundle with additional CA certificates to trust
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<PathBuf>,
+
+    /// Accept invalid (e.g. self-signed) TLS certificates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accept_invalid_certs: Option<bool>,
+
+    /// Timeout of a single API request in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct GitHubConfig {
+    #[serde(flatten)]
+    pub token: TokenConfig,
+
+    /// Organizations synced when no organization is given
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub orgs: Vec<String>,
+
+    /// Timeout of a single API request in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+}
+
+/// Where the token of a service comes from when none is given on the command
+/// line. The first one set is used.
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct TokenConfig {
+    /// Environment variable holding the token
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_env: Option<String>,
+
+    /// File holding the token
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_file: Option<PathBuf>,
+
+    /// Command printing the token, e.g. `pass show gitlab`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_command: Option<String>,
+
+    /// Name of the token in the encrypted secrets file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_secret: Option<String>,
+}
+
+impl AppConfig {
+    /// Settings of the named profile, or of the default profile if any, on
+    /// top of the top-level settings.
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile, AppError> {
+        let Some(name) = name.or(self.profile.as_deref()) else {
+            return Ok(self.defaults.clone());
+        };
+        let profile = self
+            .profiles
+            .get(name)
+            .ok_or_else(|| AppError::NotFound(format!("profile {name}")))?;
+
+        Ok(profile.clone().or(self.defaults.clone()))
+    }
+}
+
+impl Profile {
+    fn or(self, base: Profile) -> Profile {
+        Profile {
+            db: self.db.or(base.db),
+            format: self.format.or(base.format),
+            gitlab: GitLabConfig {
+                url: self.gitlab.url.or(base.gitlab.url),
+                token: self.gitlab.token.or(base.gitlab.token),
+                groups: non_empty(self.gitlab.groups).unwrap_or(base.gitlab.groups),
+                ca_cert: self.gitlab.ca_cert.or(base.gitlab.ca_cert),
+                accept_invalid_certs: self
+                    .gitlab
+                    .accept_invalid_certs
+                    .or(base.gitlab.accept_invalid_certs),
+                timeout: self.gitlab.timeout.or(base.gitlab.timeout),
+            },
+            github: GitHubConfig {
+                token: self.github.token.or(base.github.token),
+                orgs: non_empty(self.github.orgs).unwrap_or(base.github.orgs),
+                timeout: self.github.timeout.or(base.github.timeout),
+            },
+        }
+    }
+
+    /// Whether commands print JSON unless asked otherwise.
+    pub fn prefers_json(&self) -> bool {
+        self.format == Some(OutputFormat::Json)
+    }
+}
+
+impl TokenConfig {
+    /// A profile's token source replaces the top-level one as a whole, so
+    /// that e.g. its `token_env` isn't shadowed by a top-level `token_file`.
+    fn or(self, base: TokenConfig) -> TokenConfig {
+        if self.is_set() { self } else { base }
+    }
+
+    fn is_set(&self) -> bool {
+        self.token_env.is_some()
+            || self.token_file.is_some()
+            || self.token_command.is_some()
+            || self.token_secret.is_some()
+    }
+}
+
+fn non_empty(values: Vec<String>) -> Option<Vec<String>> {
+    (!values.is_empty()).then_some(values)
+}
+
+/// The config file in the user's config directory, e.g.
+/// `~/.config/devsec/default-config.toml` on Linux.
+pub fn config_path() -> Result<PathBuf, AppError> {
+    let proj_dirs = ProjectDirs::from("rs", "", APP_NAME)
+        .ok_or_else(|| AppError::NotFound("home directory".to_string()))?;
+    Ok(proj_dirs.config_dir().join(CONFIG_FILE))
+}
+
+/// Reads the config file, if there is one.
+pub fn load_config() -> Result<AppConfig, AppError> {
+    let path = config_path()?;
+    if !path.exists() {
+        return Ok(AppConfig::default());
+    }
+
+    parse_config(&path, &std::fs::read_to_string(&path)?)
+}
+
+fn parse_config(path: &Path, content: &str) -> Result<AppConfig, AppError> {
+    toml::from_str(content)
+        .map_err(|err| AppError::InvalidConfig(format!("{}: {}", path.display(), err.message())))
+}
+
+/// Writes a commented config file unless there is one.
+pub fn init_config(force: bool) -> Result<PathBuf, AppError> {
+    let path = config_path()?;
+    if path.exists() && !force {
+        return Err(AppError::InvalidConfig(format!(
+            "{} already exists, use --force to overwrite it",
+            path.display()
+        )));
+    }
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, CONFIG_TEMPLATE)?;
+    Ok(path)
+}
+
+/// Sets `key` of a profile, or the top-level setting without a profile,
+/// keeping the rest of the file as it is. An empty value removes the key.
+pub fn set_config(profile: Option<&str>, key: &str, raw: &str) -> Result<PathBuf, AppError> {
+    let Some((_, kind)) = KEYS.iter().find(|(name, _)| *name == key) else {
+        let keys: Vec<&str> = KEYS.iter().map(|(name, _)| *name).collect();
+        return Err(AppError::InvalidConfig(format!(
+            "unknown key {key}, expected one of {}",
+            keys.join(", ")
+        )));
+    };
+    if profile.is_some() && key == "profile" {
+        return Err(AppError::InvalidConfig(
+            "profile is a top-level setting".to_string(),
+        ));
+    }
+
+    let path = config_path()?;
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err.into()),
+    };
+    let mut document: DocumentMut = content
+        .parse()
+        .map_err(|err| AppError::InvalidConfig(format!("{}: {err}", path.display())))?;
+
+    let mut table = document.as_table_mut();
+    if let Some(profile) = profile {
+        let profiles = table
+            .entry("profiles")
+            .or_insert_with(toml_edit::table)
+            .as_table_mut()
+            .ok_or_else(|| AppError::InvalidConfig("profiles must be a table".to_string()))?;
+        profiles.set_implicit(true);
+        table = profiles
+            .entry(profile)
+            .or_insert_with(toml_edit::table)
+            .as_table_mut()
+            .ok_or_else(|| AppError::InvalidConfig(format!("profile {profile} must be a table")))?;
+    }
+
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let name = parts.pop().unwrap_or(key);
+    for part in parts {
+        table = table
+            .entry(part)
+            .or_insert_with(toml_edit::table)
+            .as_table_mut()
+            .ok_or_else(|| AppError::InvalidConfig(format!("{part} must be a table")))?;
+    }
+
+    if raw.is_empty() {
+        table.remove(name);
+    } else {
+        table.insert(name, parse_value(key, *kind, raw)?);
+    }
+
+    let content = document.to_string();
+    // Catch values the config can't hold, e.g. an unknown format
+    parse_config(&path, &content)?;
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, content)?;
+    Ok(path)
+}
+
+fn parse_value(key: &str, kind: ValueKind, raw: &str) -> Result<Item, AppError> {
+    let invalid =
+        |expected: &str| AppError::InvalidConfig(format!("{key} must be {expected}, got '{raw}'"));
+
+    Ok(match kind {
+        ValueKind::String => value(raw),
+        ValueKind::Bool => value(raw.parse::<bool>().map_err(|_| invalid("true or false"))?),
+        ValueKind::Integer => value(raw.parse::<i64>().map_err(|_| invalid("a number"))?),
+        ValueKind::List => {
+            let values: Array = raw
+                .split(',')
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .collect();
+            value(values)
+        }
+    })
+}